@@ -0,0 +1,262 @@
+use crate::backend::{self, BackendFuture, LlmBackend, assistant_response, http_error, parse_error, parse_sse};
+use crate::llm::LlmError;
+use crate::models::{ChatRequest, ChatResponse, FunctionCall, ToolCall, Usage};
+use crate::transport::{HttpClient, HttpRequest};
+
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use std::time::Duration;
+
+pub const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+pub const API_VERSION: &str = "2023-06-01";
+
+// Messages API は返答の上限の指定が必須
+const MAX_TOKENS: u32 = 1024;
+
+/// Anthropic Messages API
+pub struct AnthropicBackend {
+    http: HttpClient,
+    api_key: String,
+    base_url: String,
+    stream: bool,
+}
+
+impl AnthropicBackend {
+    pub fn new(http: HttpClient, api_key: &str, base_url: &str) -> Self {
+        Self {
+            http,
+            api_key: api_key.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            stream: false,
+        }
+    }
+
+    pub fn streaming(mut self, stream: bool) -> Self {
+        self.stream = stream;
+        self
+    }
+}
+
+fn tool_input(arguments: &str) -> Value {
+    serde_json::from_str(arguments).unwrap_or_else(|_| json!({}))
+}
+
+/// ChatRequest を Messages API の形にする
+/// - system はメッセージから外して system に入れる
+/// - assistant のツール呼び出しは tool_use、tool の結果は user の tool_result にする
+/// - 同じ役割が続いたら1つにまとめる（user / assistant は交互でないといけない）
+pub fn to_request_body(request: &ChatRequest, stream: bool) -> Value {
+    let system = request
+        .messages
+        .iter()
+        .filter(|m| m.role == "system")
+        .map(|m| m.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let mut messages: Vec<(String, Vec<Value>)> = Vec::new();
+    for m in request.messages.iter().filter(|m| m.role != "system") {
+        let (role, blocks) = match m.role.as_str() {
+            "tool" => (
+                "user",
+                vec![json!({ "type": "tool_result", "tool_use_id": m.tool_call_id, "content": m.content })],
+            ),
+            "assistant" => {
+                let mut blocks = Vec::new();
+                if !m.content.is_empty() {
+                    blocks.push(json!({ "type": "text", "text": m.content }));
+                }
+                for call in m.tool_calls.iter().flatten() {
+                    blocks.push(json!({
+                        "type": "tool_use",
+                        "id": call.id,
+                        "name": call.function.name,
+                        "input": tool_input(&call.function.arguments),
+                    }));
+                }
+                ("assistant", blocks)
+            }
+            _ => ("user", vec![json!({ "type": "text", "text": m.content })]),
+        };
+
+        match messages.last_mut() {
+            Some((last, content)) if last == role => content.extend(blocks),
+            _ => messages.push((role.to_string(), blocks)),
+        }
+    }
+
+    let mut body = json!({
+        "model": request.model,
+        "max_tokens": MAX_TOKENS,
+        "messages": messages
+            .into_iter()
+            .map(|(role, content)| json!({ "role": role, "content": content }))
+            .collect::<Vec<_>>(),
+    });
+    if !system.is_empty() {
+        body["system"] = json!(system);
+    }
+    if let Some(tools) = &request.tools {
+        body["tools"] = tools
+            .iter()
+            .map(|t| json!({
+                "name": t.function.name,
+                "description": t.function.description,
+                "input_schema": t.function.parameters,
+            }))
+            .collect();
+    }
+    if stream {
+        body["stream"] = json!(true);
+    }
+    body
+}
+
+fn usage(input: u64, output: u64) -> Usage {
+    Usage { prompt_tokens: input, completion_tokens: output, total_tokens: input + output }
+}
+
+/// Messages API の応答（content ブロックの並び）を読む
+pub fn parse_response(body: &str) -> Result<ChatResponse, LlmError> {
+    let value: Value = serde_json::from_str(body).map_err(|e| LlmError::Parse(e.to_string()))?;
+    let Some(blocks) = value["content"].as_array() else {
+        return Err(LlmError::Parse(format!("No content found in the response: {}", body)));
+    };
+
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+    for block in blocks {
+        match block["type"].as_str() {
+            Some("text") => text.push_str(block["text"].as_str().unwrap_or_default()),
+            Some("tool_use") => tool_calls.push(ToolCall {
+                id: block["id"].as_str().unwrap_or_default().to_string(),
+                kind: "function".to_string(),
+                function: FunctionCall {
+                    name: block["name"].as_str().unwrap_or_default().to_string(),
+                    arguments: block["input"].to_string(),
+                },
+            }),
+            _ => {}
+        }
+    }
+
+    let usage = value["usage"]["input_tokens"]
+        .as_u64()
+        .map(|input| usage(input, value["usage"]["output_tokens"].as_u64().unwrap_or(0)));
+    Ok(assistant_response(text, tool_calls, usage))
+}
+
+/// ストリーム（message_start → content_block_* → message_delta → message_stop）を1つの応答にまとめる
+pub fn assemble_stream(body: &str) -> Result<ChatResponse, LlmError> {
+    let mut text = String::new();
+    let mut tool_calls: Vec<ToolCall> = Vec::new();
+    let mut blocks: Vec<Option<usize>> = Vec::new();   // ブロック番号 → tool_calls の位置
+    let (mut input_tokens, mut output_tokens) = (0, 0);
+
+    for (event, data) in parse_sse(body) {
+        let value: Value = serde_json::from_str(&data).map_err(|e| LlmError::Parse(e.to_string()))?;
+        match event.as_deref().or(value["type"].as_str()) {
+            Some("message_start") => {
+                input_tokens = value["message"]["usage"]["input_tokens"].as_u64().unwrap_or(0);
+            }
+            Some("content_block_start") => {
+                let block = &value["content_block"];
+                let index = value["index"].as_u64().unwrap_or(0) as usize;
+                if blocks.len() <= index {
+                    blocks.resize(index + 1, None);
+                }
+                if block["type"] == "tool_use" {
+                    blocks[index] = Some(tool_calls.len());
+                    tool_calls.push(ToolCall {
+                        id: block["id"].as_str().unwrap_or_default().to_string(),
+                        kind: "function".to_string(),
+                        function: FunctionCall { name: block["name"].as_str().unwrap_or_default().to_string(), arguments: String::new() },
+                    });
+                }
+            }
+            Some("content_block_delta") => {
+                let delta = &value["delta"];
+                match delta["type"].as_str() {
+                    Some("text_delta") => text.push_str(delta["text"].as_str().unwrap_or_default()),
+                    Some("input_json_delta") => {
+                        let index = value["index"].as_u64().unwrap_or(0) as usize;
+                        if let Some(Some(call)) = blocks.get(index) {
+                            tool_calls[*call].function.arguments.push_str(delta["partial_json"].as_str().unwrap_or_default());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Some("message_delta") => {
+                output_tokens = value["usage"]["output_tokens"].as_u64().unwrap_or(output_tokens);
+            }
+            // 途中で混雑などが起きたら、再試行できるエラーとして返す
+            Some("error") => {
+                return Err(LlmError::Http { status: StatusCode::SERVICE_UNAVAILABLE, body: backend::error_message(&data) });
+            }
+            _ => {}
+        }
+    }
+
+    for call in &mut tool_calls {
+        if call.function.arguments.is_empty() {
+            call.function.arguments = "{}".to_string();
+        }
+    }
+    Ok(assistant_response(text, tool_calls, Some(usage(input_tokens, output_tokens))))
+}
+
+impl LlmBackend for AnthropicBackend {
+    fn send<'a>(&'a self, request: &'a ChatRequest, timeout: Duration) -> BackendFuture<'a> {
+        Box::pin(async move {
+            let http_request = HttpRequest::post(format!("{}/v1/messages", self.base_url))
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", API_VERSION)
+                .timeout(timeout)
+                .json(&to_request_body(request, self.stream))
+                .map_err(parse_error)?;
+            let response = self.http.send(http_request).await.map_err(|e| (LlmError::from(e), None))?;
+
+            if !response.status.is_success() {
+                return Err(http_error(&response));
+            }
+            let body = response.text();
+            if self.stream { assemble_stream(&body) } else { parse_response(&body) }.map_err(|e| (e, None))
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ChatMessage;
+
+    #[test]
+    fn test_tool_results_are_merged_into_one_user_turn() {
+        let mut assistant = ChatMessage::new("assistant", "");
+        assistant.tool_calls = Some(vec![
+            ToolCall { id: "a".into(), kind: "function".into(), function: FunctionCall { name: "list_tasks".into(), arguments: "{}".into() } },
+            ToolCall { id: "b".into(), kind: "function".into(), function: FunctionCall { name: "search_tasks".into(), arguments: r#"{"query":"牛乳"}"#.into() } },
+        ]);
+        let request = ChatRequest {
+            model: "claude-3-5-haiku-latest".into(),
+            messages: vec![
+                ChatMessage::new("system", "秘書です"),
+                ChatMessage::new("user", "牛乳のタスクある？"),
+                assistant,
+                ChatMessage::tool_result("a", "[]"),
+                ChatMessage::tool_result("b", "[]"),
+            ],
+            tools: None,
+        };
+
+        let body = to_request_body(&request, false);
+        assert_eq!(body["system"], "秘書です");
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1]["content"][1]["input"]["query"], "牛乳");
+        assert_eq!(messages[2]["role"], "user");
+        assert_eq!(messages[2]["content"].as_array().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,326 @@
+use crate::cache::fingerprint;
+use crate::reading::{self, ReadingDictionary};
+use crate::tts::{TtsEngine, TtsFuture, Voice, VoiceSpec};
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+pub const DEFAULT_TTS_CACHE_DIR: &str = "tts_cache";
+pub const DEFAULT_TTS_CACHE_MB: u64 = 64;
+
+/// キャッシュの当たり・外れ
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AudioCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+impl AudioCacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 { 0.0 } else { self.hits as f64 / total as f64 }
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "音声キャッシュ: {}件（{:.1}MB）、ヒット {} / ミス {}（ヒット率 {:.0}%）",
+            self.entries,
+            self.bytes as f64 / (1024.0 * 1024.0),
+            self.hits,
+            self.misses,
+            self.hit_rate() * 100.0
+        )
+    }
+}
+
+struct Entry {
+    size: u64,
+    last_used: SystemTime,
+}
+
+/// 合成した WAV をディレクトリに覚えておき、同じ（エンジン, 話者, 調整, 読み, 文）なら合成しない
+/// 合計が max_bytes を超えたら、しばらく使っていないものから消す
+pub struct CachedEngine {
+    inner: Box<dyn TtsEngine>,
+    dir: PathBuf,
+    max_bytes: u64,
+    readings: String, // 読みの辞書と規則の指紋
+    index: Mutex<HashMap<String, Entry>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CachedEngine {
+    /// dir にある WAV を読み込んで始める（最後に使った時刻はファイルの更新時刻）。読みは設定の辞書
+    pub fn new(inner: impl TtsEngine + 'static, dir: &Path, max_bytes: u64) -> Self {
+        let mut index = HashMap::new();
+        for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            let (Some(key), Ok(meta)) = (path.file_stem().and_then(|s| s.to_str()), entry.metadata()) else {
+                continue;
+            };
+            if path.extension().is_some_and(|e| e == "wav") {
+                let last_used = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                index.insert(key.to_string(), Entry { size: meta.len(), last_used });
+            }
+        }
+
+        Self {
+            inner: Box::new(inner),
+            dir: dir.to_path_buf(),
+            max_bytes,
+            readings: reading::dictionary().fingerprint(),
+            index: Mutex::new(index),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// 設定の辞書の代わりに dictionary で読む前提のキーにする
+    pub fn with_readings(mut self, dictionary: &ReadingDictionary) -> Self {
+        self.readings = dictionary.fingerprint();
+        self
+    }
+
+    /// TTS_CACHE_DIR / TTS_CACHE_MAX_MB で設定する。DISABLE_TTS_CACHE があれば使わない
+    pub fn from_env(inner: impl TtsEngine + 'static) -> Option<Self> {
+        if env::var("DISABLE_TTS_CACHE").is_ok() {
+            return None;
+        }
+        let dir = env::var("TTS_CACHE_DIR").unwrap_or_else(|_| DEFAULT_TTS_CACHE_DIR.to_string());
+        let max_mb = env::var("TTS_CACHE_MAX_MB").ok().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_TTS_CACHE_MB);
+        Some(Self::new(inner, Path::new(&dir), max_mb * 1024 * 1024))
+    }
+
+    /// （エンジン, 話者, 調整, 読み, 文）のキー。調整・読みの辞書・読みの規則を変えたら別の音になる
+    pub fn key(engine: &str, voice: &VoiceSpec, readings: &str, text: &str) -> String {
+        let params = serde_json::to_string(&voice.params).unwrap_or_default();
+        fingerprint(&format!("{}|{}|{}|{}|{}", engine, voice.speaker, params, readings, text))
+    }
+
+    pub fn stats(&self) -> AudioCacheStats {
+        let index = self.index.lock().unwrap();
+        AudioCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: index.len(),
+            bytes: index.values().map(|e| e.size).sum(),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.wav", key))
+    }
+
+    fn lookup(&self, key: &str) -> Option<Vec<u8>> {
+        if !self.index.lock().unwrap().contains_key(key) {
+            return None;
+        }
+        let path = self.path(key);
+        let Ok(audio) = fs::read(&path) else {
+            // 外から消されていたら忘れる
+            self.index.lock().unwrap().remove(key);
+            return None;
+        };
+
+        let now = SystemTime::now();
+        if let Some(entry) = self.index.lock().unwrap().get_mut(key) {
+            entry.last_used = now;
+        }
+        // 次に起動したときも順番が分かるように、ファイルの更新時刻も進めておく
+        if let Ok(file) = fs::File::options().write(true).open(&path) {
+            let _ = file.set_modified(now);
+        }
+        Some(audio)
+    }
+
+    fn store(&self, key: &str, audio: &[u8]) {
+        if let Err(e) = fs::create_dir_all(&self.dir).and_then(|_| fs::write(self.path(key), audio)) {
+            eprintln!("Failed to write TTS cache: {} ({})", self.dir.display(), e);
+            return;
+        }
+
+        let mut index = self.index.lock().unwrap();
+        index.insert(key.to_string(), Entry { size: audio.len() as u64, last_used: SystemTime::now() });
+        let mut total: u64 = index.values().map(|e| e.size).sum();
+        while total > self.max_bytes {
+            let Some(oldest) = index.iter().min_by_key(|(_, e)| e.last_used).map(|(k, _)| k.clone()) else {
+                break;
+            };
+            if let Some(entry) = index.remove(&oldest) {
+                total -= entry.size;
+            }
+            let _ = fs::remove_file(self.path(&oldest));
+        }
+    }
+
+    /// 決まり文句を先に合成しておく。新しく合成した数を返す
+    pub async fn prewarm(&self, phrases: &[String], voice: &VoiceSpec) -> usize {
+        let mut synthesized = 0;
+        for text in phrases {
+            let key = Self::key(self.inner.name(), voice, &self.readings, text);
+            if self.index.lock().unwrap().contains_key(&key) {
+                continue;
+            }
+            match self.inner.synthesize(text, voice).await {
+                Ok(audio) if !audio.is_empty() => {
+                    self.store(&key, &audio);
+                    synthesized += 1;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    // エンジンが起きていなければ、残りも無理なのでやめる
+                    eprintln!("Failed to prewarm TTS cache: {}", e);
+                    break;
+                }
+            }
+        }
+        synthesized
+    }
+}
+
+impl TtsEngine for CachedEngine {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn synthesize<'a>(&'a self, text: &'a str, voice: &'a VoiceSpec) -> TtsFuture<'a, Vec<u8>> {
+        Box::pin(async move {
+            let key = Self::key(self.inner.name(), voice, &self.readings, text);
+            if let Some(audio) = self.lookup(&key) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(audio);
+            }
+
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            let audio = self.inner.synthesize(text, voice).await?;
+            // 音の無い結果（文字だけのエンジンなど）は覚えない
+            if !audio.is_empty() {
+                self.store(&key, &audio);
+            }
+            Ok(audio)
+        })
+    }
+
+    fn voices(&self) -> TtsFuture<'_, Vec<Voice>> {
+        self.inner.voices()
+    }
+
+    fn health_check(&self) -> TtsFuture<'_, ()> {
+        self.inner.health_check()
+    }
+
+    fn plays_audio(&self) -> bool {
+        self.inner.plays_audio()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tts::{TtsError, VoiceParams};
+    use std::sync::Arc;
+
+    // 呼ばれた回数を数え、文ごとに決まった大きさの WAV を返す
+    #[derive(Default)]
+    struct Counting {
+        calls: AtomicU64,
+    }
+
+    impl TtsEngine for Counting {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn synthesize<'a>(&'a self, text: &'a str, _voice: &'a VoiceSpec) -> TtsFuture<'a, Vec<u8>> {
+            Box::pin(async move {
+                self.calls.fetch_add(1, Ordering::Relaxed);
+                let mut audio = b"RIFF".to_vec();
+                audio.extend(text.as_bytes());
+                Ok::<_, TtsError>(audio)
+            })
+        }
+
+        fn voices(&self) -> TtsFuture<'_, Vec<Voice>> {
+            Box::pin(async { Ok(vec![]) })
+        }
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("tts_cache_{}", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_hits_skip_the_engine_and_params_change_the_key() {
+        let dir = temp_dir();
+        let inner = Arc::new(Counting::default());
+        let cache = CachedEngine::new(inner.clone(), &dir, 1024 * 1024);
+        let voice = VoiceSpec::speaker(8);
+        let faster = VoiceSpec { speaker: 8, params: VoiceParams { speed_scale: Some(1.3), ..Default::default() } };
+
+        let first = cache.synthesize("了解です。", &voice).await.unwrap();
+        assert_eq!(cache.synthesize("了解です。", &voice).await.unwrap(), first);
+        cache.synthesize("了解です。", &faster).await.unwrap();
+        cache.synthesize("了解です。", &VoiceSpec::speaker(3)).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::Relaxed), 3);
+
+        // 次の起動でもディスクから読める
+        let reopened = CachedEngine::new(inner.clone(), &dir, 1024 * 1024);
+        reopened.synthesize("了解です。", &voice).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::Relaxed), 3);
+        assert_eq!(reopened.stats(), AudioCacheStats { hits: 1, misses: 0, entries: 3, bytes: 3 * first.len() as u64 });
+        assert_eq!(cache.stats().hits, 1);
+        assert_eq!(cache.stats().misses, 3);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_reading_dictionary_changes_the_key() {
+        let dir = temp_dir();
+        let inner = Arc::new(Counting::default());
+        let voice = VoiceSpec::speaker(8);
+        let dictionary = |reading: &str| ReadingDictionary::new([("main.rs".to_string(), reading.to_string())]);
+
+        let cache = CachedEngine::new(inner.clone(), &dir, 1024 * 1024).with_readings(&dictionary("めいん"));
+        cache.synthesize("main.rsを更新", &voice).await.unwrap();
+        cache.synthesize("main.rsを更新", &voice).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::Relaxed), 1);
+
+        // 読みを書き換えたら、前の音は使わない
+        let rewritten = CachedEngine::new(inner.clone(), &dir, 1024 * 1024).with_readings(&dictionary("めいんあーるえす"));
+        rewritten.synthesize("main.rsを更新", &voice).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::Relaxed), 2);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_evicts_least_recently_used_and_prewarms() {
+        let dir = temp_dir();
+        let inner = Arc::new(Counting::default());
+        // 「RIFF」+ 全角2文字 = 10バイトを2つまで
+        let cache = CachedEngine::new(inner.clone(), &dir, 20);
+        let voice = VoiceSpec::speaker(8);
+
+        let phrases = vec!["時報".to_string(), "挨拶".to_string()];
+        assert_eq!(cache.prewarm(&phrases, &voice).await, 2);
+        assert_eq!(cache.prewarm(&phrases, &voice).await, 0);
+
+        // 時報を使ってから新しい文を足すと、使っていない挨拶が消える
+        cache.synthesize("時報", &voice).await.unwrap();
+        cache.synthesize("休憩", &voice).await.unwrap();
+        assert_eq!(cache.stats().entries, 2);
+        cache.synthesize("時報", &voice).await.unwrap();
+        cache.synthesize("挨拶", &voice).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::Relaxed), 4);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,334 @@
+use crate::cache::normalize_input;
+use crate::llm::LlmError;
+use crate::models::{ChatChoice, ChatMessage, ChatRequest, ChatResponse, FunctionCall, ToolCall, Usage};
+use crate::transport::{HttpClient, HttpRequest, HttpResponse};
+
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::fs;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 1回分の呼び出しの結果。失敗時は Retry-After（あれば）も返す
+pub type BackendResult = Result<ChatResponse, (LlmError, Option<Duration>)>;
+pub type BackendFuture<'a> = Pin<Box<dyn Future<Output = BackendResult> + Send + 'a>>;
+
+/// チャットAPIの送り先。再試行・ブレーカー・使用量の記録は LlmClient が受け持つ
+pub trait LlmBackend: Send + Sync {
+    /// 1回だけ呼び出す（再試行はしない）
+    fn send<'a>(&'a self, request: &'a ChatRequest, timeout: Duration) -> BackendFuture<'a>;
+}
+
+// 記録した内容を後から取り出せるよう、共有したまま渡せるようにする
+impl<B: LlmBackend + ?Sized> LlmBackend for Arc<B> {
+    fn send<'a>(&'a self, request: &'a ChatRequest, timeout: Duration) -> BackendFuture<'a> {
+        (**self).send(request, timeout)
+    }
+}
+
+impl<B: LlmBackend + ?Sized> LlmBackend for Box<B> {
+    fn send<'a>(&'a self, request: &'a ChatRequest, timeout: Duration) -> BackendFuture<'a> {
+        (**self).send(request, timeout)
+    }
+}
+
+/// SSE（text/event-stream）を (event 名, data) の並びにする
+pub fn parse_sse(body: &str) -> Vec<(Option<String>, String)> {
+    let mut events = Vec::new();
+    let mut event = None;
+    let mut data: Vec<&str> = Vec::new();
+
+    for line in body.lines().chain(std::iter::once("")) {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            if !data.is_empty() {
+                events.push((event.take(), data.join("\n")));
+                data.clear();
+            }
+            event = None;
+        } else if let Some(value) = line.strip_prefix("event:") {
+            event = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data.push(value.strip_prefix(' ').unwrap_or(value));
+        }
+    }
+    events
+}
+
+/// エラーのボディから人が読めるメッセージを取り出す
+/// OpenAI / Azure / Gemini: {"error": {"message": ...}}、Anthropic: {"type": "error", "error": {"message": ...}}
+pub fn error_message(body: &str) -> String {
+    serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|v| v["error"]["message"].as_str().map(str::to_string))
+        .unwrap_or_else(|| body.to_string())
+}
+
+/// 非成功ステータスを LlmError にする（Retry-After があれば添える）
+pub fn http_error(response: &HttpResponse) -> (LlmError, Option<Duration>) {
+    let retry_after = response.header("retry-after").and_then(crate::llm::parse_retry_after);
+    (LlmError::Http { status: response.status, body: error_message(&response.text()) }, retry_after)
+}
+
+pub fn parse_error(e: impl std::fmt::Display) -> (LlmError, Option<Duration>) {
+    (LlmError::Parse(e.to_string()), None)
+}
+
+/// OpenAI 互換の Chat Completions API（Azure OpenAI もこの形式）
+pub struct OpenAiBackend {
+    http: HttpClient,
+    url: String,
+    query: Vec<(String, String)>,
+    auth: (String, String),
+    stream: bool,
+}
+
+impl OpenAiBackend {
+    /// HTTP_RECORD / HTTP_REPLAY が指定されていれば記録・再生する
+    pub fn new(api_key: &str, base_url: &str) -> Self {
+        Self::with_http(HttpClient::from_env(), api_key, base_url)
+    }
+
+    pub fn with_http(http: HttpClient, api_key: &str, base_url: &str) -> Self {
+        Self {
+            http,
+            url: format!("{}/chat/completions", base_url.trim_end_matches('/')),
+            query: vec![],
+            auth: ("Authorization".to_string(), format!("Bearer {}", api_key)),
+            stream: false,
+        }
+    }
+
+    /// Azure OpenAI: モデルはデプロイ名で URL に入り、キーは api-key ヘッダーで渡す
+    pub fn azure(http: HttpClient, api_key: &str, endpoint: &str, deployment: &str, api_version: &str) -> Self {
+        Self {
+            http,
+            url: format!("{}/openai/deployments/{}/chat/completions", endpoint.trim_end_matches('/'), deployment),
+            query: vec![("api-version".to_string(), api_version.to_string())],
+            auth: ("api-key".to_string(), api_key.to_string()),
+            stream: false,
+        }
+    }
+
+    /// 応答をストリーミングで受け取って組み立てる
+    pub fn streaming(mut self, stream: bool) -> Self {
+        self.stream = stream;
+        self
+    }
+}
+
+/// OpenAI 形式のストリーム（data: {...} の並び、最後は [DONE]）を1つの応答にまとめる
+pub fn assemble_openai_stream(body: &str) -> Result<ChatResponse, LlmError> {
+    let mut content = String::new();
+    let mut tool_calls: Vec<ToolCall> = Vec::new();
+    let mut usage = None;
+
+    for (_, data) in parse_sse(body) {
+        if data.trim() == "[DONE]" {
+            break;
+        }
+        let chunk: Value = serde_json::from_str(&data).map_err(|e| LlmError::Parse(e.to_string()))?;
+        if !chunk["error"].is_null() {
+            return Err(LlmError::Parse(error_message(&data)));
+        }
+        if let Ok(u) = serde_json::from_value::<Usage>(chunk["usage"].clone()) {
+            usage = Some(u);
+        }
+
+        let delta = &chunk["choices"][0]["delta"];
+        if let Some(text) = delta["content"].as_str() {
+            content.push_str(text);
+        }
+        for call in delta["tool_calls"].as_array().into_iter().flatten() {
+            let index = call["index"].as_u64().unwrap_or(0) as usize;
+            if tool_calls.len() <= index {
+                tool_calls.resize_with(index + 1, || ToolCall {
+                    id: String::new(),
+                    kind: "function".to_string(),
+                    function: FunctionCall { name: String::new(), arguments: String::new() },
+                });
+            }
+            let target = &mut tool_calls[index];
+            if let Some(id) = call["id"].as_str() {
+                target.id = id.to_string();
+            }
+            if let Some(name) = call["function"]["name"].as_str() {
+                target.function.name.push_str(name);
+            }
+            if let Some(arguments) = call["function"]["arguments"].as_str() {
+                target.function.arguments.push_str(arguments);
+            }
+        }
+    }
+
+    Ok(assistant_response(content, tool_calls, usage))
+}
+
+/// 組み立てた内容を OpenAI 形式の応答にする（各アダプターの出口）
+pub fn assistant_response(content: String, tool_calls: Vec<ToolCall>, usage: Option<Usage>) -> ChatResponse {
+    let mut message = ChatMessage::new("assistant", content);
+    message.tool_calls = (!tool_calls.is_empty()).then_some(tool_calls);
+    ChatResponse { choices: vec![ChatChoice { message }], usage }
+}
+
+impl LlmBackend for OpenAiBackend {
+    fn send<'a>(&'a self, request: &'a ChatRequest, timeout: Duration) -> BackendFuture<'a> {
+        Box::pin(async move {
+            let mut body = serde_json::to_value(request).map_err(parse_error)?;
+            if self.stream {
+                body["stream"] = json!(true);
+                body["stream_options"] = json!({ "include_usage": true });
+            }
+
+            let mut http_request = HttpRequest::post(&self.url)
+                .header(&self.auth.0, &self.auth.1)
+                .timeout(timeout)
+                .json(&body)
+                .map_err(parse_error)?;
+            for (name, value) in &self.query {
+                http_request = http_request.query(name, value);
+            }
+            let response = self.http.send(http_request).await.map_err(|e| (LlmError::from(e), None))?;
+
+            if !response.status.is_success() {
+                return Err(http_error(&response));
+            }
+
+            let body = response.text();
+            let parsed = if self.stream {
+                assemble_openai_stream(&body).map_err(|e| (e, None))?
+            } else {
+                serde_json::from_str(&body).map_err(parse_error)?
+            };
+            if parsed.choices.is_empty() {
+                return Err((LlmError::Parse(format!("No choices found in the response: {}", body)), None));
+            }
+            Ok(parsed)
+        })
+    }
+}
+
+/// 最後の user 発言をキーにする（表記ゆれは cache と同じ規則で吸収する）
+fn fixture_key(request: &ChatRequest) -> String {
+    let prompt = request
+        .messages
+        .iter()
+        .rev()
+        .find(|m| m.role == "user")
+        .map(|m| m.content.as_str())
+        .unwrap_or_default();
+    normalize_input(prompt)
+}
+
+fn text_response(content: &str) -> ChatResponse {
+    assistant_response(content.to_string(), vec![], Some(Usage::default()))
+}
+
+/// 記録しておいた（プロンプト → 返答）で答える。評価やテストをオフラインで回す用
+#[derive(Debug, Clone, Default)]
+pub struct FixtureBackend {
+    replies: HashMap<String, String>,
+}
+
+impl FixtureBackend {
+    pub fn from_pairs<I, P, R>(pairs: I) -> Self
+    where
+        I: IntoIterator<Item = (P, R)>,
+        P: AsRef<str>,
+        R: Into<String>,
+    {
+        Self {
+            replies: pairs.into_iter().map(|(p, r)| (normalize_input(p.as_ref()), r.into())).collect(),
+        }
+    }
+
+    /// {"プロンプト": "返答", ...} のJSONを読む
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let pairs: HashMap<String, String> = serde_json::from_str(&fs::read_to_string(path)?)?;
+        Ok(Self::from_pairs(pairs))
+    }
+
+    pub fn len(&self) -> usize {
+        self.replies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replies.is_empty()
+    }
+}
+
+impl LlmBackend for FixtureBackend {
+    fn send<'a>(&'a self, request: &'a ChatRequest, _timeout: Duration) -> BackendFuture<'a> {
+        Box::pin(async move {
+            match self.replies.get(&fixture_key(request)) {
+                Some(reply) => Ok(text_response(reply)),
+                None => Err((LlmError::Parse("No fixture recorded for this prompt".to_string()), None)),
+            }
+        })
+    }
+}
+
+/// 別の送り先を包んで、（プロンプト → 返答）を FixtureBackend の形で書き出す
+pub struct RecordingBackend<B> {
+    inner: B,
+    recorded: Mutex<HashMap<String, String>>,
+}
+
+impl<B: LlmBackend> RecordingBackend<B> {
+    pub fn new(inner: B) -> Self {
+        Self { inner, recorded: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let recorded = self.recorded.lock().unwrap();
+        fs::write(path, serde_json::to_string_pretty(&*recorded)?)
+    }
+}
+
+impl<B: LlmBackend> LlmBackend for RecordingBackend<B> {
+    fn send<'a>(&'a self, request: &'a ChatRequest, timeout: Duration) -> BackendFuture<'a> {
+        Box::pin(async move {
+            let response = self.inner.send(request, timeout).await?;
+            if let (Some(prompt), Some(choice)) = (request.messages.iter().rev().find(|m| m.role == "user"), response.choices.first()) {
+                self.recorded
+                    .lock()
+                    .unwrap()
+                    .insert(prompt.content.clone(), choice.message.content.clone());
+            }
+            Ok(response)
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(prompt: &str) -> ChatRequest {
+        ChatRequest {
+            model: "gpt-3.5-turbo".into(),
+            messages: vec![ChatMessage::new("system", "prompt"), ChatMessage::new("user", prompt)],
+            tools: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fixture_backend_replays_and_records() {
+        let fixtures = FixtureBackend::from_pairs([("牛乳を買う。", "タスク")]);
+        let reply = fixtures.send(&request("牛乳を買う"), Duration::from_secs(1)).await.unwrap();
+        assert_eq!(reply.choices[0].message.content, "タスク");
+        assert!(fixtures.send(&request("こんにちは"), Duration::from_secs(1)).await.is_err());
+
+        let path = std::env::temp_dir().join(format!("fixture_test_{}.json", uuid::Uuid::new_v4()));
+        let recorder = RecordingBackend::new(fixtures);
+        recorder.send(&request("牛乳を買う"), Duration::from_secs(1)).await.unwrap();
+        recorder.save(&path).unwrap();
+
+        assert_eq!(FixtureBackend::load(&path).unwrap().len(), 1);
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,142 @@
+//! 分類・抽出の評価
+//!
+//! cargo run --bin eval -- [--dataset PATH] [--baseline PATH] [--fixtures PATH] [--record PATH] [--write-baseline]
+//!
+//! --fixtures を付けると記録済みの応答で（API を呼ばずに）評価する。
+//! --record を付けると API の応答をその形式で書き出す。
+//! 基準値を下回った指標があれば終了コード 1 で終わる。
+
+use kotonoha_core::backend::{FixtureBackend, LlmBackend, RecordingBackend};
+use kotonoha_core::eval;
+use kotonoha_core::llm::{CircuitBreaker, LlmClient, RetryPolicy};
+use kotonoha_core::providers::ProviderConfig;
+use kotonoha_core::transport::HttpClient;
+
+use dotenvy::dotenv;
+use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+struct Args {
+    dataset: PathBuf,
+    baseline: PathBuf,
+    fixtures: Option<PathBuf>,
+    record: Option<PathBuf>,
+    write_baseline: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        dataset: PathBuf::from(eval::DEFAULT_DATASET),
+        baseline: PathBuf::from(eval::DEFAULT_BASELINE),
+        fixtures: None,
+        record: None,
+        write_baseline: false,
+    };
+
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().map(PathBuf::from).ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--dataset" => args.dataset = value()?,
+            "--baseline" => args.baseline = value()?,
+            "--fixtures" => args.fixtures = Some(value()?),
+            "--record" => args.record = Some(value()?),
+            "--write-baseline" => args.write_baseline = true,
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+    }
+    Ok(args)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv().ok();
+
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if env::var("MOCK_OPENAI").is_ok() {
+        eprintln!("MOCK_OPENAI is set; the offline classifier will be evaluated instead of the model.");
+    }
+
+    let cases = match eval::load_dataset(&args.dataset) {
+        Ok(cases) => cases,
+        Err(e) => {
+            eprintln!("Failed to load dataset: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // 評価するのは LLM_PROVIDER / LLM_MODEL で選んだ提供元とモデル
+    let provider = match ProviderConfig::from_env() {
+        Ok(provider) => provider,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut recorder = None;
+    let backend: Box<dyn LlmBackend> = match &args.fixtures {
+        Some(path) => match FixtureBackend::load(path) {
+            Ok(fixtures) => Box::new(fixtures),
+            Err(e) => {
+                eprintln!("Failed to load fixtures: {}", e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => {
+            let live = Arc::new(RecordingBackend::new(provider.backend(HttpClient::from_env())));
+            recorder = Some(live.clone());
+            Box::new(live)
+        }
+    };
+
+    // 評価中はブレーカーで止めない（失敗はそのまま採点に出す）
+    let client = LlmClient::with_backend(backend, RetryPolicy::from_env(), CircuitBreaker::new(u32::MAX, Duration::ZERO))
+        .with_models(provider.model.clone(), provider.provider.cheap_model());
+    let report = eval::run(&client, &cases).await;
+    println!("{}", report.describe());
+
+    if let (Some(path), Some(recorder)) = (&args.record, &recorder) {
+        match recorder.save(path) {
+            Ok(()) => println!("\n応答を記録しました: {}", path.display()),
+            Err(e) => eprintln!("Failed to write fixtures: {}", e),
+        }
+    }
+
+    let scores = report.scores();
+    if args.write_baseline {
+        if let Err(e) = eval::save_baseline(&args.baseline, &scores) {
+            eprintln!("Failed to write baseline: {}", e);
+            return ExitCode::FAILURE;
+        }
+        println!("\n基準値を更新しました: {}", args.baseline.display());
+        return ExitCode::SUCCESS;
+    }
+
+    let baseline = match eval::load_baseline(&args.baseline) {
+        Ok(baseline) => baseline,
+        Err(e) => {
+            eprintln!("Failed to load baseline: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let regressions = report.regressions(&baseline);
+    if regressions.is_empty() {
+        println!("\n基準値を満たしています。");
+        ExitCode::SUCCESS
+    } else {
+        println!("\n基準値を下回りました:\n{}", regressions.join("\n"));
+        ExitCode::FAILURE
+    }
+}
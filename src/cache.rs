@@ -0,0 +1,166 @@
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const DEFAULT_CACHE_FILE: &str = "llm_cache.json";
+pub const DEFAULT_TTL_HOURS: i64 = 24 * 7;
+pub const DEFAULT_MAX_ENTRIES: usize = 500;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    value: String,
+    created_at: DateTime<Local>,
+}
+
+/// 分類・抽出の結果を覚えておくキャッシュ
+/// キーは（プロンプトのテンプレート, モデル, 正規化した入力）
+pub struct ResponseCache {
+    path: Option<PathBuf>,
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+/// 表記ゆれを吸収する（全角英数→半角、小文字化、空白の詰め、末尾の句読点の除去）
+pub fn normalize_input(input: &str) -> String {
+    let halfwidth: String = input
+        .chars()
+        .map(|c| match c {
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            '\u{3000}' => ' ',
+            _ => c,
+        })
+        .collect();
+
+    halfwidth
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+        .trim_end_matches(['。', '、', '.', '!', '?', '！', '？'])
+        .to_string()
+}
+
+/// テンプレートの指紋（FNV-1a）。テンプレートを書き換えたら別のキーになる
+pub fn fingerprint(text: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in text.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+impl ResponseCache {
+    /// path が None ならファイルに保存しない
+    pub fn new(path: Option<&Path>, ttl: Duration, max_entries: usize) -> Self {
+        let entries = path
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+
+        Self {
+            path: path.map(Path::to_path_buf),
+            ttl,
+            max_entries,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    /// CACHE_FILE / CACHE_TTL_HOURS / CACHE_MAX_ENTRIES で設定する
+    pub fn from_env() -> Self {
+        let path = env::var("CACHE_FILE").unwrap_or_else(|_| DEFAULT_CACHE_FILE.to_string());
+        let ttl_hours = env::var("CACHE_TTL_HOURS").ok().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_TTL_HOURS);
+        let max_entries = env::var("CACHE_MAX_ENTRIES").ok().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_MAX_ENTRIES);
+        Self::new(Some(Path::new(&path)), Duration::hours(ttl_hours), max_entries)
+    }
+
+    pub fn key(template: &str, model: &str, input: &str) -> String {
+        format!("{}|{}|{}", fingerprint(template), model, normalize_input(input))
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(key)?;
+        (Local::now() - entry.created_at < self.ttl).then(|| entry.value.clone())
+    }
+
+    pub fn put(&self, key: String, value: String) {
+        let mut entries = self.entries.lock().unwrap();
+        let now = Local::now();
+        entries.insert(key, CacheEntry { value, created_at: now });
+
+        // 期限切れを捨て、それでも多ければ古いものから捨てる
+        entries.retain(|_, e| now - e.created_at < self.ttl);
+        while entries.len() > self.max_entries {
+            let Some(oldest) = entries.iter().min_by_key(|(_, e)| e.created_at).map(|(k, _)| k.clone()) else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+
+        if let Some(path) = &self.path {
+            match serde_json::to_string(&*entries) {
+                Ok(json) => {
+                    if let Err(e) = fs::write(path, json) {
+                        eprintln!("Failed to write cache file: {} ({})", path.display(), e);
+                    }
+                }
+                Err(e) => eprintln!("Failed to serialize cache: {}", e),
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalized_inputs_share_a_key() {
+        let a = ResponseCache::key("tmpl", "gpt-3.5-turbo", "タスク一覧。");
+        let b = ResponseCache::key("tmpl", "gpt-3.5-turbo", "  タスク一覧 ");
+        let c = ResponseCache::key("tmpl v2", "gpt-3.5-turbo", "タスク一覧");
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(normalize_input("ＡＢＣ　ｄｅｆ！"), "abc def");
+    }
+
+    #[test]
+    fn test_ttl_and_size_limit() {
+        let cache = ResponseCache::new(None, Duration::hours(1), 2);
+        cache.put("a".into(), "1".into());
+        cache.put("b".into(), "2".into());
+        cache.put("c".into(), "3".into());
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.get("c"), Some("3".into()));
+
+        let expired = ResponseCache::new(None, Duration::zero(), 10);
+        expired.put("a".into(), "1".into());
+        assert_eq!(expired.get("a"), None);
+    }
+
+    #[test]
+    fn test_persists_to_file() {
+        let path = std::env::temp_dir().join(format!("cache_test_{}.json", uuid::Uuid::new_v4()));
+        ResponseCache::new(Some(&path), Duration::hours(1), 10).put("k".into(), "v".into());
+
+        let reloaded = ResponseCache::new(Some(&path), Duration::hours(1), 10);
+        assert_eq!(reloaded.get("k"), Some("v".into()));
+        let _ = fs::remove_file(&path);
+    }
+}
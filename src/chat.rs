@@ -1,33 +1,25 @@
 use crate::models::{ChatMessage, ChatRequest, ChatResponse};
+use crate::tools::{self, PendingAction, ToolOutcome};
+use crate::tokens;
+use crate::memory::{self, FactCategory};
+use crate::mood::{self, MoodScore};
 
-use reqwest::Client;
+use crate::llm::LlmClient;
+use crate::usage::CallSite;
+use crate::cache::ResponseCache;
+use crate::prompts::{self, PromptTemplate};
 
 use std::env;
 use std::error::Error;
 
-pub const SYSTEM_PROMPT: &str = r#"
-あなたの名前は「ことのは」です。
-あなたはユーザー専属の秘書型AIとして動作します。
+/// 呼び出しに使うモデル
+pub const CHAT_MODEL: &str = "gpt-3.5-turbo";
 
-【会話ルール】
-- 自分の名前は必ず「ことのは」と名乗ってください
-- ユーザーに対しては丁寧で親しみやすい口調で話します
-- 名前や役割を聞かれたときは「秘書のことのはです」と答えてください
-- 話し方は柔らかく、女性的な印象にしてください
-- ユーザーの感情に寄り添い、共感的に返答します
-
-【目的】
-- ユーザーのタスク管理をサポートする
-- ユーザーの生活や思考を整理する手助けをする
-- 必要に応じてタスクを提案する
-
-これからユーザーと会話を始めます。
-"#;
-
-pub const FIRST_GREETING: &str = r#"
-はじめまして、秘書のことのはです。
-今日もよろしくお願いしますね。 "#;
+/// API に繋がらないあいだの雑談の返事
+pub const OFFLINE_REPLY: &str = "ごめんなさい、いまはうまく考えがまとまらないみたいです。タスクの追加や一覧なら今でもお手伝いできますよ。";
 
+/// 呼び出しに失敗したときに読み上げるお詫び
+pub const APOLOGY: &str = "ごめんなさい、いま少し調子が悪いみたいです。もう一度お願いできますか？";
 
 
 fn mock_openai_enabled() -> bool {
@@ -48,215 +40,338 @@ fn mock_task_title(input: &str) -> String {
     input.trim().to_string()
 }
 
-pub async fn classify_input(client: &Client, api_key: &str, input: &str) -> Result<String, Box<dyn Error>> {
-    if mock_openai_enabled() {
-        if input.contains("タスク") || input.contains("やる") || input.contains("完了") {
-            return Ok("タスク".to_string());
-        }
-        return Ok("雑談".to_string());
+/// オフラインの分類器で代用するか（モック指定時、API 障害中、または月の予算超過時）
+fn use_offline(llm: &LlmClient) -> bool {
+    mock_openai_enabled() || !llm.is_available() || llm.over_budget()
+}
+
+/// API を使わない分類（「タスク」か「雑談」）
+pub fn offline_classify_input(input: &str) -> String {
+    if input.contains("タスク") || input.contains("やる") || input.contains("完了") {
+        return "タスク".to_string();
+    }
+    "雑談".to_string()
+}
+
+/// API を使わないタスク操作の分類
+pub fn offline_classify_task_action(input: &str) -> String {
+    if input.contains("完了") {
+        return "完了".to_string();
     }
+    if input.contains("一覧") {
+        return "一覧".to_string();
+    }
+    if input.contains("追加") || input.contains("覚えて") || input.contains("登録") {
+        return "追加".to_string();
+    }
+    "なし".to_string()
+}
 
-    let prompt = format!(
-        "以下の文章はユーザからの入力です。この文章が「やるべきこと（ToDo）」に関する指示なら「タスク」、そうでなく会話や質問なら「雑談」とだけ返答してください。\n\n文章：{}",
-        input
-    );
+/// 最初の choice の本文を取り出す（send_chat で空でないことは確認済み）
+fn first_content(response: ChatResponse) -> String {
+    response
+        .choices
+        .into_iter()
+        .next()
+        .map(|choice| choice.message.content.trim().to_string())
+        .unwrap_or_default()
+}
 
+/// テンプレートを埋めて1問1答で呼び出す。使ったテンプレートの版は台帳に残る
+async fn complete_with_template(llm: &LlmClient, site: CallSite, template: &PromptTemplate, vars: &[(&str, &str)]) -> Result<String, Box<dyn Error>> {
     let request = ChatRequest {
-        model: "gpt-3.5-turbo".into(),
-        messages: vec![ChatMessage {
-            role: "user".into(),
-            content: prompt,
-        }],
+        model: llm.model_for(CHAT_MODEL),
+        messages: vec![ChatMessage::new("user", template.render(vars))],
+        tools: None,
     };
 
-    let response = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .bearer_auth(api_key)
-        .json(&request)
-        .send()
-        .await?;
-
-    let status = response.status();
-    let text = response.text().await?;
-    
-    if !status.is_success() {
-        return Err(format!("API Error {}: {}", status, text).into());
-       }
-
-    let parsed: ChatResponse = serde_json::from_str(&text)?;
-    let content = parsed
-        .choices
-        .get(0)
-        .ok_or_else(|| Box::<dyn Error>::from(format!("OpenAI: No choices found in the response: {}", text)))?
-        .message
-        .content
-        .trim()
-        .to_lowercase();
+    let response = llm.send_chat_with_prompt(site, Some(&template.id()), &request).await?;
+    Ok(first_content(response))
+}
 
+/// テンプレートに発言を埋めて1問1答で呼び出す。同じ入力の結果はキャッシュから返す
+async fn cached_completion(llm: &LlmClient, site: CallSite, name: &str, input: &str) -> Result<String, Box<dyn Error>> {
+    let template = prompts::template(name);
+    let model = llm.model_for(CHAT_MODEL);
+    let key = ResponseCache::key(&template.text, &model, input);
+    if let Some(hit) = llm.cache().and_then(|c| c.get(&key)) {
+        return Ok(hit);
+    }
+
+    let content = complete_with_template(llm, site, template, &[("input", input)]).await?;
+    if let Some(cache) = llm.cache() {
+        cache.put(key, content.clone());
+    }
     Ok(content)
 }
 
-pub async fn classify_task_action(client: &Client, api_key: &str, input: &str) -> Result<String, Box<dyn std::error::Error>> {
-    if mock_openai_enabled() {
-        if input.contains("完了") {
-            return Ok("完了".to_string());
-        }
-        if input.contains("一覧") {
-            return Ok("一覧".to_string());
-        }
-        if input.contains("追加") || input.contains("覚えて") || input.contains("登録") {
-            return Ok("追加".to_string());
-        }
-        return Ok("なし".to_string());
+pub async fn classify_input(llm: &LlmClient, input: &str) -> Result<String, Box<dyn Error>> {
+    if use_offline(llm) {
+        return Ok(offline_classify_input(input));
     }
 
-    let prompt = format!(
-        "次のユーザーの発言がタスク操作だとしたら、操作の種類を一語で答えてください。「追加」「完了」「一覧」「なし」のいずれかで返答してください。\n\n入力: {}",
-        input
-    );
-
-    let req = ChatRequest {
-        model: "gpt-3.5-turbo".into(),
-        messages: vec![ChatMessage {
-            role: "user".into(),
-            content: prompt,
-        }],
-    };
+    let content = cached_completion(llm, CallSite::Classify, prompts::CLASSIFY_INPUT, input).await?;
+    Ok(content.to_lowercase())
+}
 
-    let resp = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .bearer_auth(api_key)
-        .json(&req)
-        .send()
-        .await?;
-
-    let status = resp.status();
-    let body = resp.text().await?;
-    if !status.is_success() {
-        return Err(format!("API Error: ({}):{}", status, body).into());
+pub async fn classify_task_action(llm: &LlmClient, input: &str) -> Result<String, Box<dyn std::error::Error>> {
+    if use_offline(llm) {
+        return Ok(offline_classify_task_action(input));
     }
 
-    let parsed: ChatResponse = serde_json::from_str(&body)?;
-    Ok(parsed
-        .choices
-        .get(0)
-        .ok_or_else(|| format!("OpenAI: No choices found in the response: {}", body))?
-        .message
-        .content
-        .trim()
-        .to_string()
-    )
-
+    cached_completion(llm, CallSite::Action, prompts::CLASSIFY_TASK_ACTION, input).await
 }
 
 
+/// API を呼ばずに済む決まった言い回しを見分ける
+/// - "list": タスク一覧
+/// - "usage": 今月のAPI利用状況
+/// - "history": 会話履歴の一覧
+/// - "mood": 最近の気分の推移
 pub fn detect_special_command(input: &str) -> Option<&'static str>{
     if input.contains("タスク一覧") || input.contains("タスク確認"){
         Some("list")
-    }else {
+    } else if input.contains("API利用状況") {
+        Some("usage")
+    } else if input.trim() == "会話履歴" {
+        Some("history")
+    } else if input.contains("最近の調子") || input.contains("気分の記録") {
+        Some("mood")
+    } else {
         None
     }
 }
 
-pub async fn extract_task(client: &Client, api_key: &str, input: &str) -> Result<String, Box<dyn Error>> {
-    if mock_openai_enabled() {
+pub async fn extract_task(llm: &LlmClient, input: &str) -> Result<String, Box<dyn Error>> {
+    if use_offline(llm) {
         return Ok(mock_task_title(input));
     }
 
-    let prompt = format!(
-        "以下の文から、やるべきタスクがあればタイトルだけを抽出してください。\n文:{}",
-        input
-    );
+    cached_completion(llm, CallSite::Extract, prompts::EXTRACT_TASK, input).await
+}
 
-    let request = ChatRequest {
-        model: "gpt-3.5-turbo".into(),
-        messages: vec![ChatMessage {
-            role: "user".into(),
-            content: prompt,
-        }],
-    };
+/// 「カテゴリ|事実」の行を読み取る（「なし」や形式外の行は捨てる）
+fn parse_fact_lines(text: &str) -> Vec<(FactCategory, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let (label, fact) = line.trim().trim_start_matches(['・', '-']).split_once('|')?;
+            let fact = fact.trim();
+            (!fact.is_empty()).then(|| (FactCategory::from_label(label), fact.to_string()))
+        })
+        .collect()
+}
 
-    let response = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .bearer_auth(api_key)
-        .json(&request)
-        .send()
-        .await?;
-
-    let status = response.status();
-    let body = response.text().await?;
-    if !status.is_success() {
-        return Err(format!("API Error: ({}):{}", status, body).into());
+/// 雑談の発言から、ユーザーについて覚えておく価値のある事実を取り出す
+pub async fn extract_user_facts(llm: &LlmClient, input: &str) -> Result<Vec<(FactCategory, String)>, Box<dyn Error>> {
+    if use_offline(llm) {
+        return Ok(memory::extract_facts_locally(input));
     }
 
-    let parsed: ChatResponse = serde_json::from_str(&body)?;
-    Ok(parsed
-        .choices
-        .get(0)
-        .ok_or_else(|| format!("OpenAI: No choices found in the response: {}", body))?
-        .message
-        .content
-        .trim()
-        .to_string()
-    )
+    let template = prompts::template(prompts::EXTRACT_USER_FACTS);
+    let content = complete_with_template(llm, CallSite::Memory, template, &[("input", input)]).await?;
+    Ok(parse_fact_lines(&content))
 }
 
-pub async fn respond_to_chat(client: &Client, api_key: &str, messages: &Vec<ChatMessage>) -> Result<String, Box<dyn Error>> {
-    if mock_openai_enabled() {
-        return Ok("はい、承知しました。".to_string());
+/// 発言からいまの気分を判定してもらう（辞書で分からなかったときの補助）
+pub async fn assess_mood(llm: &LlmClient, input: &str) -> Result<MoodScore, Box<dyn Error>> {
+    if use_offline(llm) {
+        return Ok(mood::score_text(input));
     }
 
-    let request = ChatRequest {
-        model: "gpt-3.5-turbo".into(),
-        messages: messages.clone(),
-    };
+    let content = cached_completion(llm, CallSite::Mood, prompts::ASSESS_MOOD, input).await?;
+    Ok(mood::score_label(&content))
+}
+
+/// 雑談への応答。確認待ちの操作があれば、頼まれた順に pending に入る
+pub struct ChatReply {
+    pub text: String,
+    pub pending: Vec<PendingAction>,
+}
 
-    let response = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .bearer_auth(api_key)
-        .json(&request)
-        .send()
-        .await?;
+/// 雑談に応答する。モデルがツールを呼んだら実行して結果を返し、
+/// 最終的な返答が得られるまで MAX_TOOL_STEPS 回まで繰り返す
+pub async fn respond_to_chat(llm: &LlmClient, messages: &[ChatMessage]) -> Result<ChatReply, Box<dyn Error>> {
+    if mock_openai_enabled() {
+        return Ok(ChatReply { text: "はい、承知しました。".to_string(), pending: Vec::new() });
+    }
+    if !llm.is_available() {
+        return Ok(ChatReply { text: OFFLINE_REPLY.to_string(), pending: Vec::new() });
+    }
 
-    let status = response.status();
-    let text = response.text().await?;
+    // ツールのやり取りはこの応答の中だけで使い、会話履歴には残さない
+    let mut working = messages.to_vec();
+    let mut pending = Vec::new();
+
+    for _ in 0..tools::MAX_TOOL_STEPS {
+        let request = ChatRequest {
+            model: llm.model_for(CHAT_MODEL),
+            messages: working.clone(),
+            tools: Some(tools::definitions()),
+        };
+
+        let response = llm.send_chat(CallSite::Chat, &request).await?;
+        let Some(choice) = response.choices.into_iter().next() else {
+            break;
+        };
+        let message = choice.message;
+
+        let calls = message.tool_calls.clone().unwrap_or_default();
+        if calls.is_empty() {
+            return Ok(ChatReply { text: message.content.trim().to_string(), pending });
+        }
 
-    if !status.is_success() {
-            return Err(format!("API Error: ({}):{}", status, text).into());
+        working.push(message);
+        for call in &calls {
+            let result = match tools::execute(call) {
+                ToolOutcome::Done(result) => result,
+                ToolOutcome::NeedsConfirmation(action) => {
+                    let note = format!("ユーザーに確認中のため、まだ実行していません: {}", action.prompt());
+                    pending.push(action);
+                    note
+                }
+            };
+            working.push(ChatMessage::tool_result(&call.id, result));
         }
+    }
+
+    Ok(ChatReply {
+        text: "ごめんなさい、操作が多すぎたので途中で止めました。もう一度お願いできますか？".to_string(),
+        pending,
+    })
+}
+
+
+/// 要約メッセージの目印（system ロールで履歴の2番目に置く）
+pub const SUMMARY_HEADER: &str = "【これまでの会話の要約】";
 
-    let parsed: ChatResponse = serde_json::from_str(&text)?;
-    let reply = parsed.choices
-        .get(0)
-        .map(| choice | choice.message.content.clone())
-        .ok_or({
-            format!("No choices found in the response: {}", text)
-        })?;
+// 要約せずにそのまま残す直近のメッセージ数
+const KEEP_RECENT_MESSAGES: usize = 6;
 
-    Ok(reply.trim().to_string())
+fn is_summary(message: &ChatMessage) -> bool {
+    message.role == "system" && message.content.starts_with(SUMMARY_HEADER)
 }
 
+/// 予算を超えていたら、要約に回すメッセージの範囲（先頭の system を除く [1, end)）を返す
+/// 直近 KEEP_RECENT_MESSAGES 件と先頭の system は必ず残す
+pub fn plan_compaction(messages: &[ChatMessage], budget: usize) -> Option<usize> {
+    if tokens::estimate_messages(messages) <= budget {
+        return None;
+    }
+    let end = messages.len().saturating_sub(KEEP_RECENT_MESSAGES);
+    // system + 要約だけなら、もう縮められない
+    if end <= 1 || (end == 2 && is_summary(&messages[1])) {
+        return None;
+    }
+    Some(end)
+}
 
-#[cfg(test)]
-pub fn make_classification_prompt(input: &str) -> String {
-    format!(
-        "以下の文章はユーザからの入力です。この文章が「やるべきこと（ToDo）」に関する指示なら「タスク」、そうでなく会話や質問なら「雑談」とだけ返答してください。\n\n文章：{}",
-        input
-    )
+/// messages[1..end] を要約1件に置き換える
+pub fn apply_summary(messages: &mut Vec<ChatMessage>, end: usize, summary: &str) {
+    let summary = ChatMessage::new("system", format!("{}\n{}", SUMMARY_HEADER, summary.trim()));
+    messages.splice(1..end, [summary]);
+}
+
+/// API を使わない簡易要約（発言の冒頭を並べるだけ）
+pub fn local_summary(messages: &[ChatMessage]) -> String {
+    messages
+        .iter()
+        .map(|m| {
+            if is_summary(m) {
+                return m.content.trim_start_matches(SUMMARY_HEADER).trim().to_string();
+            }
+            let who = if m.role == "user" { "ユーザー" } else { "ことのは" };
+            let head: String = m.content.chars().take(40).collect();
+            format!("・{}: {}", who, head)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+async fn summarize(llm: &LlmClient, messages: &[ChatMessage]) -> Result<String, Box<dyn Error>> {
+    let transcript = messages
+        .iter()
+        .map(|m| format!("{}: {}", m.role, m.content))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let template = prompts::template(prompts::SUMMARIZE_HISTORY);
+    complete_with_template(llm, CallSite::Summary, template, &[("transcript", &transcript)]).await
 }
 
+/// 会話履歴が budget を超えていたら古いやり取りを要約にまとめる
+/// 要約に失敗しても会話は止めず、簡易要約で代用する。縮めたら true
+pub async fn compact_history(llm: &LlmClient, messages: &mut Vec<ChatMessage>, budget: usize) -> bool {
+    let Some(end) = plan_compaction(messages, budget) else {
+        return false;
+    };
+
+    let old = &messages[1..end];
+    let summary = if use_offline(llm) {
+        local_summary(old)
+    } else {
+        match summarize(llm, old).await {
+            Ok(summary) if !summary.is_empty() => summary,
+            Ok(_) => local_summary(old),
+            Err(e) => {
+                eprintln!("Failed to summarize history: {}", e);
+                local_summary(old)
+            }
+        }
+    };
+
+    apply_summary(messages, end, &summary);
+    true
+}
+
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_make_classification_prompt_contains_input() {
+    fn test_classification_prompt_contains_input() {
         let input = "明日までに洗濯";
-        let prompt = make_classification_prompt(input);
+        let prompt = prompts::template(prompts::CLASSIFY_INPUT).render(&[("input", input)]);
         assert!(prompt.contains(input));
         assert!(prompt.contains("タスク")); // 安全確認
     }
 
+    #[test]
+    fn test_compaction_keeps_system_and_recent_turns() {
+        let mut messages = vec![ChatMessage::new("system", "prompt")];
+        for i in 0..20 {
+            messages.push(ChatMessage::new("user", format!("{}回目の相談です。今日はとても忙しかったです。", i)));
+            messages.push(ChatMessage::new("assistant", "お疲れさまでした。少し休みましょうね。"));
+        }
+        let last = messages.last().unwrap().content.clone();
+
+        let end = plan_compaction(&messages, 200).expect("予算超過なので要約されるはず");
+        let summary = local_summary(&messages[1..end]);
+        apply_summary(&mut messages, end, &summary);
+
+        assert_eq!(messages.len(), 2 + KEEP_RECENT_MESSAGES);
+        assert_eq!(messages[0].content, "prompt");
+        assert!(messages[1].content.starts_with(SUMMARY_HEADER));
+        assert_eq!(messages.last().unwrap().content, last);
+
+        // 要約済みで直近しか残っていなければ、それ以上は縮めない
+        assert_eq!(plan_compaction(&messages, 10), None);
+    }
+
+    #[test]
+    fn test_parse_fact_lines() {
+        let facts = parse_fact_lines("好み|辛いものが好き\n人物|妹は大阪に住んでいる\nなし");
+        assert_eq!(facts.len(), 2);
+        assert_eq!(facts[1], (FactCategory::Person, "妹は大阪に住んでいる".to_string()));
+    }
+
+    #[test]
+    fn test_detect_special_command() {
+        assert_eq!(detect_special_command("タスク一覧を見せて"), Some("list"));
+        assert_eq!(detect_special_command("今月のAPI利用状況"), Some("usage"));
+        assert_eq!(detect_special_command("会話履歴"), Some("history"));
+        assert_eq!(detect_special_command("こんにちは"), None);
+    }
+
     #[test]
     fn test_classify_mode_formatting() {
         let result = "タスク".trim().to_lowercase();
@@ -0,0 +1,200 @@
+use crate::models::{ChatMessage, Task};
+use crate::tasks;
+use crate::tokens::estimate_tokens;
+use crate::memory::MemoryStore;
+use crate::encourage;
+
+use chrono::{DateTime, Datelike, Duration, Local};
+use std::env;
+use std::fs;
+
+/// システムプロンプトに載せるタスク一覧の上限（おおよそのトークン数）
+pub const DEFAULT_TASK_TOKEN_BUDGET: usize = 400;
+
+pub const DEFAULT_PROFILE_FILE: &str = "profile.json";
+
+// 「最近終わったこと」として扱う期間と件数
+const RECENT_COMPLETED_DAYS: i64 = 3;
+const RECENT_COMPLETED_LIMIT: usize = 5;
+
+const WEEKDAYS: [&str; 7] = ["月", "火", "水", "木", "金", "土", "日"];
+
+fn format_task_line(task: &Task) -> String {
+    let mut attrs = Vec::new();
+    if let Some(due) = task.due_date {
+        attrs.push(format!("期限: {}", due));
+    }
+    if let Some(priority) = task.priority {
+        attrs.push(format!("優先度: {}", priority));
+    }
+
+    if attrs.is_empty() {
+        format!("・[{}] {}", task.id, task.title)
+    } else {
+        format!("・[{}] {}（{}）", task.id, task.title, attrs.join("、"))
+    }
+}
+
+/// 未完了タスクを期限・優先度順に並べ、token_budget に収まる分だけ列挙する
+pub fn format_open_tasks(tasks: &[Task], token_budget: usize) -> String {
+    if tasks.is_empty() {
+        return "現在、登録されているタスクはありません。".to_string();
+    }
+
+    let open = tasks::open_tasks_sorted(tasks);
+    if open.is_empty() {
+        return "未完了のタスクはありません。".to_string();
+    }
+
+    let header = "現在の未完了タスク一覧:";
+    let mut used = estimate_tokens(header);
+    let mut lines = vec![header.to_string()];
+
+    for (i, task) in open.iter().enumerate() {
+        let line = format_task_line(task);
+        let cost = estimate_tokens(&line);
+        if used + cost > token_budget {
+            lines.push(format!("…ほか {} 件", open.len() - i));
+            break;
+        }
+        used += cost;
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
+
+/// 直近 RECENT_COMPLETED_DAYS 日に完了したタスクを新しい順に返す
+pub fn recently_completed(tasks: &[Task], now: DateTime<Local>) -> Vec<Task> {
+    fn walk(out: &mut Vec<(DateTime<Local>, Task)>, tasks: &[Task], since: DateTime<Local>) {
+        for t in tasks {
+            if t.done
+                && let Some(at) = tasks::completed_at(t)
+                && at.with_timezone(&Local) >= since
+            {
+                out.push((at.with_timezone(&Local), t.clone()));
+            }
+            walk(out, &t.subtasks, since);
+        }
+    }
+
+    let mut found = Vec::new();
+    walk(&mut found, tasks, now - Duration::days(RECENT_COMPLETED_DAYS));
+    found.sort_by_key(|(at, _)| std::cmp::Reverse(*at));
+    found.into_iter().take(RECENT_COMPLETED_LIMIT).map(|(_, t)| t).collect()
+}
+
+/// ユーザーのプロフィール（覚えておいてほしいこと）を読み込む
+/// PROFILE_FILE（既定: profile.json）に文字列の配列で書く
+pub fn load_profile_facts() -> Vec<String> {
+    let path = env::var("PROFILE_FILE").unwrap_or_else(|_| DEFAULT_PROFILE_FILE.to_string());
+    let Ok(raw) = fs::read_to_string(&path) else {
+        return vec![];
+    };
+    serde_json::from_str(&raw).unwrap_or_else(|e| {
+        eprintln!("Failed to parse profile file: {} ({})", path, e);
+        vec![]
+    })
+}
+
+/// 基本のシステムプロンプトに、現在日時・タスク・プロフィールを付け足す
+pub fn build_system_prompt(base: &str, now: DateTime<Local>, tasks: &[Task], profile: &[String]) -> String {
+    let weekday = WEEKDAYS[now.weekday().num_days_from_monday() as usize];
+    let mut sections = vec![
+        base.trim().to_string(),
+        format!("【現在日時】\n{}（{}）{}", now.format("%Y年%m月%d日"), weekday, now.format("%H:%M")),
+        format!("【タスク】\n{}", format_open_tasks(tasks, DEFAULT_TASK_TOKEN_BUDGET)),
+    ];
+
+    let done = recently_completed(tasks, now);
+    if !done.is_empty() {
+        let list = done.iter().map(|t| format!("・{}", t.title)).collect::<Vec<_>>().join("\n");
+        sections.push(format!("【最近完了したこと】\n{}", list));
+    }
+
+    if !profile.is_empty() {
+        let list = profile.iter().map(|f| format!("・{}", f)).collect::<Vec<_>>().join("\n");
+        sections.push(format!("【ユーザーについて】\n{}", list));
+    }
+
+    sections.push("上の情報をもとに、「今日何をやればいい？」のような質問には実際のタスクに沿って答えてください。".to_string());
+    sections.join("\n\n")
+}
+
+/// 会話履歴の先頭（system）を最新の状況で差し替える。毎ターン呼ぶ想定
+pub fn refresh_system_message(messages: &mut Vec<ChatMessage>, base: &str) {
+    let tasks = tasks::load_tasks::<&str>(None);
+    let mut profile = load_profile_facts();
+    profile.extend(MemoryStore::open_default().prompt_lines());
+    let mut content = build_system_prompt(base, Local::now(), &tasks, &profile);
+
+    // まだ答えを知らない話題を、会話が途切れたとき用に渡しておく
+    content.push_str(&format!("\n\n【話題に困ったら】\n{}", encourage::random_topic_excluding(&profile)));
+
+    match messages.first_mut() {
+        Some(first) if first.role == "system" => first.content = content,
+        _ => messages.insert(0, ChatMessage::new("system", content)),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{TaskStatus, Visibility};
+    use chrono::{NaiveDate, TimeZone};
+    use serde_json::Map;
+
+    fn task(id: u32, title: &str, due: Option<NaiveDate>, priority: Option<u8>) -> Task {
+        Task {
+            id,
+            title: title.into(),
+            done: false,
+            due_date: due,
+            priority,
+            status: TaskStatus::NotStarted,
+            visibility: Visibility::Visible,
+            notes: None,
+            tags: vec![],
+            subtasks: vec![],
+            extensions: Map::new(),
+        }
+    }
+
+    #[test]
+    fn test_open_tasks_sorted_by_due_then_priority() {
+        let d = |day| NaiveDate::from_ymd_opt(2025, 1, day);
+        let tasks = vec![
+            task(1, "期限なし", None, Some(1)),
+            task(2, "遅い期限", d(20), None),
+            task(3, "早い期限", d(10), Some(3)),
+            task(4, "同じ期限で優先", d(10), Some(1)),
+        ];
+
+        let text = format_open_tasks(&tasks, DEFAULT_TASK_TOKEN_BUDGET);
+        let order: Vec<usize> = ["同じ期限で優先", "早い期限", "遅い期限", "期限なし"]
+            .iter()
+            .map(|t| text.find(t).unwrap())
+            .collect();
+        assert!(order.windows(2).all(|w| w[0] < w[1]), "{}", text);
+    }
+
+    #[test]
+    fn test_open_tasks_truncated_to_budget() {
+        let tasks: Vec<Task> = (1..=50).map(|i| task(i, "とても長いタスクのタイトルです", None, None)).collect();
+
+        let text = format_open_tasks(&tasks, 60);
+        assert!(estimate_tokens(&text) < 80);
+        assert!(text.contains("…ほか"));
+    }
+
+    #[test]
+    fn test_system_prompt_contains_date_and_profile() {
+        let now = Local.with_ymd_and_hms(2025, 6, 2, 9, 30, 0).unwrap();
+        let prompt = build_system_prompt("base", now, &[task(1, "週報提出", None, None)], &["朝はコーヒー派".to_string()]);
+
+        assert!(prompt.contains("2025年06月02日（月）09:30"));
+        assert!(prompt.contains("週報提出"));
+        assert!(prompt.contains("朝はコーヒー派"));
+    }
+}
@@ -1,25 +1,36 @@
 use rand::prelude::IndexedRandom;
+use crate::persona::Persona;
 
-pub fn random_encouragement() -> &'static str{
-    let encouragements = [
-        "焦らず、自分のペースで進めましょうね。",
-        "無理せず、できることからで大丈夫ですよ。",
-        "あなたならきっと大丈夫です！",
-        "今日も一歩前進ですね。応援しています。",
-        "疲れたら、少し休むのも大事ですよ。",
-        "頑張りすぎないでくださいね。ことのははいつでも味方です。"
-    ];
+// 話題と、その答えを覚えていたら避けるためのキーワード
+const TOPICS: [(&str, &str); 6] = [
+    ("ところで、最近ハマっていることはありますか？", "ハマ"),
+    ("最近見た映画や本でおすすめはありますか？", "映画"),
+    ("お休みの日はどんなふうに過ごされていますか？", "休み"),
+    ("好きな食べ物を教えてください！", "食べ物"),
+    ("最近チャレンジしたことがあれば、ぜひ聞かせてください！", "チャレンジ"),
+    ("今日の天気、いい感じでしたか？", "天気"),
+];
 
-    encouragements.choose(&mut rand::rng()).unwrap()
-}
 pub fn random_topic() -> &'static str {
-    let topics = [
-        "ところで、最近ハマっていることはありますか？",
-        "最近見た映画や本でおすすめはありますか？",
-        "お休みの日はどんなふうに過ごされていますか？",
-        "好きな食べ物を教えてください！",
-        "最近チャレンジしたことがあれば、ぜひ聞かせてください！",
-        "今日の天気、いい感じでしたか？"
-    ];
-    topics.choose(&mut rand::rng()).unwrap()
+    random_topic_excluding(&[])
+}
+
+/// 覚えている事実（known）で既に答えが分かっている話題を避けて選ぶ
+/// 全部分かっていたら、どれかをもう一度聞く
+pub fn random_topic_excluding(known: &[String]) -> &'static str {
+    let fresh: Vec<&str> = TOPICS
+        .iter()
+        .filter(|(_, keyword)| !known.iter().any(|fact| fact.contains(keyword)))
+        .map(|(topic, _)| *topic)
+        .collect();
+
+    if fresh.is_empty() {
+        return TOPICS.choose(&mut rand::rng()).unwrap().0;
+    }
+    fresh.choose(&mut rand::rng()).unwrap()
+}
+
+/// 励ましの言葉と話題のすべて（音声キャッシュの先読み用）
+pub fn fixed_phrases(persona: &Persona) -> Vec<String> {
+    persona.encouragements.iter().cloned().chain(TOPICS.iter().map(|(topic, _)| topic.to_string())).collect()
 }
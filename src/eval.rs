@@ -0,0 +1,263 @@
+use crate::cache::normalize_input;
+use crate::chat;
+use crate::llm::LlmClient;
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+pub const DEFAULT_DATASET: &str = "eval/dataset.jsonl";
+pub const DEFAULT_BASELINE: &str = "eval/baseline.json";
+
+// 呼び出しに失敗したときの予測ラベル
+const ERROR_LABEL: &str = "(エラー)";
+
+/// 正解付きの発言1件（JSONL の1行）
+/// action は kind が「タスク」のときの操作、title は「追加」のときのタスク名
+#[derive(Debug, Clone, Deserialize)]
+pub struct EvalCase {
+    pub input: String,
+    pub kind: String,
+    #[serde(default)]
+    pub action: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+pub fn load_dataset(path: &Path) -> Result<Vec<EvalCase>, Box<dyn Error>> {
+    let mut cases = Vec::new();
+    for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let case = serde_json::from_str(&line).map_err(|e| format!("{}:{}: {}", path.display(), i + 1, e))?;
+        cases.push(case);
+    }
+    Ok(cases)
+}
+
+/// 混同行列（正解 × 予測）
+#[derive(Debug, Clone, Default)]
+pub struct ConfusionMatrix {
+    counts: BTreeMap<(String, String), u32>,
+}
+
+impl ConfusionMatrix {
+    pub fn record(&mut self, expected: &str, actual: &str) {
+        *self.counts.entry((expected.to_string(), actual.to_string())).or_default() += 1;
+    }
+
+    pub fn count(&self, expected: &str, actual: &str) -> u32 {
+        self.counts.get(&(expected.to_string(), actual.to_string())).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u32 {
+        self.counts.values().sum()
+    }
+
+    pub fn correct(&self) -> u32 {
+        self.counts.iter().filter(|((e, a), _)| e == a).map(|(_, n)| n).sum()
+    }
+
+    pub fn accuracy(&self) -> f64 {
+        ratio(self.correct(), self.total()).unwrap_or(0.0)
+    }
+
+    /// 正解・予測のどちらかに出てきたラベル
+    pub fn labels(&self) -> BTreeSet<&str> {
+        self.counts.keys().flat_map(|(e, a)| [e.as_str(), a.as_str()]).collect()
+    }
+
+    /// label と予測したうち、正しかった割合（一度も予測していなければ None）
+    pub fn precision(&self, label: &str) -> Option<f64> {
+        let predicted = self.counts.iter().filter(|((_, a), _)| a == label).map(|(_, n)| n).sum();
+        ratio(self.count(label, label), predicted)
+    }
+
+    /// 正解が label のうち、当てられた割合（正解に無ければ None）
+    pub fn recall(&self, label: &str) -> Option<f64> {
+        let expected = self.counts.iter().filter(|((e, _), _)| e == label).map(|(_, n)| n).sum();
+        ratio(self.count(label, label), expected)
+    }
+
+    /// ラベルごとの適合率・再現率と、行列そのものを表にする
+    pub fn render(&self) -> String {
+        let labels: Vec<&str> = self.labels().into_iter().collect();
+        let mut lines = vec![format!("正解率 {:.1}%（{}/{}）", self.accuracy() * 100.0, self.correct(), self.total())];
+
+        for label in &labels {
+            lines.push(format!(
+                "  {}: 適合率 {} / 再現率 {}",
+                label,
+                percent(self.precision(label)),
+                percent(self.recall(label))
+            ));
+        }
+
+        lines.push(format!("  正解＼予測\t{}", labels.join("\t")));
+        for expected in &labels {
+            let row = labels.iter().map(|actual| self.count(expected, actual).to_string()).collect::<Vec<_>>();
+            lines.push(format!("  {}\t{}", expected, row.join("\t")));
+        }
+        lines.join("\n")
+    }
+}
+
+fn ratio(n: u32, d: u32) -> Option<f64> {
+    (d > 0).then(|| n as f64 / d as f64)
+}
+
+fn percent(value: Option<f64>) -> String {
+    value.map_or("-".to_string(), |v| format!("{:.1}%", v * 100.0))
+}
+
+/// 評価の結果
+#[derive(Debug, Clone, Default)]
+pub struct EvalReport {
+    pub classify: ConfusionMatrix,
+    pub action: ConfusionMatrix,
+    pub extract_correct: u32,
+    pub extract_total: u32,
+    pub errors: Vec<String>,
+    pub mistakes: Vec<String>,
+}
+
+impl EvalReport {
+    /// 指標名 → 正解率（baseline.json と同じ名前）
+    pub fn scores(&self) -> BTreeMap<String, f64> {
+        let mut scores = BTreeMap::new();
+        if self.classify.total() > 0 {
+            scores.insert("classify_input".to_string(), self.classify.accuracy());
+        }
+        if self.action.total() > 0 {
+            scores.insert("classify_task_action".to_string(), self.action.accuracy());
+        }
+        if let Some(accuracy) = ratio(self.extract_correct, self.extract_total) {
+            scores.insert("extract_task".to_string(), accuracy);
+        }
+        scores
+    }
+
+    /// baseline を下回った指標（なければ空）
+    pub fn regressions(&self, baseline: &BTreeMap<String, f64>) -> Vec<String> {
+        let scores = self.scores();
+        baseline
+            .iter()
+            .filter_map(|(name, floor)| {
+                let score = scores.get(name).copied().unwrap_or(0.0);
+                (score + 1e-9 < *floor).then(|| format!("{}: {:.1}% < 基準 {:.1}%", name, score * 100.0, floor * 100.0))
+            })
+            .collect()
+    }
+
+    pub fn describe(&self) -> String {
+        let mut sections = vec![
+            format!("■ 入力の分類（classify_input）\n{}", self.classify.render()),
+            format!("■ タスク操作の分類（classify_task_action）\n{}", self.action.render()),
+            format!(
+                "■ タスク名の抽出（extract_task）\n正解率 {}（{}/{}）",
+                percent(ratio(self.extract_correct, self.extract_total)),
+                self.extract_correct,
+                self.extract_total
+            ),
+        ];
+        if !self.mistakes.is_empty() {
+            sections.push(format!("■ 間違えた発言\n{}", self.mistakes.join("\n")));
+        }
+        if !self.errors.is_empty() {
+            sections.push(format!("■ 呼び出しの失敗\n{}", self.errors.join("\n")));
+        }
+        sections.join("\n\n")
+    }
+}
+
+/// 基準値（{"指標名": 正解率の下限}）を読む
+pub fn load_baseline(path: &Path) -> Result<BTreeMap<String, f64>, Box<dyn Error>> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+pub fn save_baseline(path: &Path, scores: &BTreeMap<String, f64>) -> Result<(), Box<dyn Error>> {
+    fs::write(path, serde_json::to_string_pretty(scores)? + "\n")?;
+    Ok(())
+}
+
+fn label_or_error(result: Result<String, Box<dyn Error>>, input: &str, errors: &mut Vec<String>) -> String {
+    result.unwrap_or_else(|e| {
+        errors.push(format!("・{}: {}", input, e));
+        ERROR_LABEL.to_string()
+    })
+}
+
+/// データセットを本番と同じ関数に通して採点する
+pub async fn run(llm: &LlmClient, cases: &[EvalCase]) -> EvalReport {
+    let mut report = EvalReport::default();
+
+    for case in cases {
+        let kind = label_or_error(chat::classify_input(llm, &case.input).await, &case.input, &mut report.errors);
+        report.classify.record(&case.kind, &kind);
+        if kind != case.kind {
+            report.mistakes.push(format!("・{}: 分類 {} → {}", case.input, case.kind, kind));
+        }
+
+        if let Some(expected) = &case.action {
+            let action = label_or_error(chat::classify_task_action(llm, &case.input).await, &case.input, &mut report.errors);
+            report.action.record(expected, &action);
+            if action != *expected {
+                report.mistakes.push(format!("・{}: 操作 {} → {}", case.input, expected, action));
+            }
+        }
+
+        if let Some(expected) = &case.title {
+            report.extract_total += 1;
+            let title = label_or_error(chat::extract_task(llm, &case.input).await, &case.input, &mut report.errors);
+            if normalize_input(&title) == normalize_input(expected) {
+                report.extract_correct += 1;
+            } else {
+                report.mistakes.push(format!("・{}: タスク名 「{}」 → 「{}」", case.input, expected, title));
+            }
+        }
+    }
+
+    report
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precision_and_recall() {
+        let mut matrix = ConfusionMatrix::default();
+        matrix.record("タスク", "タスク");
+        matrix.record("タスク", "タスク");
+        matrix.record("タスク", "雑談");
+        matrix.record("雑談", "雑談");
+
+        assert_eq!(matrix.accuracy(), 0.75);
+        assert_eq!(matrix.precision("タスク"), Some(1.0));
+        assert!((matrix.recall("タスク").unwrap() - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(matrix.precision("雑談"), Some(0.5));
+        assert_eq!(matrix.recall("なし"), None);
+        assert!(matrix.render().contains("正解率 75.0%"));
+    }
+
+    #[test]
+    fn test_regressions_against_baseline() {
+        let mut report = EvalReport::default();
+        report.classify.record("雑談", "雑談");
+        report.classify.record("雑談", "タスク");
+        report.extract_correct = 1;
+        report.extract_total = 1;
+
+        let baseline = BTreeMap::from([("classify_input".to_string(), 0.5), ("extract_task".to_string(), 1.0)]);
+        assert!(report.regressions(&baseline).is_empty());
+
+        let stricter = BTreeMap::from([("classify_input".to_string(), 0.9)]);
+        assert_eq!(report.regressions(&stricter).len(), 1);
+    }
+}
@@ -0,0 +1,414 @@
+use crate::reading;
+use crate::tts::{TtsEngine, TtsError, TtsFuture, Voice, VoiceSpec};
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// 落ちたエンジンをもう一度試すまでの間隔
+pub const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+// 合成コマンドがこれ以上かかったら諦める
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// PATH から実行ファイルを探す
+pub fn find_program(name: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+    env::split_paths(&path).map(|dir| dir.join(name)).find(|p| p.is_file())
+}
+
+/// オフラインの合成コマンドの種類
+#[derive(Debug, Clone)]
+pub enum CommandKind {
+    /// open_jtalk -x 辞書 -m 声 -ow 出力（本文は標準入力）
+    OpenJTalk { dictionary: PathBuf, voice: PathBuf },
+    /// espeak-ng -v 声 -w 出力 本文
+    EspeakNg { voice: String },
+}
+
+/// ローカルの合成コマンドをサブプロセスで呼ぶエンジン（VoiceVox が動いていないとき用）
+pub struct CommandEngine {
+    program: PathBuf,
+    kind: CommandKind,
+}
+
+impl CommandEngine {
+    pub fn new(program: &Path, kind: CommandKind) -> Self {
+        Self { program: program.to_path_buf(), kind }
+    }
+
+    /// 設定から選ぶ（TTS_FALLBACK=open_jtalk / espeak-ng / off、既定: 使えるほう）
+    /// - open_jtalk: OPEN_JTALK_DIC（辞書ディレクトリ）と OPEN_JTALK_VOICE（.htsvoice）が要る
+    /// - espeak-ng: ESPEAK_VOICE（既定: ja）
+    pub fn from_env() -> Option<Self> {
+        let choice = env::var("TTS_FALLBACK").ok();
+        let open_jtalk = || {
+            let dictionary = PathBuf::from(env::var("OPEN_JTALK_DIC").ok()?);
+            let voice = PathBuf::from(env::var("OPEN_JTALK_VOICE").ok()?);
+            Some(Self::new(&find_program("open_jtalk")?, CommandKind::OpenJTalk { dictionary, voice }))
+        };
+        let espeak = || {
+            let voice = env::var("ESPEAK_VOICE").unwrap_or_else(|_| "ja".to_string());
+            Some(Self::new(&find_program("espeak-ng")?, CommandKind::EspeakNg { voice }))
+        };
+
+        match choice.as_deref() {
+            Some("off") | Some("none") => None,
+            Some("open_jtalk") => open_jtalk(),
+            Some("espeak-ng") | Some("espeak") => espeak(),
+            _ => open_jtalk().or_else(espeak),
+        }
+    }
+
+    fn command(&self, text: &str, out: &Path, voice: &VoiceSpec) -> Command {
+        let speed = voice.params.speed_scale.unwrap_or(1.0);
+        let mut command = Command::new(&self.program);
+        match &self.kind {
+            CommandKind::OpenJTalk { dictionary, voice } => {
+                command.arg("-x").arg(dictionary).arg("-m").arg(voice).arg("-r").arg(speed.to_string()).arg("-ow").arg(out);
+            }
+            CommandKind::EspeakNg { voice } => {
+                // espeak-ng の既定の速さは毎分175語
+                let words_per_minute = (175.0 * speed).round() as u32;
+                command.arg("-v").arg(voice).arg("-s").arg(words_per_minute.to_string()).arg("-w").arg(out).arg(text);
+            }
+        }
+        command
+    }
+
+    async fn run(&self, text: &str, voice: &VoiceSpec) -> Result<Vec<u8>, TtsError> {
+        // マークアップは解けないので、読みを置き換えた1本の文にする
+        let text = &reading::plain_text(text, reading::dictionary());
+        let out = env::temp_dir().join(format!("kotonoha_tts_{}.wav", uuid::Uuid::new_v4()));
+        let mut child = self
+            .command(text, &out, voice)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        // Open JTalk は本文を標準入力から読む。閉じて終わりを知らせる
+        if let Some(mut stdin) = child.stdin.take()
+            && matches!(self.kind, CommandKind::OpenJTalk { .. })
+        {
+            stdin.write_all(text.as_bytes()).await?;
+        }
+
+        let output = tokio::time::timeout(COMMAND_TIMEOUT, child.wait_with_output())
+            .await
+            .map_err(|_| TtsError::Engine(format!("{} timed out", self.program.display())))??;
+        let audio = std::fs::read(&out);
+        let _ = std::fs::remove_file(&out);
+
+        if !output.status.success() {
+            return Err(TtsError::Engine(format!(
+                "{} failed ({}): {}",
+                self.program.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let audio = audio?;
+        if !audio.starts_with(b"RIFF") {
+            return Err(TtsError::Engine(format!("{} did not write WAV audio", self.program.display())));
+        }
+        Ok(audio)
+    }
+}
+
+impl TtsEngine for CommandEngine {
+    fn name(&self) -> &str {
+        match self.kind {
+            CommandKind::OpenJTalk { .. } => "open_jtalk",
+            CommandKind::EspeakNg { .. } => "espeak-ng",
+        }
+    }
+
+    fn synthesize<'a>(&'a self, text: &'a str, voice: &'a VoiceSpec) -> TtsFuture<'a, Vec<u8>> {
+        Box::pin(self.run(text, voice))
+    }
+
+    fn voices(&self) -> TtsFuture<'_, Vec<Voice>> {
+        let name = match &self.kind {
+            CommandKind::OpenJTalk { voice, .. } => voice.file_stem().unwrap_or_default().to_string_lossy().to_string(),
+            CommandKind::EspeakNg { voice } => voice.clone(),
+        };
+        Box::pin(async move { Ok(vec![Voice { id: 0, name, style: String::new() }]) })
+    }
+
+    /// コマンドと、Open JTalk なら辞書・声のファイルがあるか
+    fn health_check(&self) -> TtsFuture<'_, ()> {
+        Box::pin(async move {
+            let mut required = vec![self.program.as_path()];
+            if let CommandKind::OpenJTalk { dictionary, voice } = &self.kind {
+                required.extend([dictionary.as_path(), voice.as_path()]);
+            }
+            match required.into_iter().find(|p| !p.exists()) {
+                Some(missing) => Err(TtsError::Engine(format!("{} not found", missing.display()))),
+                None => Ok(()),
+            }
+        })
+    }
+}
+
+/// 声を出さず、文字だけ出す（最後の手段）
+pub struct TextOnlyEngine;
+
+impl TtsEngine for TextOnlyEngine {
+    fn name(&self) -> &str {
+        "text"
+    }
+
+    fn synthesize<'a>(&'a self, text: &'a str, _voice: &'a VoiceSpec) -> TtsFuture<'a, Vec<u8>> {
+        Box::pin(async move {
+            println!("（音声なし）{}", reading::strip_markup(text));
+            Ok(vec![])
+        })
+    }
+
+    fn voices(&self) -> TtsFuture<'_, Vec<Voice>> {
+        Box::pin(async { Ok(vec![]) })
+    }
+
+    fn plays_audio(&self) -> bool {
+        false
+    }
+}
+
+/// 前から順に試すエンジンの列（例: VoiceVox → Open JTalk → 文字だけ）
+/// 落ちている（接続できない・5xx）エンジンは retry_after のあいだ飛ばし、その後ヘルスチェックが通れば元に戻す
+/// 入力を断られただけなら、その文だけ次のエンジンに回し、休ませはしない
+pub struct FallbackEngine {
+    engines: Vec<Arc<dyn TtsEngine>>,
+    down_until: Mutex<Vec<Option<Instant>>>,
+    retry_after: Duration,
+}
+
+impl FallbackEngine {
+    pub fn new(engines: Vec<Arc<dyn TtsEngine>>, retry_after: Duration) -> Self {
+        let down_until = Mutex::new(vec![None; engines.len()]);
+        Self { engines, down_until, retry_after }
+    }
+
+    /// primary のあとに、使えればオフラインのコマンド、最後に文字だけを並べる
+    pub fn with_offline(primary: Arc<dyn TtsEngine>) -> Self {
+        let mut engines = vec![primary];
+        if let Some(command) = CommandEngine::from_env() {
+            engines.push(Arc::new(command));
+        }
+        engines.push(Arc::new(TextOnlyEngine));
+        Self::new(engines, DEFAULT_RETRY_AFTER)
+    }
+
+    /// いま最初に試すエンジンの名前
+    pub fn active(&self) -> &str {
+        let down_until = self.down_until.lock().unwrap();
+        let now = Instant::now();
+        self.engines
+            .iter()
+            .zip(down_until.iter())
+            .find(|(_, until)| until.is_none_or(|t| now >= t))
+            .map_or("none", |(e, _)| e.name())
+    }
+
+    fn mark_down(&self, i: usize) {
+        self.down_until.lock().unwrap()[i] = Some(Instant::now() + self.retry_after);
+    }
+
+    /// i 番目を使ってよいか。休ませていたものは、時間が来たらヘルスチェックで確かめる
+    async fn available(&self, i: usize) -> bool {
+        let until = self.down_until.lock().unwrap()[i];
+        let Some(until) = until else {
+            return true;
+        };
+        if Instant::now() < until {
+            return false;
+        }
+        match self.engines[i].health_check().await {
+            Ok(()) => {
+                eprintln!("TTS engine recovered: {}", self.engines[i].name());
+                self.down_until.lock().unwrap()[i] = None;
+                true
+            }
+            Err(_) => {
+                self.mark_down(i);
+                false
+            }
+        }
+    }
+}
+
+impl TtsEngine for FallbackEngine {
+    fn name(&self) -> &str {
+        "fallback"
+    }
+
+    fn synthesize<'a>(&'a self, text: &'a str, voice: &'a VoiceSpec) -> TtsFuture<'a, Vec<u8>> {
+        Box::pin(async move {
+            let mut last_error = None;
+            for (i, engine) in self.engines.iter().enumerate() {
+                if !self.available(i).await {
+                    continue;
+                }
+                match engine.synthesize(text, voice).await {
+                    Ok(audio) => return Ok(audio),
+                    Err(e) => {
+                        eprintln!("TTS engine {} failed ({}), falling back", engine.name(), e);
+                        if e.is_outage() {
+                            self.mark_down(i);
+                        }
+                        last_error = Some(e);
+                    }
+                }
+            }
+            Err(last_error.unwrap_or_else(|| TtsError::Engine("no TTS engine is available".to_string())))
+        })
+    }
+
+    /// 声の一覧は primary のものだけ。答えられなければ失敗にして、呼び出し側があとで聞き直せるようにする
+    /// （代わりのエンジンの一覧を覚えられると、primary が戻っても名前で選んだ声が見つからない）
+    fn voices(&self) -> TtsFuture<'_, Vec<Voice>> {
+        Box::pin(async move {
+            let primary = self.engines.first().ok_or_else(|| TtsError::Engine("no TTS engine".to_string()))?;
+            let voices = primary.voices().await?;
+            if voices.is_empty() {
+                return Err(TtsError::Engine("no voices".to_string()));
+            }
+            Ok(voices)
+        })
+    }
+
+    fn health_check(&self) -> TtsFuture<'_, ()> {
+        Box::pin(async move {
+            for i in 0..self.engines.len() {
+                if self.available(i).await {
+                    return Ok(());
+                }
+            }
+            Err(TtsError::Engine("no TTS engine is available".to_string()))
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tts::MockEngine;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // up が false のあいだは合成もヘルスチェックも失敗する
+    struct Flaky {
+        up: AtomicBool,
+    }
+
+    impl TtsEngine for Flaky {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        fn synthesize<'a>(&'a self, _text: &'a str, _voice: &'a VoiceSpec) -> TtsFuture<'a, Vec<u8>> {
+            Box::pin(async move {
+                if self.up.load(Ordering::Relaxed) { Ok(b"RIFF".to_vec()) } else { Err(TtsError::Engine("down".into())) }
+            })
+        }
+
+        fn voices(&self) -> TtsFuture<'_, Vec<Voice>> {
+            Box::pin(async move {
+                if self.up.load(Ordering::Relaxed) {
+                    Ok(vec![Voice { id: 2, name: "四国めたん".into(), style: "ノーマル".into() }])
+                } else {
+                    Err(TtsError::Engine("down".into()))
+                }
+            })
+        }
+
+        fn health_check(&self) -> TtsFuture<'_, ()> {
+            Box::pin(async move { if self.up.load(Ordering::Relaxed) { Ok(()) } else { Err(TtsError::Engine("down".into())) } })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_and_recovers() {
+        let primary = Arc::new(Flaky { up: AtomicBool::new(false) });
+        let backup = Arc::new(MockEngine::new());
+        let chain = FallbackEngine::new(vec![primary.clone(), backup.clone()], Duration::from_millis(20));
+        let voice = VoiceSpec::speaker(8);
+
+        assert_eq!(chain.synthesize("一", &voice).await.unwrap(), Vec::<u8>::new());
+        assert_eq!(chain.active(), "mock");
+        // 休ませているあいだは primary を呼ばない
+        primary.up.store(true, Ordering::Relaxed);
+        chain.synthesize("二", &voice).await.unwrap();
+        assert_eq!(backup.take_spoken(), vec!["一", "二"]);
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(chain.synthesize("三", &voice).await.unwrap(), b"RIFF".to_vec());
+        assert_eq!(chain.active(), "flaky");
+        assert!(backup.take_spoken().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_voices_come_only_from_the_primary() {
+        let primary = Arc::new(Flaky { up: AtomicBool::new(false) });
+        let chain = FallbackEngine::new(vec![primary.clone(), Arc::new(MockEngine::new())], Duration::from_millis(20));
+
+        // 落ちているあいだは代わりの一覧を返さず、失敗にする
+        assert!(chain.voices().await.is_err());
+        primary.up.store(true, Ordering::Relaxed);
+        assert_eq!(chain.voices().await.unwrap()[0].name, "四国めたん");
+    }
+
+    // 「変」だけは受け付けない
+    struct Picky;
+
+    impl TtsEngine for Picky {
+        fn name(&self) -> &str {
+            "picky"
+        }
+
+        fn synthesize<'a>(&'a self, text: &'a str, _voice: &'a VoiceSpec) -> TtsFuture<'a, Vec<u8>> {
+            Box::pin(async move {
+                if text == "変" { Err(TtsError::Rejected("bad input".into())) } else { Ok(b"RIFF".to_vec()) }
+            })
+        }
+
+        fn voices(&self) -> TtsFuture<'_, Vec<Voice>> {
+            Box::pin(async { Ok(vec![]) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejected_input_does_not_mark_the_engine_down() {
+        let backup = Arc::new(MockEngine::new());
+        let chain = FallbackEngine::new(vec![Arc::new(Picky), backup.clone()], Duration::from_secs(60));
+        let voice = VoiceSpec::speaker(8);
+
+        // その文だけ次のエンジンに回す
+        assert_eq!(chain.synthesize("変", &voice).await.unwrap(), Vec::<u8>::new());
+        assert_eq!(backup.take_spoken(), vec!["変"]);
+        assert_eq!(chain.active(), "picky");
+        assert_eq!(chain.synthesize("普通", &voice).await.unwrap(), b"RIFF".to_vec());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_engine_reads_the_written_wav() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // espeak-ng の代わりに、-w の次の引数へ WAV を書くだけのスクリプト
+        let script = env::temp_dir().join(format!("fake_espeak_{}.sh", uuid::Uuid::new_v4()));
+        std::fs::write(&script, "#!/bin/sh\nwhile [ \"$1\" != \"-w\" ]; do shift; done\nprintf 'RIFFfake' > \"$2\"\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let engine = CommandEngine::new(&script, CommandKind::EspeakNg { voice: "ja".into() });
+        engine.health_check().await.unwrap();
+        assert_eq!(engine.synthesize("こんにちは", &VoiceSpec::speaker(0)).await.unwrap(), b"RIFFfake".to_vec());
+        let _ = std::fs::remove_file(&script);
+    }
+}
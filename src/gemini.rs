@@ -0,0 +1,259 @@
+use crate::backend::{BackendFuture, LlmBackend, assistant_response, http_error, parse_error, parse_sse};
+use crate::llm::LlmError;
+use crate::models::{ChatRequest, ChatResponse, FunctionCall, ToolCall, Usage};
+use crate::transport::{HttpClient, HttpRequest};
+
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub const DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com";
+
+/// Gemini API（generateContent）
+pub struct GeminiBackend {
+    http: HttpClient,
+    api_key: String,
+    base_url: String,
+    stream: bool,
+}
+
+impl GeminiBackend {
+    pub fn new(http: HttpClient, api_key: &str, base_url: &str) -> Self {
+        Self {
+            http,
+            api_key: api_key.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            stream: false,
+        }
+    }
+
+    pub fn streaming(mut self, stream: bool) -> Self {
+        self.stream = stream;
+        self
+    }
+}
+
+/// ChatRequest を generateContent の形にする
+/// - system は systemInstruction に入れる
+/// - assistant は model、ツール呼び出しは functionCall、tool の結果は functionResponse にする
+/// - 同じ役割が続いたら1つにまとめる
+pub fn to_request_body(request: &ChatRequest) -> Value {
+    let system = request
+        .messages
+        .iter()
+        .filter(|m| m.role == "system")
+        .map(|m| m.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    // functionResponse には関数名が要るので、呼び出しIDから引けるようにしておく
+    let names: HashMap<&str, &str> = request
+        .messages
+        .iter()
+        .flat_map(|m| m.tool_calls.iter().flatten())
+        .map(|c| (c.id.as_str(), c.function.name.as_str()))
+        .collect();
+
+    let mut contents: Vec<(&str, Vec<Value>)> = Vec::new();
+    for m in request.messages.iter().filter(|m| m.role != "system") {
+        let (role, parts) = match m.role.as_str() {
+            "tool" => {
+                let name = m.tool_call_id.as_deref().and_then(|id| names.get(id)).copied().unwrap_or_default();
+                let response: Value = serde_json::from_str(&m.content).unwrap_or_else(|_| json!(m.content));
+                ("user", vec![json!({ "functionResponse": { "name": name, "response": { "content": response } } })])
+            }
+            "assistant" => {
+                let mut parts = Vec::new();
+                if !m.content.is_empty() {
+                    parts.push(json!({ "text": m.content }));
+                }
+                for call in m.tool_calls.iter().flatten() {
+                    let args: Value = serde_json::from_str(&call.function.arguments).unwrap_or_else(|_| json!({}));
+                    parts.push(json!({ "functionCall": { "name": call.function.name, "args": args } }));
+                }
+                ("model", parts)
+            }
+            _ => ("user", vec![json!({ "text": m.content })]),
+        };
+
+        match contents.last_mut() {
+            Some((last, existing)) if *last == role => existing.extend(parts),
+            _ => contents.push((role, parts)),
+        }
+    }
+
+    let mut body = json!({
+        "contents": contents
+            .into_iter()
+            .map(|(role, parts)| json!({ "role": role, "parts": parts }))
+            .collect::<Vec<_>>(),
+    });
+    if !system.is_empty() {
+        body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+    }
+    if let Some(tools) = &request.tools {
+        let declarations: Vec<Value> = tools
+            .iter()
+            .map(|t| {
+                let mut declaration = json!({ "name": t.function.name, "description": t.function.description });
+                // 引数の無い関数に空の properties を渡すと弾かれる
+                if t.function.parameters["properties"].as_object().is_some_and(|p| !p.is_empty()) {
+                    declaration["parameters"] = t.function.parameters.clone();
+                }
+                declaration
+            })
+            .collect();
+        body["tools"] = json!([{ "functionDeclarations": declarations }]);
+    }
+    body
+}
+
+/// 応答1つ（ストリームなら1チャンク）から、本文・関数呼び出し・使用量を拾う
+fn collect_chunk(value: &Value, text: &mut String, tool_calls: &mut Vec<ToolCall>, usage: &mut Option<Usage>) {
+    for part in value["candidates"][0]["content"]["parts"].as_array().into_iter().flatten() {
+        if let Some(t) = part["text"].as_str() {
+            text.push_str(t);
+        }
+        if let Some(call) = part.get("functionCall") {
+            // Gemini は呼び出しIDを返さないので、こちらで振る（会話全体で重ならないように）
+            tool_calls.push(ToolCall {
+                id: format!("call_{}", uuid::Uuid::new_v4().simple()),
+                kind: "function".to_string(),
+                function: FunctionCall {
+                    name: call["name"].as_str().unwrap_or_default().to_string(),
+                    arguments: call["args"].to_string(),
+                },
+            });
+        }
+    }
+
+    let metadata = &value["usageMetadata"];
+    if let Some(prompt) = metadata["promptTokenCount"].as_u64() {
+        let completion = metadata["candidatesTokenCount"].as_u64().unwrap_or(0);
+        *usage = Some(Usage {
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            total_tokens: metadata["totalTokenCount"].as_u64().unwrap_or(prompt + completion),
+        });
+    }
+}
+
+fn blocked(value: &Value) -> Option<String> {
+    let reason = value["promptFeedback"]["blockReason"].as_str()?;
+    Some(format!("Prompt was blocked: {}", reason))
+}
+
+pub fn parse_response(body: &str) -> Result<ChatResponse, LlmError> {
+    let value: Value = serde_json::from_str(body).map_err(|e| LlmError::Parse(e.to_string()))?;
+    if let Some(reason) = blocked(&value) {
+        return Err(LlmError::Parse(reason));
+    }
+    if value["candidates"].as_array().is_none_or(|c| c.is_empty()) {
+        return Err(LlmError::Parse(format!("No candidates found in the response: {}", body)));
+    }
+
+    let (mut text, mut tool_calls, mut usage) = (String::new(), Vec::new(), None);
+    collect_chunk(&value, &mut text, &mut tool_calls, &mut usage);
+    Ok(assistant_response(text, tool_calls, usage))
+}
+
+/// streamGenerateContent?alt=sse のチャンクをつなぐ（使用量は最後のチャンクに入っている）
+pub fn assemble_stream(body: &str) -> Result<ChatResponse, LlmError> {
+    let (mut text, mut tool_calls, mut usage) = (String::new(), Vec::new(), None);
+    for (_, data) in parse_sse(body) {
+        let value: Value = serde_json::from_str(&data).map_err(|e| LlmError::Parse(e.to_string()))?;
+        if let Some(reason) = blocked(&value) {
+            return Err(LlmError::Parse(reason));
+        }
+        collect_chunk(&value, &mut text, &mut tool_calls, &mut usage);
+    }
+    Ok(assistant_response(text, tool_calls, usage))
+}
+
+impl LlmBackend for GeminiBackend {
+    fn send<'a>(&'a self, request: &'a ChatRequest, timeout: Duration) -> BackendFuture<'a> {
+        Box::pin(async move {
+            let method = if self.stream { "streamGenerateContent" } else { "generateContent" };
+            let mut http_request = HttpRequest::post(format!("{}/v1beta/models/{}:{}", self.base_url, request.model, method))
+                .header("x-goog-api-key", &self.api_key)
+                .timeout(timeout)
+                .json(&to_request_body(request))
+                .map_err(parse_error)?;
+            if self.stream {
+                http_request = http_request.query("alt", "sse");
+            }
+            let response = self.http.send(http_request).await.map_err(|e| (LlmError::from(e), None))?;
+
+            if !response.status.is_success() {
+                return Err(http_error(&response));
+            }
+            let body = response.text();
+            if self.stream { assemble_stream(&body) } else { parse_response(&body) }.map_err(|e| (e, None))
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ChatMessage;
+
+    #[test]
+    fn test_function_response_uses_the_called_name() {
+        let mut assistant = ChatMessage::new("assistant", "");
+        assistant.tool_calls = Some(vec![ToolCall {
+            id: "call_0".into(),
+            kind: "function".into(),
+            function: FunctionCall { name: "search_tasks".into(), arguments: r#"{"query":"牛乳"}"#.into() },
+        }]);
+        let request = ChatRequest {
+            model: "gemini-1.5-flash".into(),
+            messages: vec![
+                ChatMessage::new("system", "秘書です"),
+                ChatMessage::new("user", "牛乳のタスクある？"),
+                assistant,
+                ChatMessage::tool_result("call_0", r#"[{"id":1}]"#),
+            ],
+            tools: None,
+        };
+
+        let body = to_request_body(&request);
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "秘書です");
+        let contents = body["contents"].as_array().unwrap();
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(contents[1]["parts"][0]["functionCall"]["args"]["query"], "牛乳");
+        assert_eq!(contents[2]["parts"][0]["functionResponse"]["name"], "search_tasks");
+        assert_eq!(contents[2]["parts"][0]["functionResponse"]["response"]["content"][0]["id"], 1);
+    }
+
+    #[test]
+    fn test_tool_call_ids_stay_unique_across_steps() {
+        let step = |name: &str| {
+            let body = json!({ "candidates": [{ "content": { "parts": [{ "functionCall": { "name": name, "args": {} } }] } }] });
+            parse_response(&body.to_string()).unwrap().choices.remove(0).message
+        };
+        let first = step("search_tasks");
+        let second = step("list_tasks");
+        let id = |m: &ChatMessage| m.tool_calls.as_ref().unwrap()[0].id.clone();
+        assert_ne!(id(&first), id(&second));
+
+        let request = ChatRequest {
+            model: "gemini-1.5-flash".into(),
+            messages: vec![
+                ChatMessage::new("user", "牛乳のタスクある？"),
+                first.clone(),
+                ChatMessage::tool_result(&id(&first), "[]"),
+                second.clone(),
+                ChatMessage::tool_result(&id(&second), "[]"),
+            ],
+            tools: None,
+        };
+
+        // 前の手の結果も、その手で呼んだ関数の名前で返す
+        let body = to_request_body(&request);
+        let contents = body["contents"].as_array().unwrap();
+        assert_eq!(contents[2]["parts"][0]["functionResponse"]["name"], "search_tasks");
+        assert_eq!(contents[4]["parts"][0]["functionResponse"]["name"], "list_tasks");
+    }
+}
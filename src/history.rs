@@ -0,0 +1,240 @@
+use crate::models::ChatMessage;
+
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_HISTORY_DIR: &str = "sessions";
+pub const DEFAULT_RETENTION_DAYS: i64 = 30;
+
+/// 会話ログの1行（JSONL）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub timestamp: DateTime<Local>,
+    pub role: String,
+    pub content: String,
+}
+
+/// 過去セッションの概要
+#[derive(Debug, Clone)]
+pub struct SessionInfo {
+    pub id: String,
+    pub path: PathBuf,
+    pub started: DateTime<Local>,
+    pub last: DateTime<Local>,
+    pub message_count: usize,
+}
+
+/// 1セッション分の会話ログ（sessions/<id>.jsonl に追記していく）
+pub struct SessionLog {
+    id: String,
+    path: PathBuf,
+}
+
+/// HISTORY_DIR（既定: sessions）
+pub fn history_dir() -> PathBuf {
+    PathBuf::from(env::var("HISTORY_DIR").unwrap_or_else(|_| DEFAULT_HISTORY_DIR.to_string()))
+}
+
+/// HISTORY_RETENTION_DAYS（既定: 30日）
+pub fn retention_days() -> i64 {
+    env::var("HISTORY_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_RETENTION_DAYS)
+}
+
+impl SessionLog {
+    /// 新しいセッションを始める
+    pub fn start(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let id = format!(
+            "{}-{}",
+            Local::now().format("%Y%m%d-%H%M%S"),
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        );
+        let path = dir.join(format!("{}.jsonl", id));
+        Ok(Self { id, path })
+    }
+
+    /// 既存のセッションに追記を続ける
+    pub fn resume(info: &SessionInfo) -> Self {
+        Self { id: info.id.clone(), path: info.path.clone() }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// メッセージを1行追記する（system は毎ターン作り直すので残さない）
+    pub fn append(&self, message: &ChatMessage) -> io::Result<()> {
+        if message.role == "system" {
+            return Ok(());
+        }
+
+        let entry = HistoryEntry {
+            timestamp: Local::now(),
+            role: message.role.clone(),
+            content: message.content.clone(),
+        };
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)
+    }
+
+    /// 会話履歴に積みつつログにも書く。書き込み失敗は会話を止めない
+    pub fn push(&self, messages: &mut Vec<ChatMessage>, message: ChatMessage) {
+        if let Err(e) = self.append(&message) {
+            eprintln!("Failed to write session log: {} ({})", self.path.display(), e);
+        }
+        messages.push(message);
+    }
+}
+
+/// セッションファイルを読み込む。壊れた行は読み飛ばす
+pub fn load_session(path: &Path) -> Vec<HistoryEntry> {
+    let Ok(file) = File::open(path) else {
+        return vec![];
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect()
+}
+
+/// 保存されているセッションを新しい順に返す
+pub fn list_sessions(dir: &Path) -> Vec<SessionInfo> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let mut sessions: Vec<SessionInfo> = entries
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+        .filter_map(|path| {
+            let log = load_session(&path);
+            let (first, last) = (log.first()?, log.last()?);
+            Some(SessionInfo {
+                id: path.file_stem()?.to_string_lossy().into_owned(),
+                started: first.timestamp,
+                last: last.timestamp,
+                message_count: log.len(),
+                path,
+            })
+        })
+        .collect();
+
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.last));
+    sessions
+}
+
+/// 過去の会話からキーワードを含む発言を探す（新しいセッションから順に）
+pub fn search_sessions(dir: &Path, keyword: &str) -> Vec<(String, HistoryEntry)> {
+    list_sessions(dir)
+        .into_iter()
+        .flat_map(|info| {
+            load_session(&info.path)
+                .into_iter()
+                .filter(|e| e.content.contains(keyword))
+                .map(move |e| (info.id.clone(), e))
+        })
+        .collect()
+}
+
+/// 最後の発言が retention_days より古いセッションを削除し、削除件数を返す
+pub fn prune_sessions(dir: &Path, retention_days: i64, now: DateTime<Local>) -> usize {
+    let limit = now - Duration::days(retention_days);
+    list_sessions(dir)
+        .into_iter()
+        .filter(|s| s.last < limit)
+        .filter(|s| fs::remove_file(&s.path).is_ok())
+        .count()
+}
+
+/// セッションのやり取りを会話履歴（ChatMessage）に戻す
+pub fn restore_messages(info: &SessionInfo) -> Vec<ChatMessage> {
+    load_session(&info.path)
+        .into_iter()
+        .map(|e| ChatMessage::new(&e.role, e.content))
+        .collect()
+}
+
+/// 前回のセッションで話したことをひとことで振り返る（挨拶用）
+pub fn recap_previous_session(sessions: &[SessionInfo], current_id: &str, now: DateTime<Local>) -> Option<String> {
+    let previous = sessions.iter().find(|s| s.id != current_id)?;
+    let topic = load_session(&previous.path)
+        .into_iter()
+        .rev()
+        .find(|e| e.role == "user")?
+        .content;
+    let topic: String = topic.chars().take(20).collect();
+
+    let days_ago = (now.date_naive() - previous.last.date_naive()).num_days();
+    let when = match days_ago {
+        0 => "さきほど".to_string(),
+        1 => "昨日".to_string(),
+        _ => format!("{}", previous.last.format("%m月%d日")),
+    };
+    Some(format!("{}は「{}」についてお話ししましたね。", when, topic))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            Self(env::temp_dir().join(format!("kotonoha_history_{}", Uuid::new_v4())))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_session_roundtrip_and_search() {
+        let dir = TempDir::new();
+        let log = SessionLog::start(&dir.0).unwrap();
+        let mut messages = vec![ChatMessage::new("system", "prompt")];
+
+        log.push(&mut messages, ChatMessage::new("user", "明日の会議の準備をしたい"));
+        log.push(&mut messages, ChatMessage::new("assistant", "承知しました。"));
+
+        let sessions = list_sessions(&dir.0);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, log.id());
+        assert_eq!(sessions[0].message_count, 2);
+        assert_eq!(restore_messages(&sessions[0]).len(), 2);
+
+        let hits = search_sessions(&dir.0, "会議");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].1.role, "user");
+    }
+
+    #[test]
+    fn test_prune_and_recap() {
+        let dir = TempDir::new();
+        let old = SessionLog::start(&dir.0).unwrap();
+        old.append(&ChatMessage::new("user", "週末は山に行きます")).unwrap();
+
+        let current = SessionLog::start(&dir.0).unwrap();
+        let sessions = list_sessions(&dir.0);
+        let tomorrow = Local::now() + Duration::days(1);
+        let recap = recap_previous_session(&sessions, current.id(), tomorrow).unwrap();
+        assert_eq!(recap, "昨日は「週末は山に行きます」についてお話ししましたね。");
+
+        assert_eq!(prune_sessions(&dir.0, 30, Local::now() + Duration::days(31)), 1);
+        assert!(list_sessions(&dir.0).is_empty());
+    }
+}
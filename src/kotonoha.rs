@@ -1,29 +1,52 @@
 use crate::models::Task;
 use crate::models::ChatMessage;
+use crate::persona::{self, Persona};
 
 use crate::tts;
 use chrono::Local;
 use tokio::time::{sleep, Duration};
 use std::error::Error;
 
+/// 期限通知に「やる」と答えたときの返事（タスク名が分からないとき）
+pub const ACK_DO_IT_NOW: &str = "了解です。今やりましょう。";
+
 pub fn make_greeting_message(tasks: &[Task]) -> String {
+    greeting_for(&persona::active(), tasks)
+}
+
+/// persona の口調での朝の挨拶
+pub fn greeting_for(persona: &Persona, tasks: &[Task]) -> String {
     let pending_count = tasks.iter().filter(|t| !t.done).count();
 
     if pending_count == 0 {
-        "おはようございます。すべてのタスクが完了しています。今日もいい日になりますように。".to_string()
+        format!("{}{}", persona.morning_greeting, persona.all_done)
     } else {
-        format!("おはようございます。現在 {} 件のタスクがあります。", pending_count)
+        format!("{}{}", persona.morning_greeting, persona.pending_tasks.replace("{count}", &pending_count.to_string()))
     }
 }
-pub async fn greeting(messages:&mut Vec<ChatMessage>) -> Result<(), Box<dyn Error>> {
+/// 毎回同じ文になる挨拶・返事（音声キャッシュの先読み用）
+pub fn fixed_phrases(persona: &Persona) -> Vec<String> {
+    vec![
+        greeting_for(persona, &[]),
+        persona.first_greeting.clone(),
+        persona.break_suggestion.clone(),
+        ACK_DO_IT_NOW.to_string(),
+    ]
+}
+
+/// 起動時の挨拶。はじめての起動なら自己紹介し、recap があれば前回の会話にも触れる
+pub async fn greeting(messages:&mut Vec<ChatMessage>, first_time: bool, recap: Option<&str>) -> Result<(), Box<dyn Error>> {
     let tasks = crate::tasks::load_tasks::<&str>(None);
-    let greeting_text = make_greeting_message(&tasks);
+    let mut greeting_text = make_greeting_message(&tasks);
+    if first_time {
+        greeting_text = format!("{}{}", persona::active().first_greeting, greeting_text);
+    }
+    if let Some(recap) = recap {
+        greeting_text.push_str(recap);
+    }
     crate::tts::speak(&greeting_text).await?;
 
-    messages.push(ChatMessage {
-        role: "assistant".into(),
-        content: greeting_text,
-    });
+    messages.push(ChatMessage::new("assistant", greeting_text));
 
     Ok(())
 }
@@ -33,7 +56,7 @@ pub async fn greeting(messages:&mut Vec<ChatMessage>) -> Result<(), Box<dyn Erro
 pub async fn announce_time_once(){
     let now = Local::now();
     let time_str = now.format("%H時%M分").to_string();
-    let message = format!("ただいま、{}です。水分補給と休憩も忘れずに。", time_str);
+    let message = persona::active().time_announcement.replace("{time}", &time_str);
     let _ = tts::speak(&message).await;
 }
 
@@ -82,5 +105,8 @@ mod tests {
         ];
         let message = make_greeting_message(&tasks);
         assert!(message.contains("現在 1 件のタスク"));
+
+        let casual = greeting_for(&Persona::hiyori(), &tasks);
+        assert_eq!(casual, "おはよう！いまタスクが 1 件あるよ。");
     }
 }
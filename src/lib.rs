@@ -5,3 +5,27 @@ pub mod kotonoha;
 pub mod models;
 pub mod encourage;
 pub mod speech;
+pub mod tools;
+pub mod context;
+pub mod history;
+pub mod tokens;
+pub mod memory;
+pub mod llm;
+pub mod usage;
+pub mod cache;
+pub mod prompts;
+pub mod persona;
+pub mod backend;
+pub mod eval;
+pub mod transport;
+pub mod anthropic;
+pub mod gemini;
+pub mod providers;
+pub mod redact;
+pub mod mood;
+pub mod voicevox;
+pub mod fallback;
+pub mod audio_cache;
+pub mod playback;
+pub mod reading;
+pub mod quiet;
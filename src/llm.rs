@@ -0,0 +1,465 @@
+use crate::models::{ChatRequest, ChatResponse};
+use crate::usage::{self, CallSite, MonthSummary, UsageLedger, UsageRecord};
+use crate::cache::ResponseCache;
+use crate::backend::{LlmBackend, OpenAiBackend};
+use crate::providers::ProviderConfig;
+use crate::transport::{HttpClient, HttpError};
+use crate::redact::{Redaction, Redactor};
+
+use rand::Rng;
+use reqwest::StatusCode;
+use std::borrow::Cow;
+use std::env;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use chrono::{Datelike, Local};
+
+pub const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// API 呼び出しの失敗
+#[derive(Debug)]
+pub enum LlmError {
+    Timeout,                                    // 応答が時間内に返ってこない
+    Network(String),                            // 接続できないなど
+    Http { status: StatusCode, body: String },  // 非成功ステータス
+    Parse(String),                              // レスポンスが読めない
+    CircuitOpen,                                // 障害中なので呼び出しを止めている
+    Fixture(String),                            // 記録の再生で食い違った
+}
+
+impl LlmError {
+    /// 再試行すれば直る見込みがあるか
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            LlmError::Timeout | LlmError::Network(_) => true,
+            LlmError::Http { status, .. } => {
+                *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+            }
+            LlmError::Parse(_) | LlmError::CircuitOpen | LlmError::Fixture(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for LlmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LlmError::Timeout => write!(f, "API timeout"),
+            LlmError::Network(e) => write!(f, "API network error: {}", e),
+            LlmError::Http { status, body } => write!(f, "API Error: ({}):{}", status, body),
+            LlmError::Parse(e) => write!(f, "API parse error: {}", e),
+            LlmError::CircuitOpen => write!(f, "API is temporarily disabled after repeated failures"),
+            LlmError::Fixture(e) => write!(f, "API fixture error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LlmError {}
+
+impl From<HttpError> for LlmError {
+    fn from(e: HttpError) -> Self {
+        match e {
+            HttpError::Timeout => LlmError::Timeout,
+            HttpError::Network(e) => LlmError::Network(e),
+            HttpError::Fixture(e) => LlmError::Fixture(e),
+        }
+    }
+}
+
+/// タイムアウトと再試行の設定
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub timeout: Duration,      // 1リクエストあたり
+    pub max_retries: u32,       // 初回を除く再試行回数
+    pub base_delay: Duration,   // 1回目の待ち時間の上限
+    pub max_delay: Duration,    // 待ち時間の上限（Retry-After もここで打ち切る）
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(20),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// LLM_TIMEOUT_SECS / LLM_MAX_RETRIES で上書きできる
+    pub fn from_env() -> Self {
+        let mut policy = Self::default();
+        if let Some(secs) = env::var("LLM_TIMEOUT_SECS").ok().and_then(|v| v.parse().ok()) {
+            policy.timeout = Duration::from_secs(secs);
+        }
+        if let Some(n) = env::var("LLM_MAX_RETRIES").ok().and_then(|v| v.parse().ok()) {
+            policy.max_retries = n;
+        }
+        policy
+    }
+
+    /// attempt 回目（0始まり）の再試行までの待ち時間
+    /// 指数バックオフに full jitter をかける。Retry-After があればそれを優先する
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(wait) = retry_after {
+            return wait.min(self.max_delay);
+        }
+        let cap = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let millis = cap.as_millis() as u64;
+        Duration::from_millis(rand::rng().random_range(0..=millis))
+    }
+}
+
+/// Retry-After ヘッダ（秒数 または HTTP-date）を読む
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = at.signed_duration_since(chrono::Utc::now()).to_std().unwrap_or_default();
+    Some(wait)
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_started: Option<Instant>,   // 開いたあとの試しの1回を始めた時刻（結果が出るまで他は通さない）
+}
+
+/// 連続して失敗したら一定時間 API 呼び出しを止める
+/// 止めるのは開いてから cooldown 未満の間（開いた時刻 <= t < 開いた時刻 + cooldown）。ちょうど cooldown 経てば1回だけ試せる
+/// 試しの結果が出るまで、ほかの呼び出しは止めたまま（結果を記録せずに終わった試しは cooldown 経ったら諦めて次を通す）
+/// 止めている間はオフラインの分類器で代用する
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(3, Duration::from_secs(60))
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// 呼び出してよいか（開いてから cooldown 以上経ったら1回だけ試す）。試しを通したら、その呼び出しが結果を記録すること
+    pub fn allow(&self) -> bool {
+        let mut st = self.state.lock().unwrap();
+        let Some(opened_at) = st.opened_at else {
+            return true;
+        };
+        if !self.cooled_down(opened_at.elapsed()) || st.probe_started.is_some_and(|at| !self.cooled_down(at.elapsed())) {
+            return false;
+        }
+        st.probe_started = Some(Instant::now());
+        true
+    }
+
+    // 開いてから elapsed 経ったとき、もう試してよいか
+    fn cooled_down(&self, elapsed: Duration) -> bool {
+        elapsed >= self.cooldown
+    }
+
+    /// 止めているか（試しの1回は使わない）
+    pub fn is_open(&self) -> bool {
+        let st = self.state.lock().unwrap();
+        st.opened_at.is_some_and(|at| {
+            !self.cooled_down(at.elapsed()) || st.probe_started.is_some_and(|probe| !self.cooled_down(probe.elapsed()))
+        })
+    }
+
+    pub fn record_success(&self) {
+        let mut st = self.state.lock().unwrap();
+        st.consecutive_failures = 0;
+        st.opened_at = None;
+        st.probe_started = None;
+    }
+
+    /// 試しの1回が、成功とも失敗とも数えない終わり方をした（認証エラーなど）。次の呼び出しでまた試す
+    pub fn release_probe(&self) {
+        self.state.lock().unwrap().probe_started = None;
+    }
+
+    pub fn record_failure(&self) {
+        let mut st = self.state.lock().unwrap();
+        st.probe_started = None;
+        st.consecutive_failures += 1;
+        if st.consecutive_failures >= self.failure_threshold {
+            // 試しの1回が失敗したときもここで開き直す
+            st.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+/// Chat Completions API のクライアント（タイムアウト・再試行・サーキットブレーカー付き）
+pub struct LlmClient {
+    backend: Box<dyn LlmBackend>,
+    retry: RetryPolicy,
+    breaker: CircuitBreaker,
+    ledger: Option<UsageLedger>,
+    budget_usd: Option<f64>,
+    month_spent: Mutex<(i32, u32, f64)>,   // (年, 月, 使った金額)
+    cache: Option<ResponseCache>,
+    model: Option<String>,                  // 呼び出し側の既定より優先するモデル
+    cheap_model: String,
+    redactor: Option<Redactor>,
+}
+
+impl LlmClient {
+    pub fn new(api_key: &str, base_url: &str, retry: RetryPolicy, breaker: CircuitBreaker) -> Self {
+        Self::with_backend(Box::new(OpenAiBackend::new(api_key, base_url)), retry, breaker)
+    }
+
+    /// 送り先を差し替えて作る（記録済みの応答での評価など）
+    pub fn with_backend(backend: Box<dyn LlmBackend>, retry: RetryPolicy, breaker: CircuitBreaker) -> Self {
+        Self {
+            backend,
+            retry,
+            breaker,
+            ledger: None,
+            budget_usd: None,
+            month_spent: Mutex::new((0, 0, 0.0)),
+            cache: None,
+            model: None,
+            cheap_model: usage::CHEAP_MODEL.to_string(),
+            redactor: None,
+        }
+    }
+
+    /// 提供元の設定と、環境変数の再試行・予算・キャッシュ設定で作る
+    pub fn from_env(config: &ProviderConfig) -> Self {
+        Self::with_backend(config.backend(HttpClient::from_env()), RetryPolicy::from_env(), CircuitBreaker::default())
+            .with_models(config.model.clone(), config.provider.cheap_model())
+            .with_usage(UsageLedger::from_env(), usage::monthly_budget_from_env())
+            .with_cache(ResponseCache::from_env())
+            .with_redactor(Redactor::from_env())
+    }
+
+    /// 使うモデルを決める（model が None なら呼び出し側の既定のまま）
+    pub fn with_models(mut self, model: Option<String>, cheap_model: &str) -> Self {
+        self.model = model;
+        self.cheap_model = cheap_model.to_string();
+        self
+    }
+
+    /// 送る前に個人情報を伏せ、返ってきた本文とツール引数で元に戻す
+    pub fn with_redactor(mut self, redactor: Option<Redactor>) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// 分類・抽出の結果のキャッシュを有効にする
+    pub fn with_cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn cache(&self) -> Option<&ResponseCache> {
+        self.cache.as_ref()
+    }
+
+    /// 利用台帳への記録と月の予算を有効にする
+    pub fn with_usage(mut self, ledger: UsageLedger, budget_usd: Option<f64>) -> Self {
+        let now = Local::now();
+        let spent = ledger.month_summary(now).total.cost_usd;
+        self.month_spent = Mutex::new((now.year(), now.month(), spent));
+        self.ledger = Some(ledger);
+        self.budget_usd = budget_usd;
+        self
+    }
+
+    pub fn budget_usd(&self) -> Option<f64> {
+        self.budget_usd
+    }
+
+    /// 今月の予算を使い切っているか
+    pub fn over_budget(&self) -> bool {
+        let Some(budget) = self.budget_usd else {
+            return false;
+        };
+        let now = Local::now();
+        let spent = self.month_spent.lock().unwrap();
+        (spent.0, spent.1) == (now.year(), now.month()) && spent.2 >= budget
+    }
+
+    /// 呼び出しに使うモデル（予算超過中は安いモデルに落とす）
+    pub fn model_for(&self, default_model: &str) -> String {
+        if self.over_budget() {
+            self.cheap_model.clone()
+        } else {
+            self.model.clone().unwrap_or_else(|| default_model.to_string())
+        }
+    }
+
+    /// 今月の利用状況（台帳がなければ None）
+    pub fn usage_summary(&self) -> Option<MonthSummary> {
+        self.ledger.as_ref().map(|l| l.month_summary(Local::now()))
+    }
+
+    fn record_usage(&self, site: CallSite, prompt: Option<&str>, model: &str, response: &ChatResponse) {
+        let (Some(ledger), Some(u)) = (&self.ledger, &response.usage) else {
+            return;
+        };
+
+        let record = UsageRecord {
+            timestamp: Local::now(),
+            call_site: site,
+            model: model.to_string(),
+            prompt_tokens: u.prompt_tokens,
+            completion_tokens: u.completion_tokens,
+            cost_usd: usage::estimate_cost(model, u),
+            prompt: prompt.map(str::to_string),
+        };
+        if let Err(e) = ledger.append(&record) {
+            eprintln!("Failed to write usage ledger: {}", e);
+        }
+
+        let now = record.timestamp;
+        let mut spent = self.month_spent.lock().unwrap();
+        if (spent.0, spent.1) != (now.year(), now.month()) {
+            *spent = (now.year(), now.month(), 0.0);
+        }
+        spent.2 += record.cost_usd;
+    }
+
+    /// API を使える状態か（false ならオフラインで代用する）。試しの1回は実際の呼び出しに残しておく
+    pub fn is_available(&self) -> bool {
+        !self.breaker.is_open()
+    }
+
+    /// 再試行込みで1回分の呼び出しを行う。使用量は site ごとに台帳へ記録する
+    pub async fn send_chat(&self, site: CallSite, request: &ChatRequest) -> Result<ChatResponse, LlmError> {
+        self.send_chat_with_prompt(site, None, request).await
+    }
+
+    /// send_chat と同じだが、使ったテンプレート（例: classify_input@1/ja）も台帳に残す
+    pub async fn send_chat_with_prompt(&self, site: CallSite, prompt: Option<&str>, request: &ChatRequest) -> Result<ChatResponse, LlmError> {
+        if !self.breaker.allow() {
+            return Err(LlmError::CircuitOpen);
+        }
+
+        let (request, redaction) = match &self.redactor {
+            Some(redactor) => {
+                let (redacted, redaction) = redactor.redact_request(request);
+                if !redaction.is_empty() {
+                    eprintln!("Redacted before sending: {}", redaction.describe());
+                }
+                (Cow::Owned(redacted), redaction)
+            }
+            None => (Cow::Borrowed(request), Redaction::default()),
+        };
+
+        let mut attempt = 0;
+        loop {
+            match self.backend.send(&request, self.retry.timeout).await {
+                Ok(mut parsed) => {
+                    self.breaker.record_success();
+                    self.record_usage(site, prompt, &request.model, &parsed);
+                    redaction.restore_response(&mut parsed);
+                    return Ok(parsed);
+                }
+                Err((e, retry_after)) if e.is_retryable() && attempt < self.retry.max_retries => {
+                    let wait = self.retry.delay(attempt, retry_after);
+                    eprintln!("LLM call failed ({}), retrying in {:?}", e, wait);
+                    tokio::time::sleep(wait).await;
+                    attempt += 1;
+                }
+                Err((e, _)) => {
+                    // 認証エラーなどはこちらの設定の問題なので、ブレーカーには数えない
+                    if e.is_retryable() {
+                        self.breaker.record_failure();
+                    } else {
+                        self.breaker.release_probe();
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_is_bounded_and_honors_retry_after() {
+        let policy = RetryPolicy::default();
+        for attempt in 0..10 {
+            assert!(policy.delay(attempt, None) <= policy.max_delay);
+        }
+        assert_eq!(policy.delay(0, Some(Duration::from_secs(3))), Duration::from_secs(3));
+        assert_eq!(policy.delay(0, Some(Duration::from_secs(600))), policy.max_delay);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("5"), Some(Duration::from_secs(5)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn test_breaker_opens_and_recovers() {
+        let breaker = CircuitBreaker::new(2, Duration::from_millis(20));
+        breaker.record_failure();
+        assert!(breaker.allow());
+        breaker.record_failure();
+        assert!(breaker.is_open());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.allow());
+        breaker.record_success();
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn test_breaker_cooldown_is_half_open() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+        assert!(!breaker.cooled_down(Duration::ZERO));
+        assert!(!breaker.cooled_down(Duration::from_secs(60) - Duration::from_nanos(1)));
+        assert!(breaker.cooled_down(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_breaker_lets_only_one_probe_through() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+        breaker.record_failure();
+        std::thread::sleep(Duration::from_millis(30));
+
+        // 試しの1回だけ通し、結果が出るまでほかは止める
+        assert!(!breaker.is_open());
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+        assert!(breaker.is_open());
+
+        // 試しが失敗したら開き直す
+        breaker.record_failure();
+        assert!(!breaker.allow());
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.allow());
+
+        // 結果を記録しないまま cooldown 経ったら、次を試しに通す
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.allow());
+        breaker.record_success();
+        assert!(breaker.allow());
+        assert!(breaker.allow());
+    }
+}
@@ -1,18 +1,17 @@
 ﻿use kotonoha_core::*;
-use crate::{tasks, tts, chat, kotonoha};
+use crate::{tasks, tts, chat, kotonoha, tools, context, history, tokens, memory, llm, persona, providers, mood, encourage, quiet};
 use crate::models::ChatMessage;
 
-use kotonoha_core::speech::SpeechQueue;
+use kotonoha_core::speech::{ShutdownMode, SpeechQueue};
 
 use dotenvy::dotenv;
 use std::env;
 use std::io;
-use reqwest::Client;
 
 use tokio::sync::mpsc;
 use tokio::time::{self, Duration, Instant};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -33,21 +32,46 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     );
   
     let mock_openai = env::var("MOCK_OPENAI").is_ok();
-    let api_key = env::var("OPENAI_API_KEY").unwrap_or_default();
-    if api_key.is_empty() && !mock_openai {
-        return Err("OPENAI_API_KEY is not set".into());
+    let provider = providers::ProviderConfig::from_env()?;
+    if provider.api_key.is_empty() && !mock_openai {
+        return Err(format!("{} is not set", provider.provider.key_var()).into());
     }
     
-    let client = Client::new();
-
-    let mut messages = vec![
-        ChatMessage {
-            role: "system".into(),
-            content: chat::SYSTEM_PROMPT.into(),
+    // タイムアウト・再試行・障害時のオフライン切り替えは LlmClient に任せる
+    let llm = llm::LlmClient::from_env(&provider);
+
+    // キャラクター（口調・セリフ・声）
+    let personas = persona::PersonaBook::from_env();
+    persona::set_active(personas.initial().clone());
+
+    let mut messages = vec![ChatMessage::new("system", persona::active().system_prompt())];
+
+    // 会話ログ：古いものを整理してから、前回の続きか新しいセッションを始める
+    let history_dir = history::history_dir();
+    history::prune_sessions(&history_dir, history::retention_days(), chrono::Local::now());
+    let past_sessions = history::list_sessions(&history_dir);
+    let session = match past_sessions.first() {
+        Some(last) if env::var("RESUME_LAST_SESSION").is_ok() => {
+            messages.extend(history::restore_messages(last));
+            history::SessionLog::resume(last)
         }
-    ];
+        _ => history::SessionLog::start(&history_dir)?,
+    };
+
+    // 決まり文句の音声を裏で作っておく
+    let mut phrases = kotonoha::fixed_phrases(&persona::active());
+    phrases.extend(encourage::fixed_phrases(&persona::active()));
+    tokio::spawn(async move {
+        // 読みを先に登録しておくと、先読みした音声にも効く
+        tts::sync_readings().await;
+        tts::prewarm(&phrases).await;
+    });
 
-    kotonoha::greeting(&mut messages).await?;
+    let recap = history::recap_previous_session(&past_sessions, session.id(), chrono::Local::now());
+    kotonoha::greeting(&mut messages, past_sessions.is_empty(), recap.as_deref()).await?;
+    if let Some(greeting) = messages.last() {
+        let _ = session.append(greeting);
+    }
 
 
     //stdin をイベント化
@@ -68,6 +92,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
+    // ユーザーについての長期記憶
+    let mut memory = memory::MemoryStore::open_default();
+
+    // 気分の見守り（辞書で判定し、MOOD_LLM があれば分からなかった発言をAPIに聞く）
+    let mut mood_tracker = mood::MoodTracker::default();
+    let mut mood_log = mood::MoodLog::open_default();
+    let mood_llm = env::var("MOOD_LLM").is_ok();
+
     // 時報
     let mut time_tick = time::interval(Duration::from_secs(300));
 
@@ -81,9 +113,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // 期限通知の「今やる？」待ち（タスクIDと期限日を保持）
     let mut pending_due: Option<(u32, chrono::NaiveDate)> = None;
 
+    // 雑談中のツール呼び出しで確認待ちになっている操作（1件ずつ順に聞く）
+    let mut pending_actions: VecDeque<tools::PendingAction> = VecDeque::new();
+
+    // 「○○のことは忘れて」で、忘れてよいか確認待ちの事実
+    let mut pending_forget: Option<memory::MemoryFact> = None;
+
 
     println!("Kotonoha> こんにちは。ご用件をどうぞ。終了するには 'exit'またはCtrl+C と入力してください。");
 
+    // Ctrl+C なら喋りかけでも止める。exit なら言いかけたことは言い終える
+    let mut shutdown = ShutdownMode::Cancel;
+
     loop{
         tokio::select! {
             _ = tokio::signal::ctrl_c() => {
@@ -93,22 +134,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 
             _ = time_tick.tick() => {
-                kotonoha::announce_time_once().await;
+                // 静かにする時間は時報も言わない
+                if !speech.is_quiet().await {
+                    kotonoha::announce_time_once().await;
+                }
             }
 
             _ = due_tick.tick() => {
 
-                if pending_due.is_some() {
-                    // 「今やる？」待ち中は新規通知しない
+                if pending_due.is_some() || speech.holds_alerts().await {
+                    // 「今やる？」待ち中と、通知を溜めている（まだ聞かれていない問いに答えは来ない）あいだは新規通知しない
                     continue;
                 }
                 // 例：3日以内の期限を通知
-                let due_tasks = tasks::find_due_within_days(3);
+                // 余裕がなさそうなときは、明日までのものだけを間隔をあけて控えめに伝える
+                let gentle = speech.is_gentle().await;
+                let due_tasks = tasks::find_due_within_days(if gentle { 1 } else { 3 });
+                let cooldown = if gentle { notify_cooldown * 2 } else { notify_cooldown };
 
                 for t in due_tasks {
                     let now = Instant::now();
                     let should_notify = match last_notified.get(&t.id) {
-                        Some(prev) => now.duration_since(*prev) >= notify_cooldown,
+                        Some(prev) => now.duration_since(*prev) >= cooldown,
                         None => true,
                     };
 
@@ -116,7 +163,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         last_notified.insert(t.id, now);
                         // due_date は Option<NaiveDate> なので unwrap は安全（find_due_within_days が Some のみ返す想定）
                         let due = t.due_date.unwrap();
-                        let msg = format!("期限が近いタスクがあります：{}（期限: {}）。いまやりますか？(yes/no)", t.title, due);
+                        let msg = if gentle {
+                            format!("無理のない範囲で大丈夫ですが、{}の期限が{}です。いまやりますか？(yes/no)", t.title, due)
+                        } else {
+                            format!("期限が近いタスクがあります：{}（期限: {}）。いまやりますか？(yes/no)", t.title, due)
+                        };
                         speech.say_alert(msg).await;
                         pending_due = Some((t.id, due));
 
@@ -134,29 +185,180 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     }
                     if user_input == "exit" {
                         println!("Kotonoha> 終了します。またお話ししましょうね！");
+                        shutdown = ShutdownMode::Finish;
                         break;
                     }
-                     // ★期限の「いまやる？」待ちがあるなら、それを最優先で処理
+
+                    // 決まった言い回しは API を呼ばずに処理する
+                    if let Some(command) = chat::detect_special_command(user_input) {
+                        match command {
+                            "list" => tasks::list_tasks().await,
+                            "usage" => {
+                                let mut response = match llm.usage_summary() {
+                                    Some(summary) => summary.describe(llm.budget_usd()),
+                                    None => "利用状況は記録していません。".to_string(),
+                                };
+                                if let Some(cache) = tts::audio_cache() {
+                                    response = format!("{}\n{}", response, cache.stats().describe());
+                                }
+                                println!("Kotonoha > {}", response);
+                                speech.say_user(response.lines().next().unwrap_or_default().to_string()).await;
+                            }
+                            "history" => {
+                                let sessions = history::list_sessions(&history_dir);
+                                println!("Kotonoha > 保存されている会話は {} 件です。", sessions.len());
+                                for s in sessions.iter().take(10) {
+                                    println!("  {}  {} 〜 {}（{}件）", s.id, s.started.format("%m/%d %H:%M"), s.last.format("%H:%M"), s.message_count);
+                                }
+                            }
+                            "mood" => {
+                                let response = mood_log.describe_trend(chrono::Local::now().date_naive());
+                                println!("Kotonoha > {}", response);
+                                speech.say_user(response.lines().last().unwrap_or_default().to_string()).await;
+                            }
+                            _ => {}
+                        }
+                        continue;
+                    }
+
+                    // キャラの切り替え
+                    if let Some(command) = persona::detect_command(user_input) {
+                        let current = persona::active();
+                        let next = match command {
+                            persona::PersonaCommand::Next => Some(personas.next_after(&current.name)),
+                            persona::PersonaCommand::Switch(name) => personas.find(&name),
+                        };
+                        match next {
+                            Some(next) if next.name != current.name => {
+                                persona::set_active(next.clone());
+                                context::refresh_system_message(&mut messages, &next.system_prompt());
+                                println!("Kotonoha > {}", next.first_greeting);
+                                speech.say_user(next.first_greeting.clone()).await;
+                            }
+                            Some(_) => {
+                                speech.say_user(format!("いまも{}がお相手していますよ。", current.name)).await;
+                            }
+                            None => {
+                                let names = personas.personas().iter().map(|p| p.name.as_str()).collect::<Vec<_>>().join("、");
+                                speech.say_alert(format!("そのキャラは見つかりませんでした。選べるのは {} です。", names)).await;
+                            }
+                        }
+                        continue;
+                    }
+
+                    // 「30分静かにして」：そのあいだ独り言・時報は言わず、通知は設定どおり溜めるか文字だけにする
+                    if let Some(duration) = quiet::detect_quiet_request(user_input) {
+                        speech.quiet_for(duration).await;
+                        let until = chrono::TimeDelta::from_std(duration).ok().and_then(|d| chrono::Local::now().checked_add_signed(d));
+                        let response = if duration.is_zero() {
+                            // 「0分静かにして」は解除
+                            "わかりました。静かにするのをやめますね。".to_string()
+                        } else if let Some(until) = until {
+                            format!("わかりました。{}まで静かにしますね。", until.format("%H時%M分"))
+                        } else {
+                            "わかりました。しばらく静かにしますね。".to_string()
+                        };
+                        println!("Kotonoha > {}", response);
+                        speech.say_user(response).await;
+                        continue;
+                    }
+
+                    // 過去の会話の検索
+                    if let Some(keyword) = user_input.strip_prefix("会話検索") {
+                        let keyword = keyword.trim();
+                        let hits = history::search_sessions(&history_dir, keyword);
+                        println!("Kotonoha > 「{}」を含む発言は {} 件です。", keyword, hits.len());
+                        for (id, entry) in hits.iter().take(10) {
+                            println!("  [{}] {} {}: {}", id, entry.timestamp.format("%m/%d %H:%M"), entry.role, entry.content);
+                        }
+                        continue;
+                    }
                     let input = user_input.trim().to_lowercase();
 
-                    if let Some((task_id, _due)) = pending_due.clone() {
-                        let yes = matches!(input.as_str(), "yes" | "y" | "はい" | "やる" | "やります" | "今やる");
-                        let no  = matches!(input.as_str(), "no"  | "n" | "いいえ" | "やらない" | "やりません" | "あとで");
+                    if let Some(fact) = pending_forget.take() {
+                        let response = match parse_yes_no(&input) {
+                            Some(true) => match memory.forget(fact.id) {
+                                Some(fact) => format!("「{}」は忘れました。", fact.text),
+                                None => "忘れることが見つかりませんでした。".to_string(),
+                            },
+                            Some(false) => "わかりました。覚えておきますね。".to_string(),
+                            None => {
+                                pending_forget = Some(fact);
+                                speech.say_alert("「yes」か「no」でお答えください。").await;
+                                continue;
+                            }
+                        };
+                        println!("Kotonoha > {}", response);
+                        speech.say_user(response).await;
+                        continue;
+                    }
+
+                    // 記憶の確認・削除（覚えていることに当たらない「○○のことは忘れて」は普段の会話として扱う）
+                    if let Some(command) = memory::detect_command(user_input) {
+                        let response = match command {
+                            memory::MemoryCommand::Recall => Some(memory.describe()),
+                            memory::MemoryCommand::ForgetLast => Some(match memory.forget_last() {
+                                Some(fact) => format!("「{}」は忘れました。", fact.text),
+                                None => "忘れることが見つかりませんでした。".to_string(),
+                            }),
+                            // 消す前に、どれを忘れるのか確かめる
+                            memory::MemoryCommand::Forget(target) => memory.find_matching(&target).cloned().map(|fact| {
+                                let question = format!("「{}」を忘れてよいですか？(yes/no)", fact.text);
+                                pending_forget = Some(fact);
+                                question
+                            }),
+                        };
+                        if let Some(response) = response {
+                            println!("Kotonoha > {}", response);
+                            speech.say_user(response).await;
+                            continue;
+                        }
+                    }
+
+                    if let Some(action) = pending_actions.pop_front() {
+                        match parse_yes_no(&input) {
+                            Some(true) => {
+                                let result = tools::apply(&action);
+                                println!("Kotonoha > {}", result);
+                                speech.say_user(result.clone()).await;
+                                session.push(&mut messages, ChatMessage::new("assistant", result));
+                            }
+                            Some(false) => {
+                                speech.say_user("わかりました。やめておきますね。").await;
+                            }
+                            None => {
+                                pending_actions.push_front(action);
+                                speech.say_alert("「yes」か「no」でお答えください。").await;
+                                continue;
+                            }
+                        }
+                        // まだ確認待ちがあれば、次を聞く
+                        if let Some(next) = pending_actions.front() {
+                            let prompt = next.prompt();
+                            println!("Kotonoha > {}", prompt);
+                            speech.say_user(prompt).await;
+                        }
+                        continue;
+                    }
+
+                    // ★期限の「いまやる？」待ちがあるなら、その返事として処理
+                    if let Some((task_id, _due)) = pending_due {
+                        let answer = parse_yes_no(&input);
 
-                        if yes {
+                        if answer == Some(true) {
                            if let Some(title) = tasks::get_task_title(task_id) {
                                 speech
                                   .say_user(format!("了解です。『{}』を今やりましょう。", title))
                                     .await;
                             } else {
-                                speech.say_user("了解です。今やりましょう。".to_string()).await;
+                                speech.say_user(kotonoha::ACK_DO_IT_NOW.to_string()).await;
                             }
 
                             pending_due = None;
                             continue;
                         }
 
-                        if no {
+                        if answer == Some(false) {
                             speech.say_user("わかりました。あとでリマインドしますね。".to_string()).await;
                             pending_due = None;
                             continue;
@@ -165,14 +367,45 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         speech.say_alert("「yes」か「no」でお答えください。".to_string()).await;
                         continue;
                     }
+                // 気分を見て、疲れていそうなら休憩を勧め、余裕がなさそうなら通知を控えめにする
+                let mut score = mood::score_text(user_input);
+                if score.is_neutral() && mood_llm {
+                    score = chat::assess_mood(&llm, user_input).await.unwrap_or_else(|e| {
+                        eprintln!("Failed to assess mood: {}", e);
+                        score
+                    });
+                }
+                mood_log.record(chrono::Local::now().date_naive(), score);
+                if let Some(state) = mood_tracker.observe(score) {
+                    speech.set_gentle(state.wants_gentle()).await;
+                    if let Some(message) = mood::response_for(state, &persona::active()) {
+                        println!("Kotonoha > {}", message);
+                        speech.say_user(message).await;
+                    }
+                }
+
                 //GPTで分類
-                let mode = chat::classify_input(&client, &api_key, user_input).await?;
+                // 分類に失敗したらオフラインの分類器で続ける
+                let mode = chat::classify_input(&llm, user_input).await.unwrap_or_else(|e| {
+                    eprintln!("Failed to classify input: {}", e);
+                    chat::offline_classify_input(user_input)
+                });
                 match mode.as_str() {
                     "タスク" => {
-                        let intent = chat::classify_task_action(&client, &api_key, user_input).await?;
+                        let intent = chat::classify_task_action(&llm, user_input).await.unwrap_or_else(|e| {
+                            eprintln!("Failed to classify task action: {}", e);
+                            chat::offline_classify_task_action(user_input)
+                        });
                         match intent.as_str() {
                             "追加" => {
-                                let task = chat::extract_task(&client, &api_key, user_input).await?;
+                                let task = match chat::extract_task(&llm, user_input).await {
+                                    Ok(task) => task,
+                                    Err(e) => {
+                                        eprintln!("Failed to extract task: {}", e);
+                                        speech.say_user(chat::APOLOGY).await;
+                                        continue;
+                                    }
+                                };
                                 if task.is_empty() || task == "なし" {
                                     speech.say_alert("追加するタスクが見つかりませんでした。もう一度お願いします。").await;
                                 } else {
@@ -189,18 +422,48 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             "一覧" => {
                                 tasks::list_tasks().await;
                             }
-                            "なし" | _ => {
+                            _ => {
                                 speech.say_alert("特別な操作はありません。").await;
                             }
                         }
                     }
 
                     "雑談" => {
-                        messages.push(ChatMessage { role: "user".into(), content: user_input.to_string() });
-                        let response = chat::respond_to_chat(&client, &api_key, &messages).await?;
-                        println!("Kotonoha > {}", response);
-                        speech.say_user(&response).await;
-                        messages.push(ChatMessage { role: "assistant".into(), content: response });
+                        session.push(&mut messages, ChatMessage::new("user", user_input));
+                        context::refresh_system_message(&mut messages, &persona::active().system_prompt());
+                        chat::compact_history(&llm, &mut messages, tokens::history_budget(&llm.model_for(chat::CHAT_MODEL))).await;
+                        let reply = match chat::respond_to_chat(&llm, &messages).await {
+                            Ok(reply) => reply,
+                            Err(e) => {
+                                eprintln!("Failed to respond: {}", e);
+                                println!("Kotonoha > {}", chat::APOLOGY);
+                                speech.say_user(chat::APOLOGY).await;
+                                continue;
+                            }
+                        };
+                        println!("Kotonoha > {}", reply.text);
+                        speech.say_user(&reply.text).await;
+                        session.push(&mut messages, ChatMessage::new("assistant", reply.text));
+
+                        // ユーザーについての事実を覚えておく（失敗しても会話は続ける）
+                        match chat::extract_user_facts(&llm, user_input).await {
+                            Ok(facts) => {
+                                for (category, text) in facts {
+                                    if let Some(fact) = memory.remember(category, &text) {
+                                        println!("（覚えました: {}）", fact.text);
+                                    }
+                                }
+                            }
+                            Err(e) => eprintln!("Failed to extract user facts: {}", e),
+                        }
+
+                        // 書き換えを伴う操作はユーザーの返事を待ってから適用する（複数あれば1件ずつ聞く）
+                        pending_actions.extend(reply.pending);
+                        if let Some(action) = pending_actions.front() {
+                            let prompt = action.prompt();
+                            println!("Kotonoha > {}", prompt);
+                            speech.say_user(prompt).await;
+                        }
                     }
 
                     _ => {
@@ -211,7 +474,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-
+    // 残りの発話を片付けてから終わる（エンジンが固まっていても待ちすぎない）
+    if time::timeout(Duration::from_secs(10), speech.shutdown(shutdown)).await.is_err() {
+        eprintln!("Failed to finish pending speech before exit");
+    }
 
     Ok(())
 }
+
+/// yes/no の返事を判定する（どちらでもなければ None）
+fn parse_yes_no(input: &str) -> Option<bool> {
+    match input {
+        "yes" | "y" | "はい" | "やる" | "やります" | "今やる" | "お願い" => Some(true),
+        "no" | "n" | "いいえ" | "やらない" | "やりません" | "あとで" | "やめて" => Some(false),
+        _ => None,
+    }
+}
@@ -0,0 +1,301 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use strsim::jaro_winkler;
+
+pub const DEFAULT_MEMORY_FILE: &str = "memory.json";
+
+// これ以上似ていたら同じ事実とみなす
+const DUPLICATE_THRESHOLD: f64 = 0.9;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum FactCategory {
+    Preference,    // 好み・趣味
+    Person,        // 周りの人
+    Event,         // 予定・習慣
+    Other,         // その他
+}
+
+impl FactCategory {
+    pub fn label(&self) -> &'static str {
+        match self {
+            FactCategory::Preference => "好み",
+            FactCategory::Person => "人物",
+            FactCategory::Event => "予定・習慣",
+            FactCategory::Other => "その他",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Self {
+        match label.trim() {
+            "好み" => FactCategory::Preference,
+            "人物" => FactCategory::Person,
+            "予定・習慣" | "予定" | "習慣" => FactCategory::Event,
+            _ => FactCategory::Other,
+        }
+    }
+}
+
+/// ユーザーについて覚えている事実1件
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MemoryFact {
+    pub id: u32,
+    pub category: FactCategory,
+    pub text: String,
+    pub created_at: DateTime<Local>,
+}
+
+/// 長期記憶（MEMORY_FILE、既定: memory.json）
+pub struct MemoryStore {
+    path: PathBuf,
+    facts: Vec<MemoryFact>,
+}
+
+/// 記憶についてのユーザーからの指示
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemoryCommand {
+    Recall,             // 私について覚えていることを教えて
+    ForgetLast,         // それは忘れて
+    Forget(String),     // ○○のことは忘れて
+}
+
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_whitespace() && !"、。,.！!？?「」".contains(*c))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+impl MemoryStore {
+    pub fn open(path: &Path) -> Self {
+        let facts = File::open(path)
+            .ok()
+            .and_then(|f| serde_json::from_reader(BufReader::new(f)).ok())
+            .unwrap_or_default();
+        Self { path: path.to_path_buf(), facts }
+    }
+
+    pub fn open_default() -> Self {
+        let path = env::var("MEMORY_FILE").unwrap_or_else(|_| DEFAULT_MEMORY_FILE.to_string());
+        Self::open(Path::new(&path))
+    }
+
+    pub fn facts(&self) -> &[MemoryFact] {
+        &self.facts
+    }
+
+    fn save(&self) {
+        match serde_json::to_string_pretty(&self.facts) {
+            Ok(json) => {
+                if let Err(e) = fs::write(&self.path, json) {
+                    eprintln!("Failed to write memory file: {} ({})", self.path.display(), e);
+                }
+            }
+            Err(e) => eprintln!("Failed to serialize memory: {}", e),
+        }
+    }
+
+    fn find_duplicate(&self, text: &str) -> Option<usize> {
+        let new = normalize(text);
+        self.facts.iter().position(|f| {
+            let old = normalize(&f.text);
+            old.contains(&new) || new.contains(&old) || jaro_winkler(&old, &new) >= DUPLICATE_THRESHOLD
+        })
+    }
+
+    /// 事実を覚える。既に同じことを覚えていれば、より詳しい方に置き換えて None を返す
+    pub fn remember(&mut self, category: FactCategory, text: &str) -> Option<&MemoryFact> {
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+
+        if let Some(i) = self.find_duplicate(text) {
+            if normalize(text).chars().count() > normalize(&self.facts[i].text).chars().count() {
+                self.facts[i].text = text.to_string();
+                self.save();
+            }
+            return None;
+        }
+
+        let id = self.facts.iter().map(|f| f.id).max().unwrap_or(0) + 1;
+        self.facts.push(MemoryFact {
+            id,
+            category,
+            text: text.to_string(),
+            created_at: Local::now(),
+        });
+        self.save();
+        self.facts.last()
+    }
+
+    /// いちばん最近覚えたことを忘れる
+    pub fn forget_last(&mut self) -> Option<MemoryFact> {
+        let i = self
+            .facts
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, f)| (f.created_at, f.id))
+            .map(|(i, _)| i)?;
+        let removed = self.facts.remove(i);
+        self.save();
+        Some(removed)
+    }
+
+    /// query にいちばん近い事実（似ていなければ None）
+    pub fn find_matching(&self, query: &str) -> Option<&MemoryFact> {
+        let query = normalize(query);
+        let (i, score) = self
+            .facts
+            .iter()
+            .enumerate()
+            .map(|(i, f)| {
+                let text = normalize(&f.text);
+                let score = if text.contains(&query) { 1.0 } else { jaro_winkler(&text, &query) };
+                (i, score)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+
+        if score < 0.7 {
+            return None;
+        }
+        Some(&self.facts[i])
+    }
+
+    /// id の事実を忘れる
+    pub fn forget(&mut self, id: u32) -> Option<MemoryFact> {
+        let i = self.facts.iter().position(|f| f.id == id)?;
+        let removed = self.facts.remove(i);
+        self.save();
+        Some(removed)
+    }
+
+    /// システムプロンプトに載せる形
+    pub fn prompt_lines(&self) -> Vec<String> {
+        self.facts
+            .iter()
+            .map(|f| format!("{}（{}）", f.text, f.category.label()))
+            .collect()
+    }
+
+    /// 「私について覚えていることを教えて」への答え
+    pub fn describe(&self) -> String {
+        if self.facts.is_empty() {
+            return "まだ何も覚えていません。よかったら、あなたのことを教えてくださいね。".to_string();
+        }
+        let list = self
+            .facts
+            .iter()
+            .map(|f| format!("・{}（{}）", f.text, f.category.label()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!("覚えていることは {} 件です。\n{}", self.facts.len(), list)
+    }
+}
+
+/// 記憶の確認・削除の指示を見分ける
+/// 「○○を忘れて」だけでは「傘を忘れて」のような普段の話と区別できないので、
+/// 「○○のことは忘れて」「○○を忘れてください」のようにはっきり頼まれたときだけにする
+pub fn detect_command(input: &str) -> Option<MemoryCommand> {
+    let input = input.trim().trim_end_matches(['。', '！', '!']);
+
+    if input.contains("覚えていること") || input.contains("覚えてること") {
+        return Some(MemoryCommand::Recall);
+    }
+    if matches!(input, "それは忘れて" | "それ忘れて" | "今のは忘れて" | "いまのは忘れて") {
+        return Some(MemoryCommand::ForgetLast);
+    }
+    for suffix in ["のことは忘れて", "のことを忘れて", "は忘れてください", "を忘れてください"] {
+        if let Some(target) = input.strip_suffix(suffix)
+            && !target.is_empty()
+        {
+            return Some(MemoryCommand::Forget(target.to_string()));
+        }
+    }
+    None
+}
+
+/// API を使わずに発言から事実を拾う（よくある言い回しだけ）
+pub fn extract_facts_locally(input: &str) -> Vec<(FactCategory, String)> {
+    let mut out = Vec::new();
+
+    for sentence in input.split(['。', '！', '!', '\n']).map(str::trim).filter(|s| !s.is_empty()) {
+        if sentence.ends_with('？') || sentence.ends_with('?') {
+            continue;
+        }
+
+        if (sentence.starts_with("好きな") && sentence.contains('は'))
+            || sentence.contains("が好き")
+            || sentence.contains("にハマって")
+            || sentence.contains("が苦手")
+        {
+            out.push((FactCategory::Preference, sentence.to_string()));
+        } else if ["毎週", "毎朝", "毎日", "毎月"].iter().any(|w| sentence.starts_with(w)) {
+            out.push((FactCategory::Event, sentence.to_string()));
+        } else if ["妻", "夫", "母", "父", "娘", "息子", "上司", "友達"]
+            .iter()
+            .any(|w| sentence.starts_with(&format!("{}の", w)) || sentence.starts_with(&format!("{}は", w)))
+        {
+            out.push((FactCategory::Person, sentence.to_string()));
+        }
+    }
+
+    out
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    struct TempMemory(PathBuf);
+
+    impl Drop for TempMemory {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_remember_deduplicates_and_persists() {
+        let file = TempMemory(std::env::temp_dir().join(format!("memory_test_{}.json", Uuid::new_v4())));
+        let mut store = MemoryStore::open(&file.0);
+
+        assert!(store.remember(FactCategory::Preference, "好きな食べ物はカレー").is_some());
+        assert!(store.remember(FactCategory::Preference, "好きな食べ物はカレー。").is_none());
+        assert!(store.remember(FactCategory::Person, "妻の誕生日は5月").is_some());
+
+        let reloaded = MemoryStore::open(&file.0);
+        assert_eq!(reloaded.facts().len(), 2);
+
+        let mut store = reloaded;
+        assert!(store.find_matching("傘").is_none());
+        let id = store.find_matching("カレー").unwrap().id;
+        assert_eq!(store.forget(id).unwrap().text, "好きな食べ物はカレー");
+        assert!(store.forget(id).is_none());
+        assert_eq!(store.forget_last().unwrap().text, "妻の誕生日は5月");
+        assert!(store.facts().is_empty());
+    }
+
+    #[test]
+    fn test_detect_command_and_local_extraction() {
+        assert_eq!(detect_command("私について覚えていることを教えて"), Some(MemoryCommand::Recall));
+        assert_eq!(detect_command("それは忘れて"), Some(MemoryCommand::ForgetLast));
+        assert_eq!(detect_command("カレーのことは忘れて。"), Some(MemoryCommand::Forget("カレー".into())));
+        assert_eq!(detect_command("カレーを忘れてください"), Some(MemoryCommand::Forget("カレー".into())));
+        assert_eq!(detect_command("今日は疲れた"), None);
+        // 普段の話の「忘れて」は指示にしない
+        assert_eq!(detect_command("昨日傘を忘れて"), None);
+        assert_eq!(detect_command("宿題は忘れて"), None);
+        assert_eq!(detect_command("財布を忘れてしまった"), None);
+
+        let facts = extract_facts_locally("最近は登山にハマってます。毎週土曜はジムに行きます。好きなものは何？");
+        assert_eq!(facts.len(), 2);
+        assert_eq!(facts[0].0, FactCategory::Preference);
+        assert_eq!(facts[1].0, FactCategory::Event);
+    }
+}
@@ -1,4 +1,4 @@
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, Deserializer};
 use chrono::NaiveDate;
 use serde_json::Value;
 use serde_json::Map;
@@ -7,20 +7,90 @@ use serde_json::Map;
 pub struct ChatRequest {
     pub model: String,
     pub messages: Vec<ChatMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ToolDefinition>>,
 }
 
-#[derive(Serialize, Clone, Deserialize)]
+#[derive(Serialize, Clone, Deserialize, Debug, Default)]
 pub struct ChatMessage {
     pub role: String,
+    // tool_calls を返すときは content が null になる
+    #[serde(default, deserialize_with = "null_as_empty")]
     pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
 }
 
-#[derive(Deserialize)]
+impl ChatMessage {
+    pub fn new(role: &str, content: impl Into<String>) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+
+    /// ツール実行結果をモデルに返すためのメッセージ
+    pub fn tool_result(tool_call_id: &str, content: impl Into<String>) -> Self {
+        Self {
+            role: "tool".to_string(),
+            content: content.into(),
+            tool_calls: None,
+            tool_call_id: Some(tool_call_id.to_string()),
+        }
+    }
+}
+
+fn null_as_empty<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FunctionCall {
+    pub name: String,
+    pub arguments: String,      // JSON文字列のまま届く
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ToolDefinition {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionDefinition,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: Value,      // JSON Schema
+}
+
+#[derive(Deserialize, Debug)]
 pub struct ChatResponse {
     pub choices: Vec<ChatChoice>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
 }
 
-#[derive(Deserialize)]
+/// レスポンスに含まれるトークン使用量
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+#[derive(Deserialize, Debug)]
 pub struct ChatChoice {
     pub message: ChatMessage,
 }
@@ -70,11 +140,38 @@ mod tests {
                     "role": "assistant",
                     "content": "タスクを追加しました。"
                 }
-            }]
+            }],
+            "usage": { "prompt_tokens": 12, "completion_tokens": 8, "total_tokens": 20 }
         }
         "#;
 
         let parsed: ChatResponse = serde_json::from_str(raw).unwrap();
         assert_eq!(parsed.choices[0].message.content, "タスクを追加しました。");
+        assert_eq!(parsed.usage.unwrap().total_tokens, 20);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_tool_call_response_deserialization() {
+        let raw = r#"
+        {
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": { "name": "add_task", "arguments": "{\"title\":\"会議の準備\"}" }
+                    }]
+                }
+            }]
+        }
+        "#;
+
+        let parsed: ChatResponse = serde_json::from_str(raw).unwrap();
+        let message = &parsed.choices[0].message;
+        assert_eq!(message.content, "");
+        let calls = message.tool_calls.as_ref().unwrap();
+        assert_eq!(calls[0].function.name, "add_task");
+    }
+}
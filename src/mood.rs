@@ -0,0 +1,333 @@
+use crate::persona::Persona;
+
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_MOOD_FILE: &str = "mood.json";
+
+/// 気分を見るときに振り返る発言の数
+pub const DEFAULT_WINDOW: usize = 5;
+
+// 直近の発言の合計がこれ以上なら、その状態とみなす
+const OVERWHELMED_STRESS: i32 = 4;
+const TIRED_FATIGUE: i32 = 3;
+const GOOD_VALENCE: i32 = 3;
+
+// (語, 快・不快, ストレス, 疲れ)
+const LEXICON: &[(&str, i32, i32, i32)] = &[
+    ("疲れ", -1, 0, 2),
+    ("つかれ", -1, 0, 2),
+    ("眠い", 0, 0, 1),
+    ("ねむい", 0, 0, 1),
+    ("寝不足", -1, 0, 2),
+    ("だるい", -1, 0, 2),
+    ("しんどい", -2, 1, 2),
+    ("ヘトヘト", -1, 0, 3),
+    ("くたくた", -1, 0, 3),
+    ("ため息", -1, 1, 1),
+    ("つらい", -2, 2, 0),
+    ("辛い", -2, 2, 0),
+    ("無理", -1, 2, 0),
+    ("終わらない", -1, 2, 0),
+    ("間に合わない", -1, 3, 0),
+    ("多すぎ", -1, 2, 0),
+    ("忙しい", -1, 1, 0),
+    ("ストレス", -2, 2, 0),
+    ("焦って", -1, 2, 0),
+    ("限界", -2, 3, 1),
+    ("いっぱいいっぱい", -2, 3, 0),
+    ("パンク", -2, 3, 0),
+    ("もう嫌", -3, 2, 0),
+    ("もうやだ", -3, 2, 0),
+    ("不安", -2, 2, 0),
+    ("落ち込", -2, 1, 0),
+    ("イライラ", -2, 2, 0),
+    ("嬉しい", 2, 0, 0),
+    ("うれしい", 2, 0, 0),
+    ("楽しい", 2, 0, 0),
+    ("たのしい", 2, 0, 0),
+    ("最高", 2, -1, 0),
+    ("できた", 1, -1, 0),
+    ("終わった", 1, -1, 0),
+    ("片付いた", 1, -1, 0),
+    ("助かった", 1, -1, 0),
+    ("スッキリ", 1, -1, 0),
+    ("よかった", 1, 0, 0),
+    ("ありがとう", 1, 0, 0),
+    ("元気", 1, 0, -1),
+];
+
+// 直後にこれが続いたら打ち消しとみなす（「疲れてない」「無理しないで」）
+const NEGATIONS: [&str; 5] = ["ない", "なく", "てない", "ていない", "しない"];
+
+/// 1回の発言から読み取った気分
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MoodScore {
+    pub valence: i32,   // 快(+) / 不快(-)
+    pub stress: i32,    // 追われている感じ
+    pub fatigue: i32,   // 体の疲れ
+}
+
+impl MoodScore {
+    pub fn is_neutral(&self) -> bool {
+        *self == MoodScore::default()
+    }
+
+    fn add(&mut self, other: &MoodScore) {
+        self.valence += other.valence;
+        self.stress += other.stress;
+        self.fatigue += other.fatigue;
+    }
+}
+
+/// 辞書で発言の気分を見積もる（APIは使わない）
+pub fn score_text(text: &str) -> MoodScore {
+    let mut score = MoodScore::default();
+    for (word, valence, stress, fatigue) in LEXICON {
+        for (i, _) in text.match_indices(word) {
+            let rest = &text[i + word.len()..];
+            if NEGATIONS.iter().any(|n| rest.starts_with(n)) {
+                continue;
+            }
+            score.add(&MoodScore { valence: *valence, stress: *stress, fatigue: *fatigue });
+        }
+    }
+    score
+}
+
+/// LLM が答えた気分のラベルを点数にする（assess_mood テンプレートの答え）
+pub fn score_label(label: &str) -> MoodScore {
+    match label.trim().trim_matches(['「', '」']) {
+        "元気" => MoodScore { valence: 2, stress: 0, fatigue: 0 },
+        "疲れ" => MoodScore { valence: -1, stress: 0, fatigue: 3 },
+        "いっぱいいっぱい" => MoodScore { valence: -2, stress: 4, fatigue: 0 },
+        _ => MoodScore::default(),
+    }
+}
+
+/// 直近の発言から見た、いまの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoodState {
+    Good,
+    Neutral,
+    Tired,
+    Overwhelmed,
+}
+
+impl MoodState {
+    fn from_total(total: &MoodScore) -> Self {
+        if total.stress >= OVERWHELMED_STRESS {
+            MoodState::Overwhelmed
+        } else if total.fatigue >= TIRED_FATIGUE {
+            MoodState::Tired
+        } else if total.valence >= GOOD_VALENCE {
+            MoodState::Good
+        } else {
+            MoodState::Neutral
+        }
+    }
+
+    /// 通知や独り言を控えめにしたほうがいい状態か
+    pub fn wants_gentle(&self) -> bool {
+        *self == MoodState::Overwhelmed
+    }
+}
+
+/// 直近 window 件の発言の気分を追いかける
+pub struct MoodTracker {
+    recent: VecDeque<MoodScore>,
+    window: usize,
+    state: MoodState,
+}
+
+impl Default for MoodTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW)
+    }
+}
+
+impl MoodTracker {
+    pub fn new(window: usize) -> Self {
+        Self { recent: VecDeque::new(), window: window.max(1), state: MoodState::Neutral }
+    }
+
+    pub fn state(&self) -> MoodState {
+        self.state
+    }
+
+    pub fn total(&self) -> MoodScore {
+        let mut total = MoodScore::default();
+        for score in &self.recent {
+            total.add(score);
+        }
+        total
+    }
+
+    /// 発言1件分を足す。状態が変わったら新しい状態を返す
+    pub fn observe(&mut self, score: MoodScore) -> Option<MoodState> {
+        self.recent.push_back(score);
+        while self.recent.len() > self.window {
+            self.recent.pop_front();
+        }
+
+        let state = MoodState::from_total(&self.total());
+        if state == self.state {
+            return None;
+        }
+        self.state = state;
+        Some(state)
+    }
+}
+
+/// 状態が変わったときにかける言葉（疲れには休憩、余裕がなさそうなら励ましと休憩）
+pub fn response_for(state: MoodState, persona: &Persona) -> Option<String> {
+    match state {
+        MoodState::Tired => Some(persona.break_suggestion.clone()),
+        MoodState::Overwhelmed => Some(format!("{} {}", persona.random_encouragement(), persona.break_suggestion)),
+        MoodState::Good | MoodState::Neutral => None,
+    }
+}
+
+/// 1日分の気分の記録
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyMood {
+    pub date: NaiveDate,
+    pub turns: u32,
+    pub valence: i32,
+    pub stress: i32,
+    pub fatigue: i32,
+}
+
+impl DailyMood {
+    pub fn label(&self) -> &'static str {
+        let total = MoodScore { valence: self.valence, stress: self.stress, fatigue: self.fatigue };
+        match MoodState::from_total(&total) {
+            MoodState::Good => "よさそう",
+            MoodState::Neutral => "ふつう",
+            MoodState::Tired => "お疲れ気味",
+            MoodState::Overwhelmed => "余裕がなさそう",
+        }
+    }
+}
+
+/// 日ごとの気分の推移（MOOD_FILE、既定: mood.json）
+pub struct MoodLog {
+    path: PathBuf,
+    days: Vec<DailyMood>,
+}
+
+impl MoodLog {
+    pub fn open(path: &Path) -> Self {
+        let days = fs::read_to_string(path)
+            .ok()
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+        Self { path: path.to_path_buf(), days }
+    }
+
+    pub fn open_default() -> Self {
+        let path = env::var("MOOD_FILE").unwrap_or_else(|_| DEFAULT_MOOD_FILE.to_string());
+        Self::open(Path::new(&path))
+    }
+
+    pub fn days(&self) -> &[DailyMood] {
+        &self.days
+    }
+
+    fn save(&self) {
+        match serde_json::to_string_pretty(&self.days) {
+            Ok(json) => {
+                if let Err(e) = fs::write(&self.path, json) {
+                    eprintln!("Failed to write mood file: {} ({})", self.path.display(), e);
+                }
+            }
+            Err(e) => eprintln!("Failed to serialize mood log: {}", e),
+        }
+    }
+
+    /// その日の集計に発言1件分を足す
+    pub fn record(&mut self, date: NaiveDate, score: MoodScore) {
+        let day = match self.days.iter().position(|d| d.date == date) {
+            Some(i) => &mut self.days[i],
+            None => {
+                self.days.push(DailyMood { date, turns: 0, valence: 0, stress: 0, fatigue: 0 });
+                self.days.sort_by_key(|d| d.date);
+                self.days.iter_mut().find(|d| d.date == date).unwrap()
+            }
+        };
+        day.turns += 1;
+        day.valence += score.valence;
+        day.stress += score.stress;
+        day.fatigue += score.fatigue;
+        self.save();
+    }
+
+    /// 「最近の調子」への答え（直近7日）
+    pub fn describe_trend(&self, today: NaiveDate) -> String {
+        let since = today - Duration::days(6);
+        let recent: Vec<&DailyMood> = self.days.iter().filter(|d| d.date >= since && d.date <= today).collect();
+        if recent.is_empty() {
+            return "まだ記録がありません。".to_string();
+        }
+
+        let lines = recent
+            .iter()
+            .map(|d| format!("・{}  {}（{}回）", d.date.format("%m/%d"), d.label(), d.turns))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let tired_days = recent.iter().filter(|d| matches!(d.label(), "お疲れ気味" | "余裕がなさそう")).count();
+        let summary = if tired_days * 2 > recent.len() {
+            "ここのところ大変そうですね。少しペースを落としてもいいかもしれません。"
+        } else {
+            "おおむね落ち着いて過ごせていそうです。"
+        };
+        format!("最近の調子です。\n{}\n{}", lines, summary)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lexicon_and_negation() {
+        assert_eq!(score_text("もう疲れた…眠い"), MoodScore { valence: -1, stress: 0, fatigue: 3 });
+        assert!(score_text("今日は全然疲れてないよ").is_neutral());
+        assert!(score_text("牛乳を買う").is_neutral());
+        assert!(score_text("全部終わった！嬉しい").valence > 0);
+    }
+
+    #[test]
+    fn test_tracker_changes_state_once_and_recovers() {
+        let mut tracker = MoodTracker::new(3);
+        assert_eq!(tracker.observe(score_text("仕事が終わらない")), None);
+        assert_eq!(tracker.observe(score_text("締め切りに間に合わない")), Some(MoodState::Overwhelmed));
+        assert_eq!(tracker.observe(score_text("資料を作る")), None);
+        assert!(tracker.state().wants_gentle());
+
+        // 古い発言が窓から出ると戻る
+        assert_eq!(tracker.observe(MoodScore::default()), Some(MoodState::Neutral));
+    }
+
+    #[test]
+    fn test_daily_log() {
+        let path = std::env::temp_dir().join(format!("mood_{}.json", uuid::Uuid::new_v4()));
+        let day = |d| NaiveDate::from_ymd_opt(2025, 6, d).unwrap();
+        let mut log = MoodLog::open(&path);
+        log.record(day(2), score_text("ヘトヘト"));
+        log.record(day(1), score_text("楽しい！最高"));
+        log.record(day(2), score_text("もう寝る"));
+
+        let reopened = MoodLog::open(&path);
+        assert_eq!(reopened.days().len(), 2);
+        assert_eq!(reopened.days()[0].date, day(1));
+        assert_eq!(reopened.days()[1].turns, 2);
+        assert!(reopened.describe_trend(day(2)).contains("06/02  お疲れ気味（2回）"));
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,306 @@
+use rand::prelude::IndexedRandom;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::{OnceLock, RwLock};
+
+pub const DEFAULT_PERSONA_FILE: &str = "personas.json";
+
+/// 口調の丁寧さ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Politeness {
+    Casual,     // タメ口
+    Polite,     // です・ます
+    Formal,     // 敬語
+}
+
+impl Politeness {
+    fn instruction(&self) -> &'static str {
+        match self {
+            Politeness::Casual => "ユーザーに対しては友達のようなくだけた口調（タメ口）で話します",
+            Politeness::Polite => "ユーザーに対しては丁寧で親しみやすい口調で話します",
+            Politeness::Formal => "ユーザーに対しては敬語を崩さず、落ち着いた口調で話します",
+        }
+    }
+}
+
+/// 秘書のキャラクター。セリフと声はすべてここから取る
+/// {count} / {time} は使う側で埋める
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Persona {
+    pub name: String,
+    pub speech_style: String,
+    pub politeness: Politeness,
+    pub first_greeting: String,
+    pub morning_greeting: String,
+    pub all_done: String,
+    pub pending_tasks: String,
+    pub time_announcement: String,
+    pub encouragements: Vec<String>,
+    /// 疲れていそうなときの休憩の提案
+    pub break_suggestion: String,
+    /// VoiceVox の話者ID
+    pub speaker: u32,
+}
+
+impl Default for Persona {
+    fn default() -> Self {
+        Self {
+            name: "ことのは".into(),
+            speech_style: "話し方は柔らかく、女性的な印象にしてください".into(),
+            politeness: Politeness::Polite,
+            first_greeting: "はじめまして、秘書のことのはです。今日もよろしくお願いしますね。".into(),
+            morning_greeting: "おはようございます。".into(),
+            all_done: "すべてのタスクが完了しています。今日もいい日になりますように。".into(),
+            pending_tasks: "現在 {count} 件のタスクがあります。".into(),
+            time_announcement: "ただいま、{time}です。水分補給と休憩も忘れずに。".into(),
+            encouragements: vec![
+                "焦らず、自分のペースで進めましょうね。".into(),
+                "無理せず、できることからで大丈夫ですよ。".into(),
+                "あなたならきっと大丈夫です！".into(),
+                "今日も一歩前進ですね。応援しています。".into(),
+                "疲れたら、少し休むのも大事ですよ。".into(),
+                "頑張りすぎないでくださいね。ことのははいつでも味方です。".into(),
+            ],
+            break_suggestion: "少し休憩しませんか？5分だけでも目を閉じると楽になりますよ。".into(),
+            speaker: 8, // 春日部つむぎ
+        }
+    }
+}
+
+impl Persona {
+    /// 元気で砕けた口調のもう一人（ファイルが無いときの切り替え先）
+    pub fn hiyori() -> Self {
+        Self {
+            name: "ひより".into(),
+            speech_style: "話し方は明るく元気に、短めの文で話してください".into(),
+            politeness: Politeness::Casual,
+            first_greeting: "はじめまして、秘書のひよりだよ。今日もよろしくね！".into(),
+            morning_greeting: "おはよう！".into(),
+            all_done: "タスクは全部おわってるよ。今日はのんびりいこう！".into(),
+            pending_tasks: "いまタスクが {count} 件あるよ。".into(),
+            time_announcement: "{time}になったよ。お水飲んで、ちょっと休憩しよっか。".into(),
+            encouragements: vec![
+                "だいじょうぶ、ちゃんと進んでるよ！".into(),
+                "ひとつずつ片付けていこう！".into(),
+                "疲れたら休憩ね。ひよりも応援してる！".into(),
+            ],
+            break_suggestion: "ちょっと休憩しよっか？5分だけでも目を休めよう！".into(),
+            speaker: 2, // 四国めたん
+        }
+    }
+
+    /// 会話用のシステムプロンプト
+    pub fn system_prompt(&self) -> String {
+        format!(
+            r#"
+あなたの名前は「{name}」です。
+あなたはユーザー専属の秘書型AIとして動作します。
+
+【会話ルール】
+- 自分の名前は必ず「{name}」と名乗ってください
+- {politeness}
+- 名前や役割を聞かれたときは「秘書の{name}です」と答えてください
+- {style}
+- ユーザーの感情に寄り添い、共感的に返答します
+
+【目的】
+- ユーザーのタスク管理をサポートする
+- ユーザーの生活や思考を整理する手助けをする
+- 必要に応じてタスクを提案する
+
+これからユーザーと会話を始めます。
+"#,
+            name = self.name,
+            politeness = self.politeness.instruction(),
+            style = self.speech_style,
+        )
+    }
+
+    pub fn random_encouragement(&self) -> String {
+        self.encouragements
+            .choose(&mut rand::rng())
+            .cloned()
+            .unwrap_or_else(|| Persona::default().encouragements[0].clone())
+    }
+}
+
+/// 選べるキャラクターの一覧（PERSONA_FILE、既定: personas.json）
+#[derive(Debug, Clone)]
+pub struct PersonaBook {
+    personas: Vec<Persona>,
+}
+
+impl PersonaBook {
+    pub fn builtin() -> Self {
+        Self { personas: vec![Persona::default(), Persona::hiyori()] }
+    }
+
+    /// ファイル（Persona の配列のJSON）を読む。無い・読めない・空なら組み込みを使う
+    /// 名前の無いキャラは使わない。省略した項目は「ことのは」のもので補い、どれを補ったかを知らせる
+    pub fn load(path: &Path) -> Self {
+        let entries: Vec<Value> = match fs::read_to_string(path) {
+            Ok(raw) => serde_json::from_str(&raw).unwrap_or_else(|e| {
+                eprintln!("Failed to parse persona file: {} ({})", path.display(), e);
+                vec![]
+            }),
+            Err(_) => vec![],
+        };
+        let personas: Vec<Persona> = entries.into_iter().filter_map(parse_persona).collect();
+
+        if personas.is_empty() {
+            return Self::builtin();
+        }
+        Self { personas }
+    }
+
+    pub fn from_env() -> Self {
+        let path = env::var("PERSONA_FILE").unwrap_or_else(|_| DEFAULT_PERSONA_FILE.to_string());
+        Self::load(Path::new(&path))
+    }
+
+    pub fn personas(&self) -> &[Persona] {
+        &self.personas
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Persona> {
+        let name = name.trim();
+        self.personas.iter().find(|p| p.name == name)
+    }
+
+    /// current の次のキャラ（最後なら先頭に戻る）
+    pub fn next_after(&self, current: &str) -> &Persona {
+        let i = self.personas.iter().position(|p| p.name == current).map_or(0, |i| i + 1);
+        &self.personas[i % self.personas.len()]
+    }
+
+    /// 起動時のキャラ（PERSONA で名前を指定、なければ先頭）
+    pub fn initial(&self) -> &Persona {
+        env::var("PERSONA")
+            .ok()
+            .and_then(|name| self.find(&name))
+            .unwrap_or(&self.personas[0])
+    }
+}
+
+// ファイルの1人分を読む（名前が無い・読めないなら None）
+fn parse_persona(entry: Value) -> Option<Persona> {
+    let Some(name) = entry["name"].as_str().map(str::trim).filter(|n| !n.is_empty()).map(str::to_string) else {
+        eprintln!("Skipping persona without a name: {}", entry);
+        return None;
+    };
+    let persona: Persona = match serde_json::from_value(entry.clone()) {
+        Ok(persona) => persona,
+        Err(e) => {
+            eprintln!("Failed to parse persona: {} ({})", name, e);
+            return None;
+        }
+    };
+
+    let default_name = Persona::default().name;
+    let missing = defaulted_fields(&entry);
+    if name != default_name && !missing.is_empty() {
+        eprintln!("Persona {} uses {}'s defaults for: {}", name, default_name, missing.join(", "));
+    }
+    Some(persona)
+}
+
+/// ファイルの1人分で省略されていて、「ことのは」のもので補われる項目
+pub fn defaulted_fields(entry: &Value) -> Vec<String> {
+    let Ok(Value::Object(fields)) = serde_json::to_value(Persona::default()) else {
+        return vec![];
+    };
+    fields.into_iter().map(|(key, _)| key).filter(|key| entry.get(key).is_none()).collect()
+}
+
+static ACTIVE: OnceLock<RwLock<Persona>> = OnceLock::new();
+
+fn active_slot() -> &'static RwLock<Persona> {
+    ACTIVE.get_or_init(|| RwLock::new(Persona::default()))
+}
+
+/// いま使っているキャラ
+pub fn active() -> Persona {
+    active_slot().read().unwrap().clone()
+}
+
+pub fn set_active(persona: Persona) {
+    *active_slot().write().unwrap() = persona;
+}
+
+/// キャラ切り替えの指示
+#[derive(Debug, Clone, PartialEq)]
+pub enum PersonaCommand {
+    Next,               // キャラを変えて
+    Switch(String),     // キャラを○○に変えて
+}
+
+pub fn detect_command(input: &str) -> Option<PersonaCommand> {
+    let input = input.trim().trim_end_matches(['。', '！', '!']);
+    let rest = input.strip_prefix("キャラを").or_else(|| input.strip_prefix("キャラ"))?;
+
+    if matches!(rest, "変えて" | "かえて" | "交代" | "チェンジ") {
+        return Some(PersonaCommand::Next);
+    }
+    for suffix in ["に変えて", "にかえて", "にして"] {
+        if let Some(name) = rest.strip_suffix(suffix)
+            && !name.is_empty()
+        {
+            return Some(PersonaCommand::Switch(name.to_string()));
+        }
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_prompt_uses_persona() {
+        let prompt = Persona::hiyori().system_prompt();
+        assert!(prompt.contains("「ひより」と名乗って"));
+        assert!(prompt.contains("タメ口"));
+        assert!(Persona::default().system_prompt().contains("秘書のことのはです"));
+    }
+
+    #[test]
+    fn test_load_fills_missing_fields_and_cycles() {
+        let path = std::env::temp_dir().join(format!("persona_test_{}.json", uuid::Uuid::new_v4()));
+        fs::write(&path, r#"[{"name": "ことのは"}, {"speaker": 3}, {"name": "執事", "politeness": "formal", "speaker": 13}]"#).unwrap();
+
+        let book = PersonaBook::load(&path);
+        assert_eq!(book.personas().len(), 2);
+        let butler = book.find("執事").unwrap();
+        assert_eq!(butler.politeness, Politeness::Formal);
+        assert_eq!(butler.speaker, 13);
+        assert!(!butler.encouragements.is_empty());
+        assert_eq!(book.next_after("ことのは").name, "執事");
+        assert_eq!(book.next_after("執事").name, "ことのは");
+
+        let _ = fs::remove_file(&path);
+        assert_eq!(PersonaBook::load(&path).personas().len(), 2);
+    }
+
+    #[test]
+    fn test_defaulted_fields_are_listed() {
+        let missing = defaulted_fields(&serde_json::json!({"name": "執事", "politeness": "formal", "speaker": 13}));
+        assert!(missing.contains(&"speech_style".to_string()));
+        assert!(missing.contains(&"encouragements".to_string()));
+        assert!(!missing.contains(&"name".to_string()));
+        assert!(!missing.contains(&"speaker".to_string()));
+    }
+
+    #[test]
+    fn test_detect_command() {
+        assert_eq!(detect_command("キャラを変えて"), Some(PersonaCommand::Next));
+        assert_eq!(detect_command("キャラをひよりに変えて。"), Some(PersonaCommand::Switch("ひより".into())));
+        assert_eq!(detect_command("キャラクターが好き"), None);
+        assert_eq!(detect_command("予定を変えて"), None);
+    }
+}
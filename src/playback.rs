@@ -0,0 +1,482 @@
+use crate::speech::SpeechKind;
+use crate::tts::TtsError;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+#[cfg(feature = "tts")]
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
+
+#[cfg(feature = "tts")]
+use std::io::Cursor;
+
+pub const DEFAULT_AUDIO_OUTPUT_DIR: &str = "speech_audio";
+pub const INDEX_FILE: &str = "index.jsonl";
+
+// 再生が終わったかを見に行く間隔
+const POLL: Duration = Duration::from_millis(20);
+
+/// 鳴らす1回分（WAV と、何をどのエンジンで喋ったか）
+#[derive(Debug, Clone)]
+pub struct Clip {
+    pub audio: Vec<u8>,
+    pub text: String,
+    pub kind: Option<SpeechKind>, // SpeechQueue を通さない発話（挨拶・時報）は None
+    pub engine: String,
+}
+
+/// 音を出す先。再生スレッドの中だけで使う
+pub trait AudioSink {
+    /// WAV を鳴らし始める（終わるのは待たない）
+    fn append(&mut self, clip: Clip) -> Result<(), TtsError>;
+    /// 鳴らしているものが無くなったか
+    fn is_finished(&self) -> bool;
+    fn stop(&mut self);
+    fn pause(&mut self);
+    fn resume(&mut self);
+}
+
+/// 1回の再生がどう終わったか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackEnd {
+    Finished,
+    Stopped, // stop() で止められた
+}
+
+enum Command {
+    Play(Clip, oneshot::Sender<Result<PlaybackEnd, TtsError>>),
+    Stop,
+    Pause,
+    Resume,
+}
+
+/// 専用のスレッドで音を鳴らすためのハンドル（一度に鳴るのは1つで、あとから来たものは順番を待つ）
+/// 複製しても同じスレッドを指すので、止める側と待つ側で分けて持てる
+#[derive(Clone)]
+pub struct Player {
+    tx: Option<mpsc::Sender<Command>>,
+}
+
+impl Player {
+    /// 再生スレッドを起こす。sink はスレッドの中で作る（rodio の出力は他のスレッドに渡せない）
+    pub fn spawn<F>(make_sink: F) -> Self
+    where
+        F: FnOnce() -> Result<Box<dyn AudioSink>, TtsError> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let sink = make_sink()
+                .map_err(|e| eprintln!("Failed to open audio output: {}", e))
+                .ok();
+            run(sink, rx);
+        });
+        Self { tx: Some(tx) }
+    }
+
+    /// 作った sink をそのまま使う（テスト用の FakeSink など）
+    pub fn with_sink(sink: impl AudioSink + Send + 'static) -> Self {
+        Self::spawn(move || Ok(Box::new(sink) as Box<dyn AudioSink>))
+    }
+
+    /// 何も鳴らさない（TTS 機能の無いビルド用）
+    pub fn silent() -> Self {
+        Self { tx: None }
+    }
+
+    /// 実際に音が出るか
+    pub fn is_audible(&self) -> bool {
+        self.tx.is_some()
+    }
+
+    /// clip を鳴らし、終わるか止められるまで待つ。鳴っていたものがあれば、それが終わってから鳴らす
+    /// 待つのをやめる（future を捨てる）と、その clip は鳴らさない・鳴っていれば止める
+    pub async fn play(&self, clip: Clip) -> Result<PlaybackEnd, TtsError> {
+        let Some(tx) = &self.tx else {
+            return Ok(PlaybackEnd::Finished);
+        };
+        let (done_tx, done_rx) = oneshot::channel();
+        tx.send(Command::Play(clip, done_tx)).map_err(|_| stopped_thread())?;
+        done_rx.await.map_err(|_| stopped_thread())?
+    }
+
+    pub fn stop(&self) {
+        self.send(Command::Stop);
+    }
+
+    pub fn pause(&self) {
+        self.send(Command::Pause);
+    }
+
+    pub fn resume(&self) {
+        self.send(Command::Resume);
+    }
+
+    fn send(&self, command: Command) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(command);
+        }
+    }
+}
+
+fn stopped_thread() -> TtsError {
+    TtsError::Engine("audio thread has stopped".to_string())
+}
+
+// 再生スレッドの本体。命令を捌きながら、鳴らし終わったら待っている側に知らせて次を鳴らす
+fn run(mut sink: Option<Box<dyn AudioSink>>, rx: mpsc::Receiver<Command>) {
+    let mut current: Option<oneshot::Sender<Result<PlaybackEnd, TtsError>>> = None;
+    let mut waiting: VecDeque<(Clip, oneshot::Sender<Result<PlaybackEnd, TtsError>>)> = VecDeque::new();
+    loop {
+        match rx.recv_timeout(POLL) {
+            Ok(Command::Play(clip, done)) => {
+                if sink.is_none() {
+                    let _ = done.send(Err(TtsError::Engine("no audio output".to_string())));
+                    continue;
+                }
+                waiting.push_back((clip, done));
+            }
+            Ok(Command::Stop) => {
+                if let (Some(sink), Some(previous)) = (sink.as_mut(), current.take()) {
+                    sink.stop();
+                    let _ = previous.send(Ok(PlaybackEnd::Stopped));
+                }
+            }
+            Ok(Command::Pause) => sink.iter_mut().for_each(|s| s.pause()),
+            Ok(Command::Resume) => sink.iter_mut().for_each(|s| s.resume()),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let Some(sink) = sink.as_mut() else {
+            continue;
+        };
+        // 待つのをやめられたものは止める
+        if current.as_ref().is_some_and(|done| done.is_closed()) {
+            sink.stop();
+            current = None;
+        }
+        if current.is_some()
+            && sink.is_finished()
+            && let Some(done) = current.take()
+        {
+            let _ = done.send(Ok(PlaybackEnd::Finished));
+        }
+        // 鳴っていなければ、順番を待っているものを鳴らす
+        while current.is_none() {
+            let Some((clip, done)) = waiting.pop_front() else {
+                break;
+            };
+            if done.is_closed() {
+                continue;
+            }
+            match sink.append(clip) {
+                Ok(()) => current = Some(done),
+                Err(e) => {
+                    let _ = done.send(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// rodio でスピーカーから鳴らす
+#[cfg(feature = "tts")]
+pub struct RodioSink {
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+    sink: Sink,
+}
+
+#[cfg(feature = "tts")]
+impl RodioSink {
+    pub fn open() -> Result<Self, TtsError> {
+        let (stream, handle) = OutputStream::try_default().map_err(|e| TtsError::Engine(e.to_string()))?;
+        let sink = Sink::try_new(&handle).map_err(|e| TtsError::Engine(e.to_string()))?;
+        Ok(Self { _stream: stream, handle, sink })
+    }
+}
+
+#[cfg(feature = "tts")]
+impl AudioSink for RodioSink {
+    fn append(&mut self, clip: Clip) -> Result<(), TtsError> {
+        let source = Decoder::new(Cursor::new(clip.audio)).map_err(|e| TtsError::Engine(e.to_string()))?;
+        self.sink.append(source);
+        Ok(())
+    }
+
+    fn is_finished(&self) -> bool {
+        self.sink.empty()
+    }
+
+    fn stop(&mut self) {
+        // 止めた Sink には続きを足せないので作り直す
+        self.sink.stop();
+        match Sink::try_new(&self.handle) {
+            Ok(sink) => self.sink = sink,
+            Err(e) => eprintln!("Failed to reopen audio output: {}", e),
+        }
+    }
+
+    fn pause(&mut self) {
+        self.sink.pause();
+    }
+
+    fn resume(&mut self) {
+        self.sink.play();
+    }
+}
+
+/// WAV のヘッダー（fmt の byte rate と data の大きさ）から長さを出す
+pub fn wav_duration(audio: &[u8]) -> Option<Duration> {
+    if audio.len() < 12 || &audio[..4] != b"RIFF" || &audio[8..12] != b"WAVE" {
+        return None;
+    }
+    let u32_at = |i: usize| audio.get(i..i + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+
+    let mut byte_rate = None;
+    let mut pos = 12;
+    while let (Some(id), Some(size)) = (audio.get(pos..pos + 4), u32_at(pos + 4)) {
+        match id {
+            b"fmt " => byte_rate = u32_at(pos + 16),
+            b"data" => {
+                // ヘッダーの大きさが実際より大きければ、残っている分で数える
+                let size = (size as usize).min(audio.len().saturating_sub(pos + 8));
+                let byte_rate = byte_rate.filter(|&r| r > 0)?;
+                return Some(Duration::from_secs_f64(size as f64 / byte_rate as f64));
+            }
+            _ => {}
+        }
+        pos += 8 + size as usize + (size as usize & 1);
+    }
+    None
+}
+
+/// 書き出した発話1件分（index.jsonl の1行）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeechRecord {
+    pub at: DateTime<Local>,
+    pub file: String,
+    pub text: String,
+    pub kind: Option<SpeechKind>,
+    pub engine: String,
+    pub duration_ms: Option<u64>,
+}
+
+/// 鳴らさずに、発話ごとの WAV と索引（index.jsonl）をディレクトリに書く（画面・スピーカーの無いサーバー用）
+pub struct WavFileSink {
+    dir: PathBuf,
+    seq: u64,
+}
+
+impl WavFileSink {
+    pub fn new(dir: &Path) -> Self {
+        Self { dir: dir.to_path_buf(), seq: 0 }
+    }
+
+    /// dir の索引を古い順に読む（読めない行は飛ばす）
+    pub fn read_index(dir: &Path) -> Vec<SpeechRecord> {
+        fs::read_to_string(dir.join(INDEX_FILE))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+}
+
+impl AudioSink for WavFileSink {
+    fn append(&mut self, clip: Clip) -> Result<(), TtsError> {
+        fs::create_dir_all(&self.dir)?;
+        let at = Local::now();
+        let file = format!("{}-{:04}.wav", at.format("%Y%m%d-%H%M%S"), self.seq);
+        self.seq += 1;
+        fs::write(self.dir.join(&file), &clip.audio)?;
+
+        let record = SpeechRecord {
+            at,
+            file,
+            duration_ms: wav_duration(&clip.audio).map(|d| d.as_millis() as u64),
+            text: clip.text,
+            kind: clip.kind,
+            engine: clip.engine,
+        };
+        let mut index = OpenOptions::new().create(true).append(true).open(self.dir.join(INDEX_FILE))?;
+        writeln!(index, "{}", serde_json::to_string(&record)?)?;
+        Ok(())
+    }
+
+    // 書いたらすぐ終わり
+    fn is_finished(&self) -> bool {
+        true
+    }
+
+    fn stop(&mut self) {}
+
+    fn pause(&mut self) {}
+
+    fn resume(&mut self) {}
+}
+
+/// テスト用の sink。WAV の中身は見ず、どれも clip の長さだけ鳴ったことにする
+pub struct FakeSink {
+    clip: Duration,
+    started: Option<Instant>,
+    paused_at: Option<Instant>,
+    paused_for: Duration,
+    events: Arc<Mutex<Vec<SinkEvent>>>,
+}
+
+/// FakeSink が受けた操作
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkEvent {
+    Play(Vec<u8>),
+    Stop,
+    Pause,
+    Resume,
+}
+
+impl FakeSink {
+    pub fn new(clip: Duration) -> Self {
+        Self { clip, started: None, paused_at: None, paused_for: Duration::ZERO, events: Arc::default() }
+    }
+
+    /// 受けた操作の記録（sink を Player に渡す前に取っておく）
+    pub fn events(&self) -> Arc<Mutex<Vec<SinkEvent>>> {
+        self.events.clone()
+    }
+
+    fn log(&self, event: SinkEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+impl AudioSink for FakeSink {
+    fn append(&mut self, clip: Clip) -> Result<(), TtsError> {
+        self.log(SinkEvent::Play(clip.audio));
+        self.started = Some(Instant::now());
+        self.paused_at = None;
+        self.paused_for = Duration::ZERO;
+        Ok(())
+    }
+
+    fn is_finished(&self) -> bool {
+        let Some(started) = self.started else {
+            return true;
+        };
+        if self.paused_at.is_some() {
+            return false;
+        }
+        started.elapsed().saturating_sub(self.paused_for) >= self.clip
+    }
+
+    fn stop(&mut self) {
+        self.log(SinkEvent::Stop);
+        self.started = None;
+    }
+
+    fn pause(&mut self) {
+        self.log(SinkEvent::Pause);
+        self.paused_at.get_or_insert_with(Instant::now);
+    }
+
+    fn resume(&mut self) {
+        self.log(SinkEvent::Resume);
+        if let Some(paused_at) = self.paused_at.take() {
+            self.paused_for += paused_at.elapsed();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(audio: &[u8]) -> Clip {
+        Clip { audio: audio.to_vec(), text: String::new(), kind: None, engine: "test".into() }
+    }
+
+    #[tokio::test]
+    async fn test_play_waits_for_the_clip_and_pause_extends_it() {
+        let sink = FakeSink::new(Duration::from_millis(60));
+        let events = sink.events();
+        let player = Player::with_sink(sink);
+
+        let started = Instant::now();
+        assert_eq!(player.play(clip(b"RIFFa")).await.unwrap(), PlaybackEnd::Finished);
+        assert!(started.elapsed() >= Duration::from_millis(60));
+
+        let paused = player.clone();
+        let started = Instant::now();
+        let pausing = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            paused.pause();
+            tokio::time::sleep(Duration::from_millis(80)).await;
+            paused.resume();
+        });
+        assert_eq!(player.play(clip(b"RIFFb")).await.unwrap(), PlaybackEnd::Finished);
+        assert!(started.elapsed() >= Duration::from_millis(140));
+        pausing.await.unwrap();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![SinkEvent::Play(b"RIFFa".to_vec()), SinkEvent::Play(b"RIFFb".to_vec()), SinkEvent::Pause, SinkEvent::Resume]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stop_ends_playback_early() {
+        let player = Player::with_sink(FakeSink::new(Duration::from_secs(10)));
+        let stopper = player.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            stopper.stop();
+        });
+
+        let result = tokio::time::timeout(Duration::from_secs(1), player.play(clip(b"RIFF"))).await.unwrap();
+        assert_eq!(result.unwrap(), PlaybackEnd::Stopped);
+        assert!(!Player::silent().is_audible());
+    }
+
+    #[tokio::test]
+    async fn test_later_clips_wait_and_abandoned_ones_are_skipped() {
+        let sink = FakeSink::new(Duration::from_millis(50));
+        let events = sink.events();
+        let player = Player::with_sink(sink);
+
+        // 鳴っている途中に来たものは、割り込まずに順番を待つ
+        let (first, second) = tokio::join!(player.play(clip(b"RIFFa")), async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            player.play(clip(b"RIFFb")).await
+        });
+        assert_eq!(first.unwrap(), PlaybackEnd::Finished);
+        assert_eq!(second.unwrap(), PlaybackEnd::Finished);
+
+        // 待つのをやめたものは止め、まだ鳴らしていないものは鳴らさない
+        let _ = tokio::time::timeout(
+            Duration::from_millis(20),
+            async { tokio::join!(player.play(clip(b"RIFFc")), player.play(clip(b"RIFFd"))) },
+        )
+        .await;
+        assert_eq!(player.play(clip(b"RIFFe")).await.unwrap(), PlaybackEnd::Finished);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                SinkEvent::Play(b"RIFFa".to_vec()),
+                SinkEvent::Play(b"RIFFb".to_vec()),
+                SinkEvent::Play(b"RIFFc".to_vec()),
+                SinkEvent::Stop,
+                SinkEvent::Play(b"RIFFe".to_vec()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+pub const DEFAULT_LOCALE: &str = "ja";
+
+pub const CLASSIFY_INPUT: &str = "classify_input";
+pub const CLASSIFY_TASK_ACTION: &str = "classify_task_action";
+pub const EXTRACT_TASK: &str = "extract_task";
+pub const EXTRACT_USER_FACTS: &str = "extract_user_facts";
+pub const SUMMARIZE_HISTORY: &str = "summarize_history";
+pub const ASSESS_MOOD: &str = "assess_mood";
+
+// 組み込みの既定テンプレート: (ロケール, 名前, 本文)
+const EMBEDDED: &[(&str, &str, &str)] = &[
+    ("ja", CLASSIFY_INPUT, include_str!("../prompts/ja/classify_input.txt")),
+    ("ja", CLASSIFY_TASK_ACTION, include_str!("../prompts/ja/classify_task_action.txt")),
+    ("ja", EXTRACT_TASK, include_str!("../prompts/ja/extract_task.txt")),
+    ("ja", EXTRACT_USER_FACTS, include_str!("../prompts/ja/extract_user_facts.txt")),
+    ("ja", SUMMARIZE_HISTORY, include_str!("../prompts/ja/summarize_history.txt")),
+    ("ja", ASSESS_MOOD, include_str!("../prompts/ja/assess_mood.txt")),
+    ("en", CLASSIFY_INPUT, include_str!("../prompts/en/classify_input.txt")),
+    ("en", CLASSIFY_TASK_ACTION, include_str!("../prompts/en/classify_task_action.txt")),
+    ("en", EXTRACT_TASK, include_str!("../prompts/en/extract_task.txt")),
+    ("en", EXTRACT_USER_FACTS, include_str!("../prompts/en/extract_user_facts.txt")),
+    ("en", SUMMARIZE_HISTORY, include_str!("../prompts/en/summarize_history.txt")),
+    ("en", ASSESS_MOOD, include_str!("../prompts/en/assess_mood.txt")),
+];
+
+/// 名前付きのプロンプト1つ。本文の {変数名} を render で埋める
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub version: u32,
+    pub locale: String,
+    pub text: String,
+}
+
+impl PromptTemplate {
+    /// ファイルの中身を読む。先頭行が「# version: N」なら版として扱う（なければ 1）
+    pub fn parse(name: &str, locale: &str, raw: &str) -> Self {
+        let (version, body) = match raw.split_once('\n') {
+            Some((first, rest)) if first.trim_start().starts_with("# version:") => {
+                let version = first.trim_start()["# version:".len()..].trim().parse().unwrap_or(1);
+                (version, rest)
+            }
+            _ => (1, raw),
+        };
+
+        Self {
+            name: name.to_string(),
+            version,
+            locale: locale.to_string(),
+            text: body.trim_end().to_string(),
+        }
+    }
+
+    /// 台帳に残す識別子（例: classify_input@1/ja）
+    pub fn id(&self) -> String {
+        format!("{}@{}/{}", self.name, self.version, self.locale)
+    }
+
+    /// 本文中の変数名（出てきた順、重複なし）
+    pub fn variables(&self) -> Vec<&str> {
+        let mut vars = Vec::new();
+        let mut rest = self.text.as_str();
+        while let Some(start) = rest.find('{') {
+            let Some(len) = rest[start + 1..].find('}') else {
+                break;
+            };
+            let name = &rest[start + 1..start + 1 + len];
+            if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') && !vars.contains(&name) {
+                vars.push(name);
+            }
+            rest = &rest[start + 1 + len..];
+        }
+        vars
+    }
+
+    pub fn render(&self, vars: &[(&str, &str)]) -> String {
+        vars.iter()
+            .fold(self.text.clone(), |text, (name, value)| text.replace(&format!("{{{}}}", name), value))
+    }
+}
+
+/// プロンプトの置き場所。組み込みの既定を、PROMPT_DIR の同じ配置のファイルで上書きできる
+#[derive(Debug, Clone, Default)]
+pub struct PromptRegistry {
+    templates: HashMap<(String, String), PromptTemplate>,
+}
+
+impl PromptRegistry {
+    pub fn embedded() -> Self {
+        let mut registry = Self::default();
+        for (locale, name, raw) in EMBEDDED {
+            registry.insert(PromptTemplate::parse(name, locale, raw));
+        }
+        registry
+    }
+
+    /// dir/<ロケール>/<名前>.txt を読み込んで上書きする
+    pub fn load(mut self, dir: &Path) -> Self {
+        let Ok(locales) = fs::read_dir(dir) else {
+            return self;
+        };
+        for locale_dir in locales.flatten().filter(|e| e.path().is_dir()) {
+            let locale = locale_dir.file_name().to_string_lossy().to_string();
+            let Ok(files) = fs::read_dir(locale_dir.path()) else {
+                continue;
+            };
+            for file in files.flatten() {
+                let path = file.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("txt") {
+                    continue;
+                }
+                let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                    continue;
+                };
+                match fs::read_to_string(&path) {
+                    Ok(raw) => self.insert(PromptTemplate::parse(name, &locale, &raw)),
+                    Err(e) => eprintln!("Failed to read prompt file: {} ({})", path.display(), e),
+                }
+            }
+        }
+        self
+    }
+
+    pub fn insert(&mut self, template: PromptTemplate) {
+        self.templates.insert((template.locale.clone(), template.name.clone()), template);
+    }
+
+    /// locale になければ日本語の既定を返す
+    pub fn get(&self, name: &str, locale: &str) -> Option<&PromptTemplate> {
+        self.templates
+            .get(&(locale.to_string(), name.to_string()))
+            .or_else(|| self.templates.get(&(DEFAULT_LOCALE.to_string(), name.to_string())))
+    }
+}
+
+/// 現在のロケール（PROMPT_LOCALE、既定: ja）
+pub fn locale() -> String {
+    env::var("PROMPT_LOCALE").unwrap_or_else(|_| DEFAULT_LOCALE.to_string())
+}
+
+/// アプリ全体で使うレジストリ（PROMPT_DIR があれば上書きを読む）
+pub fn registry() -> &'static PromptRegistry {
+    static REGISTRY: OnceLock<PromptRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let registry = PromptRegistry::embedded();
+        match env::var("PROMPT_DIR") {
+            Ok(dir) => registry.load(Path::new(&dir)),
+            Err(_) => registry,
+        }
+    })
+}
+
+/// 現在のロケールのテンプレートを取り出す。組み込みの名前なら必ず見つかる
+pub fn template(name: &str) -> &'static PromptTemplate {
+    registry()
+        .get(name, &locale())
+        .unwrap_or_else(|| panic!("unknown prompt template: {}", name))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_templates_have_both_locales() {
+        let registry = PromptRegistry::embedded();
+        for name in [CLASSIFY_INPUT, CLASSIFY_TASK_ACTION, EXTRACT_TASK, EXTRACT_USER_FACTS, SUMMARIZE_HISTORY, ASSESS_MOOD] {
+            let ja = registry.get(name, "ja").unwrap();
+            let en = registry.get(name, "en").unwrap();
+            assert_eq!(ja.variables(), en.variables(), "{}", name);
+            assert!(!ja.variables().is_empty(), "{}", name);
+        }
+        assert_eq!(registry.get(CLASSIFY_INPUT, "fr").unwrap().locale, "ja");
+    }
+
+    #[test]
+    fn test_parse_and_render() {
+        let template = PromptTemplate::parse("greet", "ja", "# version: 3\n{name}さん、{name}さん。{place}へようこそ\n");
+        assert_eq!(template.id(), "greet@3/ja");
+        assert_eq!(template.variables(), vec!["name", "place"]);
+        assert_eq!(template.render(&[("name", "田中"), ("place", "東京")]), "田中さん、田中さん。東京へようこそ");
+
+        let unversioned = PromptTemplate::parse("plain", "en", "Hello {who}");
+        assert_eq!(unversioned.version, 1);
+        assert_eq!(unversioned.text, "Hello {who}");
+    }
+
+    #[test]
+    fn test_user_directory_overrides_embedded() {
+        let dir = std::env::temp_dir().join(format!("prompts_test_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(dir.join("ja")).unwrap();
+        fs::write(dir.join("ja").join("extract_task.txt"), "# version: 2\nタスク名だけ: {input}").unwrap();
+
+        let registry = PromptRegistry::embedded().load(&dir);
+        let template = registry.get(EXTRACT_TASK, "ja").unwrap();
+        assert_eq!(template.id(), "extract_task@2/ja");
+        assert_eq!(template.render(&[("input", "牛乳")]), "タスク名だけ: 牛乳");
+        assert_eq!(registry.get(CLASSIFY_INPUT, "ja").unwrap().version, 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::anthropic::{self, AnthropicBackend};
+use crate::backend::{LlmBackend, OpenAiBackend};
+use crate::gemini::{self, GeminiBackend};
+use crate::llm;
+use crate::transport::HttpClient;
+use crate::usage;
+
+use std::env;
+
+pub const DEFAULT_AZURE_API_VERSION: &str = "2024-06-01";
+
+/// チャットAPIの提供元（LLM_PROVIDER）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    OpenAi,
+    Azure,
+    Anthropic,
+    Gemini,
+}
+
+impl Provider {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "openai" => Some(Provider::OpenAi),
+            "azure" | "azure-openai" => Some(Provider::Azure),
+            "anthropic" | "claude" => Some(Provider::Anthropic),
+            "gemini" | "google" => Some(Provider::Gemini),
+            _ => None,
+        }
+    }
+
+    /// API キーを読む環境変数
+    pub fn key_var(&self) -> &'static str {
+        match self {
+            Provider::OpenAi => "OPENAI_API_KEY",
+            Provider::Azure => "AZURE_OPENAI_API_KEY",
+            Provider::Anthropic => "ANTHROPIC_API_KEY",
+            Provider::Gemini => "GEMINI_API_KEY",
+        }
+    }
+
+    /// LLM_MODEL が無いときのモデル（None なら呼び出し側の既定のまま）
+    pub fn default_model(&self) -> Option<&'static str> {
+        match self {
+            Provider::OpenAi | Provider::Azure => None,
+            Provider::Anthropic => Some("claude-3-5-haiku-latest"),
+            Provider::Gemini => Some("gemini-1.5-flash"),
+        }
+    }
+
+    /// 予算超過時に切り替える安いモデル
+    pub fn cheap_model(&self) -> &'static str {
+        match self {
+            Provider::OpenAi | Provider::Azure => usage::CHEAP_MODEL,
+            Provider::Anthropic => "claude-3-haiku-20240307",
+            Provider::Gemini => "gemini-1.5-flash-8b",
+        }
+    }
+}
+
+/// 提供元ごとの接続設定
+#[derive(Debug, Clone)]
+pub struct ProviderConfig {
+    pub provider: Provider,
+    pub api_key: String,
+    pub base_url: String,
+    pub model: Option<String>,
+    pub stream: bool,
+    pub azure_deployment: String,
+    pub azure_api_version: String,
+}
+
+impl ProviderConfig {
+    pub fn new(provider: Provider, api_key: &str, base_url: &str) -> Self {
+        Self {
+            provider,
+            api_key: api_key.to_string(),
+            base_url: base_url.to_string(),
+            model: provider.default_model().map(str::to_string),
+            stream: false,
+            azure_deployment: String::new(),
+            azure_api_version: DEFAULT_AZURE_API_VERSION.to_string(),
+        }
+    }
+
+    /// LLM_PROVIDER（既定: openai）と提供元ごとの環境変数から作る
+    /// - openai: OPENAI_API_KEY / OPENAI_BASE_URL
+    /// - azure: AZURE_OPENAI_API_KEY / AZURE_OPENAI_ENDPOINT / AZURE_OPENAI_DEPLOYMENT / AZURE_OPENAI_API_VERSION
+    /// - anthropic: ANTHROPIC_API_KEY / ANTHROPIC_BASE_URL
+    /// - gemini: GEMINI_API_KEY / GEMINI_BASE_URL
+    pub fn from_env() -> Result<Self, String> {
+        let provider = match env::var("LLM_PROVIDER") {
+            Ok(name) => Provider::from_name(&name).ok_or(format!("Unknown LLM_PROVIDER: {}", name))?,
+            Err(_) => Provider::OpenAi,
+        };
+        let var = |name: &str, default: &str| env::var(name).unwrap_or_else(|_| default.to_string());
+
+        let base_url = match provider {
+            Provider::OpenAi => var("OPENAI_BASE_URL", llm::DEFAULT_BASE_URL),
+            Provider::Azure => var("AZURE_OPENAI_ENDPOINT", ""),
+            Provider::Anthropic => var("ANTHROPIC_BASE_URL", anthropic::DEFAULT_BASE_URL),
+            Provider::Gemini => var("GEMINI_BASE_URL", gemini::DEFAULT_BASE_URL),
+        };
+
+        let mut config = Self::new(provider, &var(provider.key_var(), ""), &base_url);
+        if let Ok(model) = env::var("LLM_MODEL") {
+            config.model = Some(model);
+        }
+        config.stream = env::var("LLM_STREAM").is_ok();
+        config.azure_deployment = var("AZURE_OPENAI_DEPLOYMENT", "");
+        config.azure_api_version = var("AZURE_OPENAI_API_VERSION", DEFAULT_AZURE_API_VERSION);
+
+        if provider == Provider::Azure && (config.base_url.is_empty() || config.azure_deployment.is_empty()) {
+            return Err("AZURE_OPENAI_ENDPOINT and AZURE_OPENAI_DEPLOYMENT must be set".to_string());
+        }
+        Ok(config)
+    }
+
+    pub fn backend(&self, http: HttpClient) -> Box<dyn LlmBackend> {
+        match self.provider {
+            Provider::OpenAi => Box::new(OpenAiBackend::with_http(http, &self.api_key, &self.base_url).streaming(self.stream)),
+            Provider::Azure => Box::new(
+                OpenAiBackend::azure(http, &self.api_key, &self.base_url, &self.azure_deployment, &self.azure_api_version)
+                    .streaming(self.stream),
+            ),
+            Provider::Anthropic => Box::new(AnthropicBackend::new(http, &self.api_key, &self.base_url).streaming(self.stream)),
+            Provider::Gemini => Box::new(GeminiBackend::new(http, &self.api_key, &self.base_url).streaming(self.stream)),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_names() {
+        assert_eq!(Provider::from_name("Claude"), Some(Provider::Anthropic));
+        assert_eq!(Provider::from_name("azure-openai"), Some(Provider::Azure));
+        assert_eq!(Provider::from_name("watson"), None);
+        assert_eq!(ProviderConfig::new(Provider::OpenAi, "k", "u").model, None);
+        assert_eq!(ProviderConfig::new(Provider::Gemini, "k", "u").model.as_deref(), Some("gemini-1.5-flash"));
+    }
+}
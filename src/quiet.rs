@@ -0,0 +1,214 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use regex::Regex;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+pub const DEFAULT_QUIET_HOURS_FILE: &str = "quiet_hours.json";
+
+// 「静かにして」だけのときの長さ
+pub const DEFAULT_QUIET_MINUTES: u64 = 30;
+
+// 「N分静かにして」で静かにできるいちばん長い時間（これより長い指定はここで止める）
+pub const MAX_QUIET: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// 静かにしている間の通知の扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertMode {
+    #[default]
+    Defer,  // 終わるまで溜めておき、あとで読む
+    Text,   // 声を出さず、文字だけ出す
+}
+
+/// 「22:00-07:00」の形の時間帯。終わりが始まり以前なら日をまたぐ（同じなら一日中）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TimeRange {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TimeRange {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl TryFrom<String> for TimeRange {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (start, end) = value.split_once('-').ok_or_else(|| format!("invalid time range: {}", value))?;
+        let parse = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M").map_err(|_| format!("invalid time: {}", t));
+        Ok(Self { start: parse(start)?, end: parse(end)? })
+    }
+}
+
+/// 会議などの枠の日付（その日だけか、毎週その曜日か）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeetingDay {
+    Date(NaiveDate),
+    Weekly(Weekday),
+}
+
+/// 「2026-10-20 14:00-15:00」「月 10:00-10:30」の形の枠
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct MeetingBlock {
+    pub day: MeetingDay,
+    pub range: TimeRange,
+}
+
+impl MeetingBlock {
+    pub fn contains(&self, at: NaiveDateTime) -> bool {
+        let on_day = match self.day {
+            MeetingDay::Date(date) => at.date() == date,
+            MeetingDay::Weekly(weekday) => at.weekday() == weekday,
+        };
+        on_day && self.range.contains(at.time())
+    }
+}
+
+impl TryFrom<String> for MeetingBlock {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (day, range) = value.trim().split_once(' ').ok_or_else(|| format!("invalid meeting block: {}", value))?;
+        let day = match day.trim_end_matches("曜日").trim_end_matches('曜') {
+            "月" => MeetingDay::Weekly(Weekday::Mon),
+            "火" => MeetingDay::Weekly(Weekday::Tue),
+            "水" => MeetingDay::Weekly(Weekday::Wed),
+            "木" => MeetingDay::Weekly(Weekday::Thu),
+            "金" => MeetingDay::Weekly(Weekday::Fri),
+            "土" => MeetingDay::Weekly(Weekday::Sat),
+            "日" => MeetingDay::Weekly(Weekday::Sun),
+            date => MeetingDay::Date(
+                NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| format!("invalid meeting day: {}", date))?,
+            ),
+        };
+        Ok(Self { day, range: TimeRange::try_from(range.to_string())? })
+    }
+}
+
+/// 声を控える時間の設定（QUIET_HOURS_FILE、既定: quiet_hours.json）
+/// 例: {"weekday": ["22:00-07:00"], "weekend": ["23:00-09:00"], "meetings": ["月 10:00-10:30", "2026-10-20 14:00-15:00"], "alerts": "text"}
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct QuietSchedule {
+    pub weekday: Vec<TimeRange>,
+    pub weekend: Vec<TimeRange>,
+    pub meetings: Vec<MeetingBlock>,
+    pub alerts: AlertMode,
+}
+
+impl QuietSchedule {
+    /// ファイルが無い・読めないなら、いつでも喋る
+    pub fn load(path: &Path) -> Self {
+        let Ok(raw) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&raw).unwrap_or_else(|e| {
+            eprintln!("Failed to parse quiet hours file: {} ({})", path.display(), e);
+            Self::default()
+        })
+    }
+
+    pub fn from_env() -> Self {
+        let path = env::var("QUIET_HOURS_FILE").unwrap_or_else(|_| DEFAULT_QUIET_HOURS_FILE.to_string());
+        Self::load(Path::new(&path))
+    }
+
+    /// その時刻は静かにする時間か（平日・週末はその時刻の日付で決める）
+    pub fn is_quiet(&self, at: NaiveDateTime) -> bool {
+        let hours = match at.weekday() {
+            Weekday::Sat | Weekday::Sun => &self.weekend,
+            _ => &self.weekday,
+        };
+        hours.iter().any(|range| range.contains(at.time())) || self.meetings.iter().any(|block| block.contains(at))
+    }
+}
+
+fn quiet_request() -> &'static Regex {
+    static QUIET: OnceLock<Regex> = OnceLock::new();
+    // 発話全体がお願いのときだけ（「〜は黙っておく」「静かにしてくれない」などの文の一部では反応しない）
+    QUIET.get_or_init(|| {
+        Regex::new(
+            r"^\s*(?:(?:ちょっと|少し|しばらく)\s*)?(?:([0-9０-９]+)\s*(分|時間)(?:くらい|ほど|ぐらい)?\s*)?(?:静かにして|黙って)(?:て|ください|くれる|くれますか|ほしい)?[\s。、！!？?]*$",
+        )
+        .unwrap()
+    })
+}
+
+/// 「30分静かにして」「1時間黙って」を読み取る（長さが無ければ30分、長すぎれば24時間）
+pub fn detect_quiet_request(input: &str) -> Option<Duration> {
+    let caps = quiet_request().captures(input)?;
+    let Some(amount) = caps.get(1) else {
+        return Some(Duration::from_secs(DEFAULT_QUIET_MINUTES * 60));
+    };
+    // 全角の数字も受け付ける。桁あふれしたら上限にする
+    let amount = amount
+        .as_str()
+        .chars()
+        .filter_map(|c| c.to_digit(10).or_else(|| ('０'..='９').contains(&c).then(|| c as u32 - '０' as u32)))
+        .try_fold(0u64, |n, d| n.checked_mul(10)?.checked_add(d as u64));
+    let minutes = amount.and_then(|amount| if &caps[2] == "時間" { amount.checked_mul(60) } else { Some(amount) });
+    let duration = minutes.and_then(|m| m.checked_mul(60)).map_or(MAX_QUIET, Duration::from_secs);
+    Some(duration.min(MAX_QUIET))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_weekday_weekend_and_meeting_blocks() {
+        let schedule: QuietSchedule = serde_json::from_str(
+            r#"{"weekday": ["22:00-07:00"], "weekend": ["23:00-09:00"], "meetings": ["月 10:00-10:30", "2026-10-21 14:00-15:00"]}"#,
+        )
+        .unwrap();
+        assert_eq!(schedule.alerts, AlertMode::Defer);
+
+        // 2026-10-19 は月曜、10-24 は土曜
+        assert!(schedule.is_quiet(at("2026-10-19 23:30")));
+        assert!(schedule.is_quiet(at("2026-10-20 06:59")));
+        assert!(!schedule.is_quiet(at("2026-10-20 07:00")));
+        assert!(!schedule.is_quiet(at("2026-10-24 22:30")));
+        assert!(schedule.is_quiet(at("2026-10-24 08:00")));
+        assert!(schedule.is_quiet(at("2026-10-19 10:15")));
+        assert!(!schedule.is_quiet(at("2026-10-20 10:15")));
+        assert!(schedule.is_quiet(at("2026-10-21 14:00")));
+        assert!(!schedule.is_quiet(at("2026-10-28 14:00")));
+
+        assert!(serde_json::from_str::<QuietSchedule>(r#"{"weekday": ["22時から"]}"#).is_err());
+    }
+
+    #[test]
+    fn test_detect_quiet_request() {
+        assert_eq!(detect_quiet_request("30分静かにして"), Some(Duration::from_secs(30 * 60)));
+        assert_eq!(detect_quiet_request("0分静かにして"), Some(Duration::ZERO));
+        assert_eq!(detect_quiet_request("１時間ほど黙ってて"), Some(Duration::from_secs(3600)));
+        assert_eq!(detect_quiet_request("ちょっと静かにしてくれる？"), Some(Duration::from_secs(DEFAULT_QUIET_MINUTES * 60)));
+        assert_eq!(detect_quiet_request("静かな場所に行きたい"), None);
+
+        // 文の一部に出てきただけなら反応しない
+        assert_eq!(detect_quiet_request("「会議中は黙っておく」をタスクに追加"), None);
+        assert_eq!(detect_quiet_request("子供が静かにしてくれない"), None);
+
+        // 長すぎる・桁あふれする長さは24時間にする
+        assert_eq!(detect_quiet_request("48時間静かにして"), Some(MAX_QUIET));
+        assert_eq!(detect_quiet_request("99999999999999999999時間静かにして"), Some(MAX_QUIET));
+        assert_eq!(detect_quiet_request("３０７４４５７３４５６１８１８０分黙って"), Some(MAX_QUIET));
+    }
+}
@@ -0,0 +1,394 @@
+use regex::{Captures, Regex};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+pub const DEFAULT_READING_FILE: &str = "readings.json";
+
+// 強調したところは少し高く、ゆっくり
+const EMPHASIS_PITCH: f64 = 0.15;
+const EMPHASIS_LENGTH: f64 = 1.15;
+
+// 拡張子など、これ以下の長さの小文字はアルファベットで読む
+const SPELL_MAX: usize = 3;
+
+// 読みの規則（normalize など）を変えたら上げる。音声キャッシュのキーに入る
+pub const RULES_VERSION: u32 = 1;
+
+// <break> の間はこれより長くしない
+const MAX_PAUSE: Duration = Duration::from_secs(5);
+
+const ALPHABET: [&str; 26] = [
+    "エー", "ビー", "シー", "ディー", "イー", "エフ", "ジー", "エイチ", "アイ", "ジェー", "ケー", "エル", "エム",
+    "エヌ", "オー", "ピー", "キュー", "アール", "エス", "ティー", "ユー", "ブイ", "ダブリュー", "エックス", "ワイ", "ゼット",
+];
+
+/// 表記→読みの辞書（READING_FILE、既定: readings.json。{"main.rs": "メインドットアールエス"} の形）
+/// VoiceVox にはユーザー辞書として登録し、ほかのエンジンでは読み上げる前に置き換える
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReadingDictionary {
+    entries: Vec<(String, String)>, // 長い表記から順
+}
+
+impl ReadingDictionary {
+    pub fn new(pairs: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut entries: Vec<(String, String)> = pairs.into_iter().filter(|(s, r)| !s.is_empty() && !r.is_empty()).collect();
+        entries.sort_by(|a, b| b.0.chars().count().cmp(&a.0.chars().count()).then_with(|| a.0.cmp(&b.0)));
+        Self { entries }
+    }
+
+    pub fn load(path: &Path) -> Self {
+        let Ok(raw) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+        match serde_json::from_str::<BTreeMap<String, String>>(&raw) {
+            Ok(pairs) => Self::new(pairs),
+            Err(e) => {
+                eprintln!("Failed to parse reading file: {} ({})", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let path = env::var("READING_FILE").unwrap_or_else(|_| DEFAULT_READING_FILE.to_string());
+        Self::load(Path::new(&path))
+    }
+
+    pub fn entries(&self) -> &[(String, String)] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 辞書の中身と読みの規則の版の指紋。どちらかが変われば同じ文でも別の音になる
+    pub fn fingerprint(&self) -> String {
+        crate::cache::fingerprint(&format!("{}|{:?}", RULES_VERSION, self.entries))
+    }
+
+    /// 表記を読みに置き換える
+    pub fn apply(&self, text: &str) -> String {
+        self.split(text)
+            .into_iter()
+            .map(|(part, reading)| reading.unwrap_or(part))
+            .collect()
+    }
+
+    // 辞書にある表記とそれ以外に分ける（表記なら読みも返す）
+    fn split<'a>(&'a self, text: &'a str) -> Vec<(&'a str, Option<&'a str>)> {
+        let mut parts = Vec::new();
+        let mut plain_start = 0;
+        let mut i = 0;
+        while i < text.len() {
+            let rest = &text[i..];
+            if let Some((surface, reading)) = self.entries.iter().find(|(s, _)| rest.starts_with(s.as_str())) {
+                if plain_start < i {
+                    parts.push((&text[plain_start..i], None));
+                }
+                parts.push((&text[i..i + surface.len()], Some(reading.as_str())));
+                i += surface.len();
+                plain_start = i;
+            } else {
+                i += rest.chars().next().map_or(1, char::len_utf8);
+            }
+        }
+        if plain_start < text.len() {
+            parts.push((&text[plain_start..], None));
+        }
+        parts
+    }
+}
+
+/// 設定の辞書（起動後に書き換えても読み直さない）
+pub fn dictionary() -> &'static ReadingDictionary {
+    static DICTIONARY: OnceLock<ReadingDictionary> = OnceLock::new();
+    DICTIONARY.get_or_init(ReadingDictionary::from_env)
+}
+
+/// 読み上げの単位。マークアップを解いたもの
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Text { text: String, emphasis: bool },
+    Pause(Duration),
+}
+
+fn markup() -> &'static Regex {
+    static MARKUP: OnceLock<Regex> = OnceLock::new();
+    MARKUP.get_or_init(|| {
+        Regex::new(r#"<break\s+time="(\d+(?:\.\d+)?)(ms|s)"\s*/>|<(/?)emphasis>|<sub\s+alias="([^"]*)">(.*?)</sub>"#).unwrap()
+    })
+}
+
+/// SSML 風のマークアップを解く
+/// - <break time="500ms"/>（または "1.5s"）: 間を空ける（長すぎるものは5秒にする）
+/// - <emphasis>…</emphasis>: 強調する
+/// - <sub alias="よみ">表記</sub>: 表記を読みで読む
+///
+/// 知らないタグはそのまま文字として残す
+pub fn parse_markup(text: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut emphasis = false;
+    let mut current = String::new();
+    let flush = |segments: &mut Vec<Segment>, current: &mut String, emphasis: bool| {
+        if !current.is_empty() {
+            segments.push(Segment::Text { text: std::mem::take(current), emphasis });
+        }
+    };
+
+    let mut last = 0;
+    for caps in markup().captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        current.push_str(&text[last..whole.start()]);
+        last = whole.end();
+
+        if let Some(amount) = caps.get(1) {
+            let amount: f64 = amount.as_str().parse().unwrap_or(0.0);
+            let seconds = if &caps[2] == "ms" { amount / 1000.0 } else { amount };
+            flush(&mut segments, &mut current, emphasis);
+            let pause = Duration::try_from_secs_f64(seconds).unwrap_or(MAX_PAUSE).min(MAX_PAUSE);
+            segments.push(Segment::Pause(pause));
+        } else if let Some(close) = caps.get(3) {
+            flush(&mut segments, &mut current, emphasis);
+            emphasis = close.as_str().is_empty();
+        } else {
+            current.push_str(&caps[4]);
+        }
+    }
+    current.push_str(&text[last..]);
+    flush(&mut segments, &mut current, emphasis);
+    segments
+}
+
+/// 画面に出す形（タグを外し、読みの上書きは元の表記に戻す）
+pub fn strip_markup(text: &str) -> String {
+    markup()
+        .replace_all(text, |caps: &Captures| caps.get(5).map_or(String::new(), |m| m.as_str().to_string()))
+        .to_string()
+}
+
+struct Rules {
+    url: Regex,
+    full_date: Regex,
+    short_date: Regex,
+    time: Regex,
+    identifier: Regex,
+    camel: Regex,
+}
+
+fn rules() -> &'static Rules {
+    static RULES: OnceLock<Rules> = OnceLock::new();
+    RULES.get_or_init(|| Rules {
+        url: Regex::new(r"https?://[^\s　、。，」）)]+").unwrap(),
+        full_date: Regex::new(r"(\d{4})[-/](\d{1,2})[-/](\d{1,2})").unwrap(),
+        // 前後に数字や / が続くもの（分数・パス）は除く
+        short_date: Regex::new(r"(^|[^\d/])(\d{1,2})/(\d{1,2})($|[^\d/])").unwrap(),
+        time: Regex::new(r"(^|[^\d:])(\d{1,2}):(\d{2})($|[^\d:])").unwrap(),
+        // main.rs / tasks::load / load_tasks / loadTasks
+        identifier: Regex::new(
+            r"[A-Za-z_][A-Za-z0-9_]*(?:(?:\.|::)[A-Za-z_][A-Za-z0-9_]*)+|[A-Za-z][A-Za-z0-9]*_[A-Za-z0-9_]+|[a-z]+[A-Z][A-Za-z0-9]*",
+        )
+        .unwrap(),
+        camel: Regex::new(r"([a-z0-9])([A-Z])").unwrap(),
+    })
+}
+
+fn spell(word: &str) -> String {
+    word.chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            c @ 'a'..='z' => ALPHABET[(c as u8 - b'a') as usize].to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+// 識別子を区切って読みやすくする（. は「ドット」、:: は「の」、_ と大文字の切れ目は区切り）
+fn read_identifier(identifier: &str) -> String {
+    let words = |part: &str| {
+        let part = rules().camel.replace_all(part, "$1 $2");
+        part.split('_').filter(|w| !w.is_empty()).collect::<Vec<_>>().join(" ")
+    };
+
+    let mut spoken = Vec::new();
+    for (i, path) in identifier.split("::").enumerate() {
+        if i > 0 {
+            spoken.push("の".to_string());
+        }
+        for (j, part) in path.split('.').enumerate() {
+            if j > 0 {
+                spoken.push("ドット".to_string());
+                // 拡張子のような短い語はアルファベットで読む
+                if part.len() <= SPELL_MAX && part.chars().all(|c| c.is_ascii_lowercase()) {
+                    spoken.push(spell(part));
+                    continue;
+                }
+            }
+            spoken.push(words(part));
+        }
+    }
+    spoken.join(" ")
+}
+
+fn number(text: &str) -> u32 {
+    text.parse().unwrap_or(0)
+}
+
+// 辞書の表記を含まない部分の読み替え
+fn normalize_plain(text: &str) -> String {
+    let rules = rules();
+    let text = rules.url.replace_all(text, "リンク");
+    let text = rules.full_date.replace_all(&text, |c: &Captures| {
+        format!("{}年{}月{}日", number(&c[1]), number(&c[2]), number(&c[3]))
+    });
+    let text = rules.short_date.replace_all(&text, |c: &Captures| {
+        let (month, day) = (number(&c[2]), number(&c[3]));
+        if (1..=12).contains(&month) && (1..=31).contains(&day) {
+            format!("{}{}月{}日{}", &c[1], month, day, &c[4])
+        } else {
+            c[0].to_string()
+        }
+    });
+    let text = rules.time.replace_all(&text, |c: &Captures| {
+        let (hour, minute) = (number(&c[2]), number(&c[3]));
+        match (hour, minute) {
+            (0..=24, 0) => format!("{}{}時{}", &c[1], hour, &c[4]),
+            (0..=24, 1..=59) => format!("{}{}時{}分{}", &c[1], hour, minute, &c[4]),
+            _ => c[0].to_string(),
+        }
+    });
+    rules.identifier.replace_all(&text, |c: &Captures| read_identifier(&c[0])).to_string()
+}
+
+/// 日付・時刻・URL・コードの識別子を読み上げやすい形にする（辞書にある表記はそのまま残す）
+pub fn normalize(text: &str, dictionary: &ReadingDictionary) -> String {
+    dictionary
+        .split(text)
+        .into_iter()
+        .map(|(part, reading)| if reading.is_some() { part.to_string() } else { normalize_plain(part) })
+        .collect()
+}
+
+/// マークアップを解いて、文の部分を読み上げやすくする（合成の前に通す）
+pub fn prepare(text: &str, dictionary: &ReadingDictionary) -> Vec<Segment> {
+    parse_markup(text)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text { text, emphasis } => Segment::Text { text: normalize(&text, dictionary), emphasis },
+            pause => pause,
+        })
+        .collect()
+}
+
+/// マークアップを扱えないエンジン向けの1本の文（読みは辞書で置き換え、間は読点にする）
+pub fn plain_text(text: &str, dictionary: &ReadingDictionary) -> String {
+    prepare(text, dictionary)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text { text, .. } => dictionary.apply(&text),
+            Segment::Pause(_) => "、".to_string(),
+        })
+        .collect()
+}
+
+/// audio_query のアクセント句を強調する（声のある拍を少し高く、母音を少し長く）
+pub fn emphasize(accent_phrases: &mut [serde_json::Value]) {
+    for phrase in accent_phrases {
+        let Some(moras) = phrase["moras"].as_array_mut() else {
+            continue;
+        };
+        for mora in moras {
+            if let Some(pitch) = mora["pitch"].as_f64().filter(|p| *p > 0.0) {
+                mora["pitch"] = serde_json::json!(pitch + EMPHASIS_PITCH);
+            }
+            if let Some(length) = mora["vowel_length"].as_f64() {
+                mora["vowel_length"] = serde_json::json!(length * EMPHASIS_LENGTH);
+            }
+        }
+    }
+}
+
+/// アクセント句のあとに置く無音（VoiceVox の pause_mora）
+pub fn pause_mora(pause: Duration) -> serde_json::Value {
+    serde_json::json!({
+        "text": "、",
+        "consonant": null,
+        "consonant_length": null,
+        "vowel": "pau",
+        "vowel_length": pause.as_secs_f64(),
+        "pitch": 0.0
+    })
+}
+
+/// 平仮名を片仮名にする（VoiceVox の辞書の読みは片仮名だけ）
+pub fn to_katakana(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'ぁ'..='ゖ' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// 半角の英数記号を全角にする（VoiceVox の辞書は表記を全角で持つ）
+pub fn to_fullwidth(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '!'..='~' => char::from_u32(c as u32 + 0xFEE0).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalizes_dates_times_urls_and_identifiers() {
+        let dictionary = ReadingDictionary::default();
+        assert_eq!(normalize("2025-06-01 9:00 と 14:30 に", &dictionary), "2025年6月1日 9時 と 14時30分 に");
+        assert_eq!(normalize("締め切りは6/15です。1/2/3 は別", &dictionary), "締め切りは6月15日です。1/2/3 は別");
+        assert_eq!(normalize("詳しくは https://example.com/a?b=1 を", &dictionary), "詳しくは リンク を");
+        assert_eq!(normalize("main.rsを更新", &dictionary), "main ドット アールエスを更新");
+        assert_eq!(normalize("tasks::load_tasks と loadTasks", &dictionary), "tasks の load tasks と load Tasks");
+    }
+
+    #[test]
+    fn test_dictionary_surfaces_are_left_for_the_engine() {
+        let dictionary = ReadingDictionary::new([
+            ("main.rs".to_string(), "めいんあーるえす".to_string()),
+            ("main".to_string(), "メイン".to_string()),
+        ]);
+        assert_eq!(normalize("main.rsを更新", &dictionary), "main.rsを更新");
+        assert_eq!(dictionary.apply("main.rs と main"), "めいんあーるえす と メイン");
+        assert_eq!(to_katakana("めいんあーるえす"), "メインアールエス");
+        assert_eq!(to_fullwidth("main.rs"), "ｍａｉｎ．ｒｓ");
+    }
+
+    #[test]
+    fn test_markup() {
+        let segments = parse_markup(r#"では<break time="500ms"/><emphasis>今すぐ</emphasis><sub alias="ぎっと">git</sub>を<b>見て</b>"#);
+        assert_eq!(
+            segments,
+            vec![
+                Segment::Text { text: "では".into(), emphasis: false },
+                Segment::Pause(Duration::from_millis(500)),
+                Segment::Text { text: "今すぐ".into(), emphasis: true },
+                Segment::Text { text: "ぎっとを<b>見て</b>".into(), emphasis: false },
+            ]
+        );
+        assert_eq!(strip_markup(r#"では<break time="1s"/><emphasis>今すぐ</emphasis><sub alias="ぎっと">git</sub>"#), "では今すぐgit");
+        assert_eq!(plain_text(r#"では<break time="1s"/>main.rs"#, &ReadingDictionary::default()), "では、main ドット アールエス");
+
+        // 長すぎる間・f64 に収まらない間でも落ちずに5秒にする
+        let huge = format!(r#"<break time="{}s"/>"#, "9".repeat(400));
+        assert_eq!(parse_markup(&huge), vec![Segment::Pause(MAX_PAUSE)]);
+        assert_eq!(parse_markup(r#"<break time="100000000000000000000s"/>"#), vec![Segment::Pause(MAX_PAUSE)]);
+        assert_eq!(parse_markup(r#"<break time="6s"/>"#), vec![Segment::Pause(MAX_PAUSE)]);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::models::{ChatRequest, ChatResponse};
+
+use regex::Regex;
+use std::env;
+use std::sync::OnceLock;
+
+// 全角の数字やハイフン（長音記号で打たれることも多い）も拾う
+const DIGIT: &str = "[0-9０-９]";
+const HYPHEN: &str = "[-‐‑–−－ー]";
+
+/// API に送る前に伏せる個人情報の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PiiKind {
+    Email,
+    MyNumber,
+    Phone,
+    PostalCode,
+    Name,
+}
+
+impl PiiKind {
+    fn tag(&self) -> &'static str {
+        match self {
+            PiiKind::Email => "EMAIL",
+            PiiKind::MyNumber => "ID",
+            PiiKind::Phone => "TEL",
+            PiiKind::PostalCode => "POSTAL",
+            PiiKind::Name => "NAME",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PiiKind::Email => "メールアドレス",
+            PiiKind::MyNumber => "マイナンバー",
+            PiiKind::Phone => "電話番号",
+            PiiKind::PostalCode => "郵便番号",
+            PiiKind::Name => "名前",
+        }
+    }
+}
+
+/// 伏せた値1つ分
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redacted {
+    pub kind: PiiKind,
+    pub placeholder: String,
+    pub original: String,
+}
+
+/// 1回の呼び出しで伏せた値の一覧。返ってきた文章を元に戻すのに使う
+#[derive(Debug, Default)]
+pub struct Redaction {
+    entries: Vec<Redacted>,
+}
+
+fn is_digit(c: char) -> bool {
+    c.is_ascii_digit() || ('０'..='９').contains(&c)
+}
+
+/// ログに出すための伏せ字（数字は下2桁、メールは先頭1文字とドメインだけ残す）
+fn mask(kind: PiiKind, original: &str) -> String {
+    match kind {
+        PiiKind::Email => {
+            let (local, domain) = original.split_once('@').unwrap_or((original, ""));
+            format!("{}***@{}", local.chars().next().unwrap_or('*'), domain)
+        }
+        PiiKind::Name => "*".repeat(original.chars().count()),
+        _ => {
+            let digits = original.chars().filter(|c| is_digit(*c)).count();
+            let mut seen = 0;
+            original
+                .chars()
+                .map(|c| {
+                    if !is_digit(c) {
+                        return c;
+                    }
+                    seen += 1;
+                    if seen + 2 > digits { c } else { '*' }
+                })
+                .collect()
+        }
+    }
+}
+
+impl Redaction {
+    pub fn entries(&self) -> &[Redacted] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 同じ値には同じプレースホルダーを使う（例: [TEL_1]）
+    fn placeholder_for(&mut self, kind: PiiKind, original: &str) -> String {
+        if let Some(e) = self.entries.iter().find(|e| e.kind == kind && e.original == original) {
+            return e.placeholder.clone();
+        }
+        let n = self.entries.iter().filter(|e| e.kind == kind).count() + 1;
+        let placeholder = format!("[{}_{}]", kind.tag(), n);
+        self.entries.push(Redacted { kind, placeholder: placeholder.clone(), original: original.to_string() });
+        placeholder
+    }
+
+    /// プレースホルダーを元の値に戻す（モデルが全角の括弧で返したときも戻す）
+    pub fn restore(&self, text: &str) -> String {
+        let mut restored = text.to_string();
+        for e in &self.entries {
+            let fullwidth = format!("［{}］", &e.placeholder[1..e.placeholder.len() - 1]);
+            restored = restored.replace(&e.placeholder, &e.original).replace(&fullwidth, &e.original);
+        }
+        restored
+    }
+
+    /// 返答の本文とツール呼び出しの引数を元に戻す
+    pub fn restore_response(&self, response: &mut ChatResponse) {
+        if self.is_empty() {
+            return;
+        }
+        for choice in &mut response.choices {
+            choice.message.content = self.restore(&choice.message.content);
+            for call in choice.message.tool_calls.iter_mut().flatten() {
+                // 引数は JSON 文字列なので、戻す値もエスケープしておく
+                let mut arguments = call.function.arguments.clone();
+                for e in &self.entries {
+                    let escaped = serde_json::to_string(&e.original).unwrap_or_default();
+                    arguments = arguments.replace(&e.placeholder, &escaped[1..escaped.len() - 1]);
+                }
+                call.function.arguments = arguments;
+            }
+        }
+    }
+
+    /// ログ用の一覧（値そのものは出さない）
+    pub fn describe(&self) -> String {
+        self.entries
+            .iter()
+            .map(|e| format!("{} {} → {}", e.kind.label(), mask(e.kind, &e.original), e.placeholder))
+            .collect::<Vec<_>>()
+            .join("、")
+    }
+}
+
+fn patterns() -> &'static [(PiiKind, Regex)] {
+    static PATTERNS: OnceLock<Vec<(PiiKind, Regex)>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        let sep = format!("(?:{}|\\s)", HYPHEN);
+        let build = |kind, pattern: String| (kind, Regex::new(&pattern).unwrap());
+        // 先に長いもの・形のはっきりしたものから伏せる
+        vec![
+            build(PiiKind::Email, r"[A-Za-z0-9._%+\-]+@[A-Za-z0-9\-]+(?:\.[A-Za-z0-9\-]+)*\.[A-Za-z]{2,}".to_string()),
+            build(PiiKind::MyNumber, format!("{d}{{4}}{s}?{d}{{4}}{s}?{d}{{4}}", d = DIGIT, s = sep)),
+            build(
+                PiiKind::Phone,
+                format!(
+                    "(?:\\+81{s}?|[0０]){d}{{1,4}}(?:{s}|[(（])?{d}{{1,4}}(?:{s}|[)）])?{d}{{3,4}}",
+                    d = DIGIT,
+                    s = sep
+                ),
+            ),
+            build(PiiKind::PostalCode, format!("〒\\s?{d}{{3}}{h}?{d}{{4}}|{d}{{3}}{h}{d}{{4}}", d = DIGIT, h = HYPHEN)),
+        ]
+    })
+}
+
+/// 電話番号として妥当な桁数か（国番号 +81 は先頭の 0 に読み替える）
+fn is_phone_length(matched: &str) -> bool {
+    let digits = matched.chars().filter(|c| is_digit(*c)).count();
+    let digits = if matched.starts_with("+81") { digits - 1 } else { digits };
+    (10..=11).contains(&digits)
+}
+
+/// 電話番号・メールアドレス・郵便番号・マイナンバーらしき番号・設定した名前を伏せる
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    names: Vec<String>,
+}
+
+impl Redactor {
+    pub fn new(names: Vec<String>) -> Self {
+        let mut names: Vec<String> = names.into_iter().map(|n| n.trim().to_string()).filter(|n| !n.is_empty()).collect();
+        // 「山田太郎」を「山田」より先に伏せる
+        names.sort_by_key(|n| std::cmp::Reverse(n.chars().count()));
+        Self { names }
+    }
+
+    /// REDACT_NAMES（カンマ区切り）の名前も伏せる。DISABLE_REDACTION があれば伏せない
+    pub fn from_env() -> Option<Self> {
+        if env::var("DISABLE_REDACTION").is_ok() {
+            return None;
+        }
+        let names = env::var("REDACT_NAMES").unwrap_or_default();
+        Some(Self::new(names.split([',', '、']).map(str::to_string).collect()))
+    }
+
+    pub fn redact(&self, text: &str, redaction: &mut Redaction) -> String {
+        let mut redacted = text.to_string();
+        for (kind, regex) in patterns() {
+            let mut out = String::with_capacity(redacted.len());
+            let mut last = 0;
+            for m in regex.find_iter(&redacted) {
+                // 数字の並びの途中から切り出したものは別物として扱う
+                let before = redacted[..m.start()].chars().next_back().is_some_and(is_digit);
+                let after = redacted[m.end()..].chars().next().is_some_and(is_digit);
+                if *kind != PiiKind::Email && (before || after) {
+                    continue;
+                }
+                if *kind == PiiKind::Phone && !is_phone_length(m.as_str()) {
+                    continue;
+                }
+                out.push_str(&redacted[last..m.start()]);
+                out.push_str(&redaction.placeholder_for(*kind, m.as_str()));
+                last = m.end();
+            }
+            out.push_str(&redacted[last..]);
+            redacted = out;
+        }
+
+        for name in &self.names {
+            if redacted.contains(name.as_str()) {
+                let placeholder = redaction.placeholder_for(PiiKind::Name, name);
+                redacted = redacted.replace(name.as_str(), &placeholder);
+            }
+        }
+        redacted
+    }
+
+    /// 送る前のリクエストを伏せたものにする（本文と、履歴に残ったツール呼び出しの引数）
+    pub fn redact_request(&self, request: &ChatRequest) -> (ChatRequest, Redaction) {
+        let mut redaction = Redaction::default();
+        let mut redacted = request.clone();
+        for message in &mut redacted.messages {
+            message.content = self.redact(&message.content, &mut redaction);
+            for call in message.tool_calls.iter_mut().flatten() {
+                call.function.arguments = self.redact(&call.function.arguments, &mut redaction);
+            }
+        }
+        (redacted, redaction)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacts_japanese_pii() {
+        let redactor = Redactor::new(vec!["山田".into(), "山田太郎".into()]);
+        let mut redaction = Redaction::default();
+        let text = "山田太郎さん（090-1234-5678、taro@example.co.jp）に〒150-0001の書類を送る。番号は1234 5678 9012";
+
+        let redacted = redactor.redact(text, &mut redaction);
+        assert_eq!(redacted, "[NAME_1]さん（[TEL_1]、[EMAIL_1]）に[POSTAL_1]の書類を送る。番号は[ID_1]");
+        assert_eq!(redaction.restore(&redacted), text);
+        assert_eq!(
+            redaction.describe(),
+            "メールアドレス t***@example.co.jp → [EMAIL_1]、マイナンバー **** **** **12 → [ID_1]、電話番号 ***-****-**78 → [TEL_1]、郵便番号 〒***-**01 → [POSTAL_1]、名前 **** → [NAME_1]"
+        );
+    }
+
+    #[test]
+    fn test_leaves_dates_and_times_alone() {
+        let redactor = Redactor::default();
+        let mut redaction = Redaction::default();
+        for text in ["期限: 2024-06-01", "2024年06月01日 12:30", "03-1234 と 5678", "合計 123456 円", "０３－１２３４－５６７８９０"] {
+            assert_eq!(redactor.redact(text, &mut redaction), text);
+        }
+        assert!(redaction.is_empty());
+
+        // 全角・国番号付き・固定電話
+        assert_eq!(redactor.redact("０３－１２３４－５６７８", &mut redaction), "[TEL_1]");
+        assert_eq!(redactor.redact("+81 90-1234-5678 か 03(1234)5678", &mut redaction), "[TEL_2] か [TEL_3]");
+    }
+}
@@ -1,154 +1,699 @@
-use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::env;
 use std::time::{Duration, Instant};
-use tokio::sync::{mpsc, Mutex};
+use chrono::Local;
+use tokio::sync::{broadcast, mpsc, oneshot, watch, Mutex};
 use std::sync::Arc;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use crate::fallback::TextOnlyEngine;
+use crate::persona;
+use crate::playback::{PlaybackEnd, Player};
+use crate::quiet::{self, AlertMode, QuietSchedule};
+use crate::tts::{self, TtsEngine, VoiceConfig, VoiceResolver};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SpeechKind {
     User,       // ユーザーへの応答（最優先）
     Alert,      // 期限通知など（次点）
     Monologue,  // 独り言（最下位）
 }
 
+// 通知をまとめて読むときの前置き（{count} は件数）
+const COALESCED_ALERTS: &str = "お知らせが{count}件あります。";
+
+// 購読者が読み遅れたら古いイベントから捨てる
+const EVENT_CAPACITY: usize = 256;
+
+// 静かにする時間で通知を溜めているとき、終わったかを見直す間隔
+const QUIET_RECHECK: Duration = Duration::from_secs(30);
+
 #[derive(Debug, Clone)]
 pub struct SpeechRequest {
     pub kind: SpeechKind,
     pub text: String,
+    pub ttl: Option<Duration>,  // None なら種類ごとの既定（SpeechPolicy）
+    pub at: Instant,            // 頼んだ時刻（期限はここから数える）
+}
+
+/// 発話を捨てた理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DropReason {
+    Expired,     // 喋る前に期限が切れた
+    Duplicate,   // 少し前に同じ文を頼まれている
+    Overflow,    // キューが溢れたので古いものから捨てた
+    Suppressed,  // 独り言のクールダウン・ユーザー操作直後・控えめモード
+    Quiet,       // 静かにする時間の独り言
+    Interrupted, // 独り言の途中でユーザーへの応答が割り込んだ・外から止められた
+    Failed,      // 合成・再生に失敗した
+    Cancelled,   // 終了時に取り消した
+}
+
+/// キューで起きたこと（subscribe で受け取る）
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpeechEvent {
+    Enqueued { kind: SpeechKind, text: String },
+    Started { kind: SpeechKind, text: String },
+    Finished { kind: SpeechKind, text: String },
+    Dropped { kind: SpeechKind, text: String, reason: DropReason },
+}
+
+/// 終了のしかた
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownMode {
+    Finish,  // 溜まっている分を喋り終えてから止める
+    Cancel,  // 喋っている途中でも止め、残りは捨てる
+}
+
+/// キューの捌き方
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeechPolicy {
+    pub user_ttl: Option<Duration>,
+    pub alert_ttl: Option<Duration>,
+    pub monologue_ttl: Option<Duration>,
+    /// この時間内に頼まれた同じ文は読まない（ユーザーへの応答は除く）
+    pub dedup_window: Duration,
+    /// 溜まった通知を1回の発話にまとめる
+    pub coalesce_alerts: bool,
+    pub max_user: usize,
+    pub max_alert: usize,
+    pub max_monologue: usize,
+}
+
+impl Default for SpeechPolicy {
+    fn default() -> Self {
+        Self {
+            user_ttl: None,
+            alert_ttl: Some(Duration::from_secs(120)),
+            monologue_ttl: Some(Duration::from_secs(30)),
+            dedup_window: Duration::from_secs(60),
+            coalesce_alerts: true,
+            max_user: 16,
+            max_alert: 8,
+            max_monologue: 2,
+        }
+    }
+}
+
+impl SpeechPolicy {
+    /// SPEECH_ALERT_TTL_SECS（0 で無期限）/ SPEECH_DEDUP_SECS / DISABLE_ALERT_COALESCING で既定を変える
+    pub fn from_env() -> Self {
+        let secs = |name: &str| env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        let mut policy = Self::default();
+        if let Some(ttl) = secs("SPEECH_ALERT_TTL_SECS") {
+            policy.alert_ttl = (ttl > 0).then(|| Duration::from_secs(ttl));
+        }
+        if let Some(window) = secs("SPEECH_DEDUP_SECS") {
+            policy.dedup_window = Duration::from_secs(window);
+        }
+        policy.coalesce_alerts = env::var("DISABLE_ALERT_COALESCING").is_err();
+        policy
+    }
+
+    pub fn ttl(&self, kind: SpeechKind) -> Option<Duration> {
+        match kind {
+            SpeechKind::User => self.user_ttl,
+            SpeechKind::Alert => self.alert_ttl,
+            SpeechKind::Monologue => self.monologue_ttl,
+        }
+    }
+
+    pub fn max_len(&self, kind: SpeechKind) -> usize {
+        match kind {
+            SpeechKind::User => self.max_user,
+            SpeechKind::Alert => self.max_alert,
+            SpeechKind::Monologue => self.max_monologue,
+        }
+    }
+}
+
+/// 種類ごとの件数（頼まれた数で数える。まとめた通知は元の件数、started だけは喋り始めた回数）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KindCounts {
+    pub enqueued: u64,
+    pub started: u64,
+    pub finished: u64,
+    pub dropped: u64,
+}
+
+/// 捨てた・まとめた発話の数
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpeechStats {
+    pub dropped: HashMap<(SpeechKind, DropReason), u64>,
+    /// まとめて読んだ通知の数
+    pub coalesced: u64,
+    pub kinds: HashMap<SpeechKind, KindCounts>,
+}
+
+impl SpeechStats {
+    pub fn counts(&self, kind: SpeechKind) -> KindCounts {
+        self.kinds.get(&kind).copied().unwrap_or_default()
+    }
+
+    pub fn dropped(&self, kind: SpeechKind, reason: DropReason) -> u64 {
+        self.dropped.get(&(kind, reason)).copied().unwrap_or(0)
+    }
+
+    pub fn total_dropped(&self) -> u64 {
+        self.dropped.values().sum()
+    }
 }
 
 #[derive(Clone)]
 pub struct SpeechQueue {
-    tx: mpsc::Sender<SpeechRequest>,
+    tx: mpsc::Sender<Message>,
     state: Arc<Mutex<State>>,
+    events: broadcast::Sender<SpeechEvent>,
+    player: Player,
+}
+
+enum Message {
+    Say(SpeechRequest),
+    Shutdown(ShutdownMode, oneshot::Sender<()>),
 }
 
-#[derive(Default)]
 struct State {
     last_user_action: Option<Instant>,
     last_monologue_spoken: Option<Instant>,
+    gentle: bool,               // ユーザーに余裕がなさそうなので控えめにする
+    policy: SpeechPolicy,
+    quiet: QuietSchedule,
+    quiet_until: Option<Instant>,   // 「30分静かにして」の終わり
+    stats: SpeechStats,
+    events: broadcast::Sender<SpeechEvent>,
+    pending: watch::Sender<usize>,  // 頼まれて、まだ喋り終えても捨ててもいない数
 }
 
-impl SpeechQueue {
-    /// 発話ワーカーを起動して、送信用ハンドルを返す
-    pub fn spawn(
-        monologue_cooldown: Duration,
-        suppress_monologue_after_user: Duration,
-    ) -> Self {
-        let (tx, mut rx) = mpsc::channel::<SpeechRequest>(64);
-
-        let state = Arc::new(Mutex::new(State::default()));
-        let state_worker = state.clone();
-
-        // 優先度別キュー
-        let q_user: Arc<Mutex<VecDeque<String>>> = Arc::new(Mutex::new(VecDeque::new()));
-        let q_alert: Arc<Mutex<VecDeque<String>>> = Arc::new(Mutex::new(VecDeque::new()));
-        let q_mono: Arc<Mutex<VecDeque<String>>> = Arc::new(Mutex::new(VecDeque::new()));
-
-        let q_user_w = q_user.clone();
-        let q_alert_w = q_alert.clone();
-        let q_mono_w = q_mono.clone();
-
-        tokio::spawn(async move {
-            loop {
-                // まず受信をなるべく捌く（バースト耐性）
-                while let Ok(req) = rx.try_recv() {
-                    match req.kind {
-                        SpeechKind::User => {
-                            // ユーザーが喋った/入力した扱い（独り言抑制に使う）
-                            {
-                                let mut st = state_worker.lock().await;
-                                st.last_user_action = Some(Instant::now());
-                            }
-                            q_user_w.lock().await.push_back(req.text);
-                        }
-                        SpeechKind::Alert => {
-                            q_alert_w.lock().await.push_back(req.text);
-                        }
-                        SpeechKind::Monologue => {
-                            q_mono_w.lock().await.push_back(req.text);
+impl State {
+    fn new(policy: SpeechPolicy, quiet: QuietSchedule, events: broadcast::Sender<SpeechEvent>) -> Self {
+        Self {
+            last_user_action: None,
+            last_monologue_spoken: None,
+            gentle: false,
+            policy,
+            quiet,
+            quiet_until: None,
+            stats: SpeechStats::default(),
+            events,
+            pending: watch::channel(0).0,
+        }
+    }
+
+    fn is_quiet(&self) -> bool {
+        self.quiet_until.is_some_and(|until| Instant::now() < until) || self.quiet.is_quiet(Local::now().naive_local())
+    }
+
+    // 静かにする時間で、通知を終わるまで溜めておくか
+    fn alerts_on_hold(&self) -> bool {
+        self.quiet.alerts == AlertMode::Defer && self.is_quiet()
+    }
+
+    // 溜めた通知を次に見直すまでの時間（「○分静かにして」なら終わる時刻に合わせる）
+    fn quiet_recheck(&self) -> Duration {
+        self.quiet_until
+            .map(|until| until.saturating_duration_since(Instant::now()))
+            .filter(|left| !left.is_zero())
+            .map_or(QUIET_RECHECK, |left| left.min(QUIET_RECHECK))
+    }
+
+    fn counts(&mut self, kind: SpeechKind) -> &mut KindCounts {
+        self.stats.kinds.entry(kind).or_default()
+    }
+
+    // covers 件分の依頼が片付いた
+    fn settle(&self, covers: usize) {
+        self.pending.send_modify(|n| *n = n.saturating_sub(covers));
+    }
+
+    fn emit(&self, event: SpeechEvent) {
+        // 購読者がいなくても構わない
+        let _ = self.events.send(event);
+    }
+
+    fn enqueued(&mut self, kind: SpeechKind, text: &str) {
+        self.counts(kind).enqueued += 1;
+        self.emit(SpeechEvent::Enqueued { kind, text: text.to_string() });
+    }
+
+    fn started(&mut self, kind: SpeechKind, text: &str) {
+        self.counts(kind).started += 1;
+        self.emit(SpeechEvent::Started { kind, text: text.to_string() });
+    }
+
+    fn finished(&mut self, kind: SpeechKind, text: &str, covers: usize) {
+        self.counts(kind).finished += covers as u64;
+        self.settle(covers);
+        self.emit(SpeechEvent::Finished { kind, text: text.to_string() });
+    }
+
+    fn dropped(&mut self, kind: SpeechKind, text: &str, reason: DropReason, covers: usize) {
+        *self.stats.dropped.entry((kind, reason)).or_default() += covers as u64;
+        self.counts(kind).dropped += covers as u64;
+        self.settle(covers);
+        self.emit(SpeechEvent::Dropped { kind, text: text.to_string(), reason });
+    }
+}
+
+struct Queued {
+    text: String,
+    at: Instant,
+    ttl: Option<Duration>,
+    covers: usize,  // まとめた通知なら元の件数
+}
+
+impl Queued {
+    fn expired(&self, now: Instant) -> bool {
+        self.ttl.is_some_and(|ttl| now.duration_since(self.at) > ttl)
+    }
+}
+
+// 優先度別キュー
+#[derive(Default)]
+struct Queues {
+    user: VecDeque<Queued>,
+    alert: VecDeque<Queued>,
+    monologue: VecDeque<Queued>,
+    recent: VecDeque<(String, Instant)>,  // 重複を見るための、最近頼まれた文
+}
+
+impl Queues {
+    fn queue_mut(&mut self, kind: SpeechKind) -> &mut VecDeque<Queued> {
+        match kind {
+            SpeechKind::User => &mut self.user,
+            SpeechKind::Alert => &mut self.alert,
+            SpeechKind::Monologue => &mut self.monologue,
+        }
+    }
+
+    // 期限切れを捨ててから、優先度順で1件取り出す（通知が溜まっていればまとめる）
+    // hold_alerts のあいだは通知を出さず、期限も数えない
+    fn pop(&mut self, st: &mut State, hold_alerts: bool) -> Option<(SpeechKind, Queued)> {
+        let now = Instant::now();
+        if hold_alerts {
+            for item in &mut self.alert {
+                item.at = now;
+            }
+        }
+        for kind in [SpeechKind::User, SpeechKind::Alert, SpeechKind::Monologue] {
+            let (expired, kept) = self.queue_mut(kind).drain(..).partition(|item| item.expired(now));
+            *self.queue_mut(kind) = kept;
+            for item in expired {
+                st.dropped(kind, &item.text, DropReason::Expired, item.covers);
+            }
+        }
+
+        if let Some(item) = self.user.pop_front() {
+            return Some((SpeechKind::User, item));
+        }
+        if hold_alerts {
+            return self.monologue.pop_front().map(|item| (SpeechKind::Monologue, item));
+        }
+        if self.alert.len() > 1 && st.policy.coalesce_alerts {
+            let items: Vec<Queued> = self.alert.drain(..).collect();
+            let covers = items.iter().map(|item| item.covers).sum();
+            let texts: Vec<String> = items.into_iter().map(|item| item.text).collect();
+            st.stats.coalesced += texts.len() as u64;
+            return Some((SpeechKind::Alert, Queued { text: coalesce(&texts), at: now, ttl: None, covers }));
+        }
+        if let Some(item) = self.alert.pop_front() {
+            return Some((SpeechKind::Alert, item));
+        }
+        self.monologue.pop_front().map(|item| (SpeechKind::Monologue, item))
+    }
+
+    fn drain_all(&mut self) -> Vec<(SpeechKind, Queued)> {
+        [SpeechKind::User, SpeechKind::Alert, SpeechKind::Monologue]
+            .into_iter()
+            .flat_map(|kind| self.queue_mut(kind).drain(..).map(move |item| (kind, item)).collect::<Vec<_>>())
+            .collect()
+    }
+}
+
+/// 複数の通知を1回で読む文にする
+fn coalesce(texts: &[String]) -> String {
+    let body: String = texts.iter().map(|t| format!("{}。", t.trim_end_matches('。'))).collect();
+    format!("{}{}", COALESCED_ALERTS.replace("{count}", &texts.len().to_string()), body)
+}
+
+// 発話ワーカー（キューはワーカーだけが持つ）
+struct Worker {
+    engine: Arc<dyn TtsEngine>,
+    voices: VoiceResolver,
+    player: Player,
+    state: Arc<Mutex<State>>,
+    queues: Queues,
+    monologue_cooldown: Duration,
+    suppress_monologue_after_user: Duration,
+    closing: Option<oneshot::Sender<()>>,  // 終了を頼まれていたら、終わったら知らせる先
+    cancelled: bool,
+}
+
+impl Worker {
+    async fn run(mut self, mut rx: mpsc::Receiver<Message>) {
+        loop {
+            // まず受信をなるべく捌く（バースト耐性）
+            while let Ok(msg) = rx.try_recv() {
+                self.accept(msg, None).await;
+            }
+
+            let (next, recheck) = {
+                let mut st = self.state.lock().await;
+                let hold = st.alerts_on_hold();
+                let next = self.queues.pop(&mut st, hold);
+                (next, (hold && !self.queues.alert.is_empty()).then(|| st.quiet_recheck()))
+            };
+            match next {
+                Some((kind, item)) => self.speak(kind, item, &mut rx).await,
+                // 終了を頼まれていて、もう喋るものが無い（溜めている通知は取り消す）
+                None if self.closing.is_some() => break,
+                // 何もなければ「次の受信」を待つ（通知を溜めていれば、ときどき静かな時間が終わったか見直す）
+                None => tokio::select! {
+                    msg = rx.recv() => match msg {
+                        Some(msg) => {
+                            self.accept(msg, None).await;
                         }
+                        None => break, // sender全破棄で終了
+                    },
+                    _ = tokio::time::sleep(recheck.unwrap_or_default()), if recheck.is_some() => {}
+                },
+            }
+        }
+
+        // 終了までに届いていた依頼・溜めていた通知は取り消す
+        rx.close();
+        self.cancelled = true;
+        while let Ok(msg) = rx.try_recv() {
+            self.accept(msg, None).await;
+        }
+        let mut st = self.state.lock().await;
+        for (kind, item) in self.queues.drain_all() {
+            st.dropped(kind, &item.text, DropReason::Cancelled, item.covers);
+        }
+        drop(st);
+        if let Some(ack) = self.closing.take() {
+            let _ = ack.send(());
+        }
+    }
+
+    // 受け取ったものを捌く。喋っている最中（speaking）に割り込むべきなら true
+    async fn accept(&mut self, msg: Message, speaking: Option<SpeechKind>) -> bool {
+        match msg {
+            Message::Say(req) => {
+                if self.cancelled {
+                    self.state.lock().await.dropped(req.kind, &req.text, DropReason::Cancelled, 1);
+                    return false;
+                }
+                // 通知・独り言の途中でユーザーへの応答が来たら、止めてそちらを先に喋る（応答どうしは割り込まない）
+                let interrupt = req.kind == SpeechKind::User && speaking.is_some_and(|k| k != SpeechKind::User);
+                self.enqueue(req).await;
+                interrupt
+            }
+            Message::Shutdown(mode, ack) => {
+                if let Some(previous) = self.closing.replace(ack) {
+                    let _ = previous.send(());
+                }
+                if mode == ShutdownMode::Cancel {
+                    self.cancelled = true;
+                    let mut st = self.state.lock().await;
+                    for (kind, item) in self.queues.drain_all() {
+                        st.dropped(kind, &item.text, DropReason::Cancelled, item.covers);
                     }
+                    return speaking.is_some();
                 }
+                false
+            }
+        }
+    }
 
-                // 優先度順で1件取り出す
-                let next = {
-                    if let Some(s) = q_user_w.lock().await.pop_front() {
-                        Some((SpeechKind::User, s))
-                    } else if let Some(s) = q_alert_w.lock().await.pop_front() {
-                        Some((SpeechKind::Alert, s))
-                    } else if let Some(s) = q_mono_w.lock().await.pop_front() {
-                        Some((SpeechKind::Monologue, s))
-                    } else {
-                        None
-                    }
-                };
-
-                if let Some((kind, text)) = next {
-                    // 独り言は「ユーザー操作直後」や「クールダウン中」なら黙る（捨てる）
-                    if kind == SpeechKind::Monologue {
-                        let now = Instant::now();
-                        let st = state_worker.lock().await;
-
-                        if let Some(t) = st.last_user_action {
-                            if now.duration_since(t) < suppress_monologue_after_user {
-                                // 邪魔なので捨てる
-                                continue;
-                            }
-                        }
-                        if let Some(t) = st.last_monologue_spoken {
-                            if now.duration_since(t) < monologue_cooldown {
-                                // 喋りすぎなので捨てる
-                                continue;
-                            }
-                        }
-                        drop(st);
+    // 受け取った依頼をキューに積む（重複は捨て、溢れたら古いものから捨てる）
+    async fn enqueue(&mut self, req: SpeechRequest) {
+        let now = Instant::now();
+        let mut st = self.state.lock().await;
+        let policy = st.policy.clone();
 
-                        // ここで「喋った」記録
-                        let mut st = state_worker.lock().await;
-                        st.last_monologue_spoken = Some(now);
-                    }
+        if req.kind == SpeechKind::User {
+            // ユーザーが喋った/入力した扱い（独り言抑制に使う）
+            st.last_user_action = Some(now);
+        } else {
+            self.queues.recent.retain(|(_, at)| now.duration_since(*at) < policy.dedup_window);
+            if self.queues.recent.iter().any(|(text, _)| *text == req.text) {
+                st.dropped(req.kind, &req.text, DropReason::Duplicate, 1);
+                return;
+            }
+            self.queues.recent.push_back((req.text.clone(), now));
+        }
 
-                    // 実際の発話（MOCK_TTS含め tts::speak に集約）
-                    // エラーは落とさずログだけ
-                    if let Err(e) = crate::tts::speak(&text).await {
-                        eprintln!("TTS failed: {}", e);
-                    }
+        st.enqueued(req.kind, &req.text);
+        let queue = self.queues.queue_mut(req.kind);
+        queue.push_back(Queued { ttl: req.ttl.or(policy.ttl(req.kind)), text: req.text, at: req.at, covers: 1 });
+        while queue.len() > policy.max_len(req.kind).max(1) {
+            if let Some(old) = queue.pop_front() {
+                st.dropped(req.kind, &old.text, DropReason::Overflow, old.covers);
+            }
+        }
+    }
+
+    async fn speak(&mut self, kind: SpeechKind, item: Queued, rx: &mut mpsc::Receiver<Message>) {
+        let text_only;
+        {
+            let now = Instant::now();
+            let mut st = self.state.lock().await;
 
-                    continue;
+            // 静かにする時間の独り言は捨てる
+            if kind == SpeechKind::Monologue && st.is_quiet() {
+                st.dropped(kind, &item.text, DropReason::Quiet, item.covers);
+                return;
+            }
+            // 独り言は、余裕がなさそうなとき・ユーザー操作の直後（邪魔）・クールダウン中（喋りすぎ）なら黙る（捨てる）
+            if kind == SpeechKind::Monologue {
+                let suppressed = st.gentle
+                    || st.last_user_action.is_some_and(|t| now.duration_since(t) < self.suppress_monologue_after_user)
+                    || st.last_monologue_spoken.is_some_and(|t| now.duration_since(t) < self.monologue_cooldown);
+                if suppressed {
+                    st.dropped(kind, &item.text, DropReason::Suppressed, item.covers);
+                    return;
                 }
+                // ここで「喋った」記録
+                st.last_monologue_spoken = Some(now);
+            }
+            st.started(kind, &item.text);
+            // 静かにする時間の通知は、文字だけにする設定なら声を出さない
+            text_only = kind == SpeechKind::Alert && st.quiet.alerts == AlertMode::Text && st.is_quiet();
+        }
 
-                // 何もなければ「次の受信」を待つ
-                match rx.recv().await {
-                    Some(req) => {
-                        // 1件だけ入れて次ループでまとめて処理
-                        match req.kind {
-                            SpeechKind::User => {
-                                let mut st = state_worker.lock().await;
-                                st.last_user_action = Some(Instant::now());
-                                drop(st);
-                                q_user_w.lock().await.push_back(req.text);
-                            }
-                            SpeechKind::Alert => q_alert_w.lock().await.push_back(req.text),
-                            SpeechKind::Monologue => q_mono_w.lock().await.push_back(req.text),
-                        }
+        // 実際の発話（声はいまのキャラの話者）
+        let engine: Arc<dyn TtsEngine> = if text_only { Arc::new(TextOnlyEngine) } else { self.engine.clone() };
+        let player = self.player.clone();
+        // 声は本来のエンジンで決める（文字だけのエンジンの空の一覧を覚えてしまわないように）
+        let voice = self.voices.voice_for(self.engine.as_ref(), kind, persona::active().speaker).await;
+        let text = item.text.clone();
+        let mut speaking = Box::pin(tts::speak_on(engine.as_ref(), &player, Some(kind), &text, &voice));
+        let result = loop {
+            tokio::select! {
+                result = &mut speaking => break Some(result),
+                Some(msg) = rx.recv() => {
+                    if self.accept(msg, Some(kind)).await {
+                        break None;
                     }
-                    None => break, // sender全破棄で終了
                 }
             }
-        });
+        };
+        // 割り込むときは待つのをやめる（再生スレッドがこの発話だけを止める。ほかの発話は止めない）
+        drop(speaking);
+
+        let mut st = self.state.lock().await;
+        match result {
+            // エラーは落とさずログだけ
+            Some(Err(e)) => {
+                eprintln!("TTS failed: {}", e);
+                st.dropped(kind, &item.text, DropReason::Failed, item.covers);
+            }
+            Some(Ok(PlaybackEnd::Finished)) => st.finished(kind, &item.text, item.covers),
+            // 外から止められた（player().stop()）
+            Some(Ok(PlaybackEnd::Stopped)) => st.dropped(kind, &item.text, DropReason::Interrupted, item.covers),
+            None if self.cancelled => st.dropped(kind, &item.text, DropReason::Cancelled, item.covers),
+            // 通知は応答のあとで言い直す。独り言はそのまま捨てる
+            None if kind == SpeechKind::Alert => {
+                let ttl = st.policy.alert_ttl;
+                self.queues.alert.push_front(Queued { at: Instant::now(), ttl, ..item });
+            }
+            None => st.dropped(kind, &item.text, DropReason::Interrupted, item.covers),
+        }
+    }
+}
+
+impl SpeechQueue {
+    /// 発話ワーカーを起動して、送信用ハンドルを返す（エンジン・声・捌き方は設定から選ぶ）
+    pub fn spawn(
+        monologue_cooldown: Duration,
+        suppress_monologue_after_user: Duration,
+    ) -> Self {
+        Self::start(
+            tts::engine_from_env(),
+            VoiceConfig::from_env(),
+            tts::player().clone(),
+            SpeechPolicy::from_env(),
+            QuietSchedule::from_env(),
+            monologue_cooldown,
+            suppress_monologue_after_user,
+        )
+    }
+
+    /// 喋るエンジンを指定してワーカーを起動する（声はキャラのまま）
+    pub fn with_engine(
+        engine: Arc<dyn TtsEngine>,
+        monologue_cooldown: Duration,
+        suppress_monologue_after_user: Duration,
+    ) -> Self {
+        Self::with_voices(engine, VoiceConfig::default(), monologue_cooldown, suppress_monologue_after_user)
+    }
+
+    /// エンジンと声の設定（話者・速さなど、発話の種類ごと）を指定してワーカーを起動する
+    pub fn with_voices(
+        engine: Arc<dyn TtsEngine>,
+        voices: VoiceConfig,
+        monologue_cooldown: Duration,
+        suppress_monologue_after_user: Duration,
+    ) -> Self {
+        Self::with_player(engine, voices, tts::player().clone(), monologue_cooldown, suppress_monologue_after_user)
+    }
+
+    /// 鳴らす先（再生スレッド）も指定してワーカーを起動する（テストでは FakeSink の Player を渡す）
+    pub fn with_player(
+        engine: Arc<dyn TtsEngine>,
+        voices: VoiceConfig,
+        player: Player,
+        monologue_cooldown: Duration,
+        suppress_monologue_after_user: Duration,
+    ) -> Self {
+        Self::start(
+            engine,
+            voices,
+            player,
+            SpeechPolicy::default(),
+            QuietSchedule::default(),
+            monologue_cooldown,
+            suppress_monologue_after_user,
+        )
+    }
 
-        Self { tx, state }
+    fn start(
+        engine: Arc<dyn TtsEngine>,
+        voices: VoiceConfig,
+        player: Player,
+        policy: SpeechPolicy,
+        quiet: QuietSchedule,
+        monologue_cooldown: Duration,
+        suppress_monologue_after_user: Duration,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel::<Message>(64);
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        let state = Arc::new(Mutex::new(State::new(policy, quiet, events.clone())));
+
+        let worker = Worker {
+            engine,
+            voices: VoiceResolver::new(voices),
+            player: player.clone(),
+            state: state.clone(),
+            queues: Queues::default(),
+            monologue_cooldown,
+            suppress_monologue_after_user,
+            closing: None,
+            cancelled: false,
+        };
+        tokio::spawn(worker.run(rx));
+
+        Self { tx, state, events, player }
     }
 
     pub async fn say(&self, kind: SpeechKind, text: impl Into<String>) {
+        self.send(kind, text.into(), None).await;
+    }
+
+    /// ttl を過ぎてもまだ喋れていなければ捨てる
+    pub async fn say_with_ttl(&self, kind: SpeechKind, text: impl Into<String>, ttl: Duration) {
+        self.send(kind, text.into(), Some(ttl)).await;
+    }
+
+    async fn send(&self, kind: SpeechKind, text: String, ttl: Option<Duration>) {
+        // flush が送信中の分も待つように、先に数えておく
+        self.state.lock().await.pending.send_modify(|n| *n += 1);
         // 送信失敗はワーカー停止なので握りつぶし
-        let _ = self.tx.send(SpeechRequest { kind, text: text.into() }).await;
+        if self.tx.send(Message::Say(SpeechRequest { kind, text, ttl, at: Instant::now() })).await.is_err() {
+            self.state.lock().await.settle(1);
+        }
+    }
+
+    /// キューのイベント（積んだ・喋り始めた・喋り終えた・捨てた）を受け取る
+    pub fn subscribe(&self) -> broadcast::Receiver<SpeechEvent> {
+        self.events.subscribe()
+    }
+
+    /// これまでに頼んだ発話がすべて喋り終わるか捨てられるまで待つ
+    pub async fn flush(&self) {
+        let mut pending = self.state.lock().await.pending.subscribe();
+        let _ = pending.wait_for(|n| *n == 0).await;
+    }
+
+    /// ワーカーを止める。Finish なら溜まっている分を喋り終えてから、Cancel なら今の発話も止めて残りを捨てる
+    /// 止まったあとの say は何もしない
+    pub async fn shutdown(&self, mode: ShutdownMode) {
+        let (ack, done) = oneshot::channel();
+        if self.tx.send(Message::Shutdown(mode, ack)).await.is_ok() {
+            let _ = done.await;
+        }
     }
 
     pub async fn say_user(&self, text: impl Into<String>) { self.say(SpeechKind::User, text).await }
     pub async fn say_alert(&self, text: impl Into<String>) { self.say(SpeechKind::Alert, text).await }
     pub async fn say_monologue(&self, text: impl Into<String>) { self.say(SpeechKind::Monologue, text).await }
 
+    /// 控えめモード（独り言を止め、呼び出し側は通知を減らす）を切り替える
+    pub async fn set_gentle(&self, gentle: bool) {
+        self.state.lock().await.gentle = gentle;
+    }
+
+    pub async fn is_gentle(&self) -> bool {
+        self.state.lock().await.gentle
+    }
+
+    /// 期限・重複・まとめ方・キューの長さを変える（次に積む・取り出す分から効く）
+    pub async fn set_policy(&self, policy: SpeechPolicy) {
+        self.state.lock().await.policy = policy;
+    }
+
+    /// 静かにする時間（平日・週末・会議の枠と、そのあいだの通知の扱い）を変える
+    pub async fn set_quiet_schedule(&self, schedule: QuietSchedule) {
+        self.state.lock().await.quiet = schedule;
+    }
+
+    /// しばらく静かにする（「30分静かにして」）。ゼロなら解除し、24時間より長くはしない
+    pub async fn quiet_for(&self, duration: Duration) {
+        let now = Instant::now();
+        self.state.lock().await.quiet_until = Some(now.checked_add(duration.min(quiet::MAX_QUIET)).unwrap_or(now));
+    }
+
+    /// いま静かにする時間か（ユーザーへの応答はこのあいだも喋る）
+    pub async fn is_quiet(&self) -> bool {
+        self.state.lock().await.is_quiet()
+    }
+
+    /// いま通知を溜めているか（静かにしていて、通知を終わるまで出さない設定のとき）
+    pub async fn holds_alerts(&self) -> bool {
+        self.state.lock().await.alerts_on_hold()
+    }
+
+    /// これまでの種類ごとの件数と、捨てた・まとめた発話の数
+    pub async fn stats(&self) -> SpeechStats {
+        self.state.lock().await.stats.clone()
+    }
+
+    /// いま鳴っている発話を止める・一時停止する・再開する
+    pub fn player(&self) -> &Player {
+        &self.player
+    }
+
     /// ユーザー操作があったことだけ記録したい場合に使う（将来：GUIのクリック等）
     pub async fn mark_user_action(&self) {
         let mut st = self.state.lock().await;
@@ -93,7 +93,8 @@ pub fn save_tasks_with_file(path: &Path, tasks: &[Task]) {
 }
 
 
-pub async fn add_task(title: &str) {
+/// タスクを1件登録して保存する（発話はしない）
+pub fn create_task(title: &str) -> Task {
     let mut tasks = load_tasks::<&str>(None);
     let new_id = tasks.iter().map(|t| t.id).max().unwrap_or(0) + 1;
 
@@ -111,8 +112,13 @@ pub async fn add_task(title: &str) {
         extensions: Map::new(),
     };
 
-    tasks.push(new_task);
+    tasks.push(new_task.clone());
     save_tasks::<&str>(None, &tasks);
+    new_task
+}
+
+pub async fn add_task(title: &str) {
+    create_task(title);
 
     println!("Kotonoha > タスク「{}」を登録しました。", title);
     let response = format!("タスクを「{}」を登録しました。", title);
@@ -174,11 +180,68 @@ pub async fn mark_done_in<P:AsRef<Path>>(path: Option<P>, task_id: u32) {
 
 
 
+/// タスクを完了にして保存する（発話はしない）
+pub fn complete_task(task_id: u32) -> bool {
+    let mut tasks = load_tasks::<&str>(None);
+    if !mark_task_done(&mut tasks, task_id) {
+        return false;
+    }
+    save_tasks::<&str>(None, &tasks);
+    true
+}
+
+/// タスクの締切日を設定して保存する
+pub fn set_due_date(task_id: u32, due: NaiveDate) -> bool {
+    fn walk(tasks: &mut [Task], task_id: u32, due: NaiveDate) -> bool {
+        for t in tasks {
+            if t.id == task_id {
+                t.due_date = Some(due);
+                return true;
+            }
+            if walk(&mut t.subtasks, task_id, due) {
+                return true;
+            }
+        }
+        false
+    }
+
+    let mut tasks = load_tasks::<&str>(None);
+    if !walk(&mut tasks, task_id, due) {
+        return false;
+    }
+    save_tasks::<&str>(None, &tasks);
+    true
+}
+
+/// タイトルの部分一致または類似度でタスクを探す（サブタスクも含む）
+pub fn search_tasks(query: &str) -> Vec<Task> {
+    let query = query.to_lowercase();
+    let mut out = Vec::new();
+
+    fn walk(out: &mut Vec<Task>, tasks: &[Task], query: &str) {
+        for t in tasks {
+            let title = t.title.to_lowercase();
+            if title.contains(query) || jaro_winkler(&title, query) >= 0.8 {
+                out.push(t.clone());
+            }
+            walk(out, &t.subtasks, query);
+        }
+    }
+
+    walk(&mut out, &load_tasks::<&str>(None), &query);
+    out
+}
+
 fn mark_task_done(tasks: &mut [Task], task_id: u32) -> bool {
     for task in tasks {
         if task.id == task_id {
             task.done = true;
             task.status = TaskStatus::Completed;
+            // 「最近終わったこと」を会話で使うため、完了時刻を拡張領域に残す
+            task.extensions.insert(
+                COMPLETED_AT_KEY.to_string(),
+                Local::now().to_rfc3339().into(),
+            );
             return true;
         }
         if mark_task_done(&mut task.subtasks, task_id) {
@@ -288,45 +351,57 @@ pub fn find_task_with_score(input: &str, threshold: f64) -> Option<(u32, String,
     let mut best_score = 0.0;
 
     for task in &tasks {
-        if let Some((id, title, score)) = find_best_match(task, input) {
-            if score > best_score {
-                best_match = Some((id, title, score));
-                best_score = score;
-            }
-        }
-    }
-
-    if let Some((id, title, score)) = &best_match {
-        if *score >= threshold {
-            return Some((id.clone(), title.clone(), *score));
+        if let Some((id, title, score)) = find_best_match(task, input)
+            && score > best_score
+        {
+            best_match = Some((id, title, score));
+            best_score = score;
         }
     }
 
-    None
+    best_match.filter(|(_, _, score)| *score >= threshold)
 }
 
 
 
 
 
-/// タスク一覧をまとめた文字列を返す
+/// タスク一覧をまとめた文字列を返す（中身は context::format_open_tasks に任せる）
 pub fn summarize_tasks_for_prompt() -> String {
     let tasks = load_tasks::<&str>(None);
-    if tasks.is_empty() {
-        "現在、登録されているタスクはありません。".to_string()
-    } else {
-        let list = tasks
-            .iter()
-            .filter(|t| !t.done)  // 未完了タスクだけ
-            .map(|t| format!("・{}", t.title))
-            .collect::<Vec<_>>()
-            .join("\n");
-
-        format!("現在の未完了タスク一覧:\n{}", list)
+    crate::context::format_open_tasks(&tasks, crate::context::DEFAULT_TASK_TOKEN_BUDGET)
+}
+
+/// 完了時刻を記録する extensions のキー
+pub const COMPLETED_AT_KEY: &str = "completed_at";
+
+/// 完了時刻（記録されていなければ None）
+pub fn completed_at(task: &Task) -> Option<DateTime<FixedOffset>> {
+    task.extensions
+        .get(COMPLETED_AT_KEY)
+        .and_then(|v| v.as_str())
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+}
+
+/// 未完了タスク（サブタスク含む）を期限→優先度→ID の順に並べて返す
+/// 期限・優先度がないものは後ろに回す
+pub fn open_tasks_sorted(tasks: &[Task]) -> Vec<Task> {
+    fn walk(out: &mut Vec<Task>, tasks: &[Task]) {
+        for t in tasks {
+            if !t.done {
+                out.push(t.clone());
+            }
+            walk(out, &t.subtasks);
+        }
     }
+
+    let mut out = Vec::new();
+    walk(&mut out, tasks);
+    out.sort_by_key(|t| (t.due_date.is_none(), t.due_date, t.priority.is_none(), t.priority, t.id));
+    out
 }
 
-use chrono::{Local, NaiveDate};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate};
 /// 期限が within_days 日以内の未完了タスクを返す
 pub fn find_due_within_days(within_days: i64) -> Vec<Task> {
     let today: NaiveDate = Local::now().date_naive();
@@ -337,12 +412,12 @@ pub fn find_due_within_days(within_days: i64) -> Vec<Task> {
 
     fn walk(out: &mut Vec<Task>, tasks: &[Task], today: NaiveDate, limit: NaiveDate) {
         for t in tasks {
-            if !t.done {
-                if let Some(due) = t.due_date {
-                    if due >= today && due <= limit {
-                        out.push(t.clone());
-                    }
-                }
+            if !t.done
+                && let Some(due) = t.due_date
+                && due >= today
+                && due <= limit
+            {
+                out.push(t.clone());
             }
             walk(out, &t.subtasks, today, limit);
         }
@@ -352,9 +427,11 @@ pub fn find_due_within_days(within_days: i64) -> Vec<Task> {
     out
 }
 
+#[cfg(test)]
 use std::sync::{OnceLock};
 
-fn test_lock() -> std::sync::MutexGuard<'static, ()> {
+#[cfg(test)]
+pub(crate) fn test_lock() -> std::sync::MutexGuard<'static, ()> {
     static LOCK: OnceLock<Mutex<()>> = OnceLock::new();
     LOCK.get_or_init(|| Mutex::new(())).lock().unwrap()
 }
@@ -422,6 +499,8 @@ mod tests {
 
     #[test]
     fn test_add_and_load_tasks() {
+        let _g = test_lock();
+
         let temp = TempTaskFile::new();
 
         
@@ -436,6 +515,8 @@ mod tests {
 
     #[test]
     fn test_mark_done_updates_task() {
+        let _g = test_lock();
+
         let temp = TempTaskFile::new();
 
         let tasks = vec![dummy_task(1, "完了チェック")];
@@ -520,6 +601,8 @@ mod tests {
 
         #[tokio::test]
         async fn test_similarity_logs_best_score() {
+            let _g = test_lock();
+
             let temp = TempTaskFile::new();
 
             let tasks = vec![
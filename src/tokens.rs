@@ -0,0 +1,82 @@
+use crate::models::ChatMessage;
+
+use std::env;
+
+// 1メッセージごとにかかる役割名などのオーバーヘッド
+const MESSAGE_OVERHEAD: usize = 4;
+
+// 応答用に空けておくトークン数
+const REPLY_RESERVE: usize = 1024;
+
+/// 日本語混じりのテキストのトークン数を見積もる
+/// - 漢字は1文字≒1.5トークン
+/// - かな・全角記号は1文字≒1トークン
+/// - 英数字・半角記号は4文字≒1トークン
+pub fn estimate_tokens(text: &str) -> usize {
+    let mut kanji: usize = 0;
+    let mut wide: usize = 0;
+    let mut ascii: usize = 0;
+
+    for c in text.chars() {
+        match c {
+            c if c.is_ascii() => ascii += 1,
+            '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' => kanji += 1,
+            _ => wide += 1,
+        }
+    }
+
+    (kanji * 3).div_ceil(2) + wide + ascii.div_ceil(4)
+}
+
+/// 会話履歴全体のトークン数を見積もる
+pub fn estimate_messages(messages: &[ChatMessage]) -> usize {
+    messages
+        .iter()
+        .map(|m| estimate_tokens(&m.content) + MESSAGE_OVERHEAD)
+        .sum()
+}
+
+/// モデルのコンテキスト長
+pub fn context_window(model: &str) -> usize {
+    match model {
+        m if m.starts_with("gpt-4o") || m.starts_with("gpt-4-turbo") => 128_000,
+        m if m.starts_with("gpt-3.5-turbo") => 16_385,
+        m if m.starts_with("gpt-4") => 8_192,
+        m if m.starts_with("claude-") => 200_000,
+        m if m.starts_with("gemini-1.5-pro") => 2_000_000,
+        m if m.starts_with("gemini-") => 1_000_000,
+        _ => 4_096,
+    }
+}
+
+/// 会話履歴に使ってよいトークン数
+/// CHAT_TOKEN_BUDGET があればそちらを優先する（コンテキスト長は超えない）
+pub fn history_budget(model: &str) -> usize {
+    let window = context_window(model).saturating_sub(REPLY_RESERVE);
+    env::var("CHAT_TOKEN_BUDGET")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .map(|budget| budget.min(window))
+        .unwrap_or(window)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_tokens_japanese_and_ascii() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("あいう"), 3);
+        assert_eq!(estimate_tokens("会議"), 3);
+        assert_eq!(estimate_tokens("main.rs"), 2);
+        assert!(estimate_tokens("明日の会議の準備をする") > estimate_tokens("meeting prep"));
+    }
+
+    #[test]
+    fn test_history_budget_leaves_room_for_reply() {
+        assert!(history_budget("gpt-3.5-turbo") < context_window("gpt-3.5-turbo"));
+        assert_eq!(context_window("unknown-model"), 4_096);
+    }
+}
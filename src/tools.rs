@@ -0,0 +1,229 @@
+use crate::models::{FunctionDefinition, Task, ToolCall, ToolDefinition};
+use crate::tasks;
+
+use chrono::NaiveDate;
+use serde_json::{json, Value};
+
+/// 1回の雑談応答でツール呼び出しを繰り返してよい上限
+pub const MAX_TOOL_STEPS: usize = 5;
+
+/// ユーザーの確認が必要な操作（既存タスクを書き換えるもの）
+#[derive(Debug, Clone, PartialEq)]
+pub enum PendingAction {
+    Complete { task_id: u32, title: String },
+    SetDueDate { task_id: u32, title: String, due: NaiveDate },
+}
+
+impl PendingAction {
+    /// ユーザーに投げかける確認の文言
+    pub fn prompt(&self) -> String {
+        match self {
+            PendingAction::Complete { title, .. } => {
+                format!("『{}』を完了にしてもよろしいですか？(yes/no)", title)
+            }
+            PendingAction::SetDueDate { title, due, .. } => {
+                format!("『{}』の期限を {} にしてもよろしいですか？(yes/no)", title, due)
+            }
+        }
+    }
+}
+
+pub enum ToolOutcome {
+    Done(String),                       // 実行済み（結果をモデルに返す）
+    NeedsConfirmation(PendingAction),   // ユーザーの確認待ち
+}
+
+fn function(name: &str, description: &str, parameters: Value) -> ToolDefinition {
+    ToolDefinition {
+        kind: "function".into(),
+        function: FunctionDefinition {
+            name: name.into(),
+            description: description.into(),
+            parameters,
+        },
+    }
+}
+
+/// 雑談モードでモデルに公開するツール一覧
+pub fn definitions() -> Vec<ToolDefinition> {
+    vec![
+        function(
+            "add_task",
+            "新しいタスクを登録する。会話の中でユーザーがやるべきことに触れたときに使う。",
+            json!({
+                "type": "object",
+                "properties": { "title": { "type": "string", "description": "タスクのタイトル" } },
+                "required": ["title"]
+            }),
+        ),
+        function(
+            "complete_task",
+            "タスクを完了にする。実行前にユーザーへ確認する。",
+            json!({
+                "type": "object",
+                "properties": { "task_id": { "type": "integer" } },
+                "required": ["task_id"]
+            }),
+        ),
+        function(
+            "list_tasks",
+            "未完了のタスク一覧を取得する。",
+            json!({ "type": "object", "properties": {} }),
+        ),
+        function(
+            "set_due_date",
+            "タスクの期限を設定する。実行前にユーザーへ確認する。",
+            json!({
+                "type": "object",
+                "properties": {
+                    "task_id": { "type": "integer" },
+                    "due_date": { "type": "string", "description": "YYYY-MM-DD形式" }
+                },
+                "required": ["task_id", "due_date"]
+            }),
+        ),
+        function(
+            "search_tasks",
+            "タイトルでタスクを検索してIDを調べる。",
+            json!({
+                "type": "object",
+                "properties": { "query": { "type": "string" } },
+                "required": ["query"]
+            }),
+        ),
+    ]
+}
+
+fn describe(tasks: &[Task]) -> String {
+    if tasks.is_empty() {
+        return "該当するタスクはありません。".to_string();
+    }
+    tasks
+        .iter()
+        .map(|t| match t.due_date {
+            Some(due) => format!("{}: {}（期限: {}）{}", t.id, t.title, due, if t.done { " [完了]" } else { "" }),
+            None => format!("{}: {}{}", t.id, t.title, if t.done { " [完了]" } else { "" }),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// モデルから届いたツール呼び出しを実行する
+/// 書き換えを伴う操作はここでは適用せず、確認待ちとして返す
+pub fn execute(call: &ToolCall) -> ToolOutcome {
+    let args: Value = match serde_json::from_str(&call.function.arguments) {
+        Ok(v) => v,
+        Err(e) => return ToolOutcome::Done(format!("引数を解釈できませんでした: {}", e)),
+    };
+    let task_id = args.get("task_id").and_then(Value::as_u64).map(|id| id as u32);
+
+    match call.function.name.as_str() {
+        "add_task" => match args.get("title").and_then(Value::as_str) {
+            Some(title) if !title.trim().is_empty() => {
+                let task = tasks::create_task(title.trim());
+                ToolOutcome::Done(format!("タスク「{}」をID {} で登録しました。", task.title, task.id))
+            }
+            _ => ToolOutcome::Done("title が指定されていません。".to_string()),
+        },
+        "complete_task" => match task_id.and_then(|id| tasks::get_task_title(id).map(|t| (id, t))) {
+            Some((task_id, title)) => ToolOutcome::NeedsConfirmation(PendingAction::Complete { task_id, title }),
+            None => ToolOutcome::Done("指定されたタスクが見つかりません。".to_string()),
+        },
+        "list_tasks" => {
+            let open: Vec<Task> = tasks::load_tasks::<&str>(None).into_iter().filter(|t| !t.done).collect();
+            ToolOutcome::Done(describe(&open))
+        }
+        "set_due_date" => {
+            let due = args
+                .get("due_date")
+                .and_then(Value::as_str)
+                .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok());
+            let Some(due) = due else {
+                return ToolOutcome::Done("due_date は YYYY-MM-DD 形式で指定してください。".to_string());
+            };
+            match task_id.and_then(|id| tasks::get_task_title(id).map(|t| (id, t))) {
+                Some((task_id, title)) => ToolOutcome::NeedsConfirmation(PendingAction::SetDueDate { task_id, title, due }),
+                None => ToolOutcome::Done("指定されたタスクが見つかりません。".to_string()),
+            }
+        }
+        "search_tasks" => {
+            let query = args.get("query").and_then(Value::as_str).unwrap_or_default();
+            ToolOutcome::Done(describe(&tasks::search_tasks(query)))
+        }
+        other => ToolOutcome::Done(format!("未知のツールです: {}", other)),
+    }
+}
+
+/// ユーザーが承認した操作を適用し、結果の文言を返す
+pub fn apply(action: &PendingAction) -> String {
+    match action {
+        PendingAction::Complete { task_id, title } => {
+            if tasks::complete_task(*task_id) {
+                format!("『{}』を完了にしました。", title)
+            } else {
+                format!("『{}』が見つかりませんでした。", title)
+            }
+        }
+        PendingAction::SetDueDate { task_id, title, due } => {
+            if tasks::set_due_date(*task_id, *due) {
+                format!("『{}』の期限を {} にしました。", title, due)
+            } else {
+                format!("『{}』が見つかりませんでした。", title)
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::FunctionCall;
+    use crate::tasks::{load_tasks, set_task_file, test_lock};
+    use uuid::Uuid;
+
+    fn call(name: &str, arguments: &str) -> ToolCall {
+        ToolCall {
+            id: "call_test".into(),
+            kind: "function".into(),
+            function: FunctionCall { name: name.into(), arguments: arguments.into() },
+        }
+    }
+
+    #[test]
+    fn test_add_then_complete_requires_confirmation() {
+        let _g = test_lock();
+        let path = format!("tasks_test_tools_{}.json", Uuid::new_v4());
+        set_task_file(&path);
+
+        let ToolOutcome::Done(msg) = execute(&call("add_task", r#"{"title":"会議の準備"}"#)) else {
+            panic!("add_task は確認なしで実行されるはず");
+        };
+        assert!(msg.contains("会議の準備"));
+
+        let ToolOutcome::NeedsConfirmation(action) = execute(&call("complete_task", r#"{"task_id":1}"#)) else {
+            panic!("complete_task は確認が必要なはず");
+        };
+        assert!(!load_tasks::<&str>(None)[0].done);
+
+        apply(&action);
+        assert!(load_tasks::<&str>(None)[0].done);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_set_due_date_rejects_bad_format() {
+        let _g = test_lock();
+        let path = format!("tasks_test_tools_{}.json", Uuid::new_v4());
+        set_task_file(&path);
+        tasks::create_task("資料作成");
+
+        let ToolOutcome::Done(msg) = execute(&call("set_due_date", r#"{"task_id":1,"due_date":"明日"}"#)) else {
+            panic!("不正な日付は確認に進まないはず");
+        };
+        assert!(msg.contains("YYYY-MM-DD"));
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
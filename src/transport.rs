@@ -0,0 +1,389 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use reqwest::{Client, Method, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+// 記録に残すレスポンスヘッダー（認証まわりは残さない）
+const RECORDED_HEADERS: [&str; 2] = ["content-type", "retry-after"];
+
+/// HTTP 呼び出しの失敗
+#[derive(Debug)]
+pub enum HttpError {
+    Timeout,
+    Network(String),
+    Fixture(String),    // 再生中に記録と合わない・記録が尽きた
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::Timeout => write!(f, "HTTP timeout"),
+            HttpError::Network(e) => write!(f, "HTTP network error: {}", e),
+            HttpError::Fixture(e) => write!(f, "HTTP fixture error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+impl From<reqwest::Error> for HttpError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            HttpError::Timeout
+        } else {
+            HttpError::Network(e.to_string())
+        }
+    }
+}
+
+/// リクエスト1回分
+#[derive(Debug, Clone, Default)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub timeout: Option<Duration>,
+}
+
+impl HttpRequest {
+    pub fn post(url: impl Into<String>) -> Self {
+        Self { method: Method::POST, url: url.into(), ..Self::default() }
+    }
+
+    pub fn get(url: impl Into<String>) -> Self {
+        Self { method: Method::GET, url: url.into(), ..Self::default() }
+    }
+
+    pub fn put(url: impl Into<String>) -> Self {
+        Self { method: Method::PUT, url: url.into(), ..Self::default() }
+    }
+
+    pub fn query(mut self, name: &str, value: &str) -> Self {
+        self.query.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn json<T: Serialize>(self, body: &T) -> Result<Self, serde_json::Error> {
+        Ok(self.header("Content-Type", "application/json").body(serde_json::to_vec(body)?))
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// ホストを除いたパス（記録と照合する単位）
+    fn path(&self) -> String {
+        Url::parse(&self.url).map(|u| u.path().to_string()).unwrap_or_else(|_| self.url.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).to_string()
+    }
+}
+
+/// 記録したやり取り1件。テキストでないボディは base64 で持つ
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpExchange {
+    pub method: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub query: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<String>,
+    pub status: u16,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_base64: Option<String>,
+}
+
+impl HttpExchange {
+    fn new(request: &HttpRequest, response: &HttpResponse) -> Self {
+        let (body, body_base64) = match String::from_utf8(response.body.clone()) {
+            Ok(text) => (Some(text), None),
+            Err(_) => (None, Some(BASE64.encode(&response.body))),
+        };
+        Self {
+            method: request.method.to_string(),
+            path: request.path(),
+            query: request.query.clone(),
+            request_body: (!request.body.is_empty()).then(|| String::from_utf8_lossy(&request.body).to_string()),
+            status: response.status.as_u16(),
+            headers: response
+                .headers
+                .iter()
+                .filter(|(k, _)| RECORDED_HEADERS.contains(&k.to_ascii_lowercase().as_str()))
+                .cloned()
+                .collect(),
+            body,
+            body_base64,
+        }
+    }
+
+    fn matches(&self, request: &HttpRequest) -> bool {
+        self.method == request.method.as_str() && self.path == request.path() && self.query == request.query
+    }
+
+    fn response(&self) -> Result<HttpResponse, HttpError> {
+        let body = match (&self.body, &self.body_base64) {
+            (_, Some(encoded)) => BASE64.decode(encoded).map_err(|e| HttpError::Fixture(e.to_string()))?,
+            (Some(text), None) => text.clone().into_bytes(),
+            (None, None) => vec![],
+        };
+        Ok(HttpResponse {
+            status: StatusCode::from_u16(self.status).map_err(|e| HttpError::Fixture(e.to_string()))?,
+            headers: self.headers.clone(),
+            body,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    Record,     // 本物に送って、やり取りをファイルに書き足す
+    Replay,     // ネットワークに出ず、記録を順に返す
+}
+
+/// やり取りの記録ファイル（JSON の配列）
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    exchanges: Mutex<Vec<HttpExchange>>,
+    cursor: Mutex<usize>,
+}
+
+impl Cassette {
+    /// 記録を始める（既存のファイルは上書きする）
+    pub fn record(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            mode: CassetteMode::Record,
+            exchanges: Mutex::new(vec![]),
+            cursor: Mutex::new(0),
+        }
+    }
+
+    pub fn replay(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let exchanges: Vec<HttpExchange> = serde_json::from_str(&fs::read_to_string(path)?)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            mode: CassetteMode::Replay,
+            exchanges: Mutex::new(exchanges),
+            cursor: Mutex::new(0),
+        })
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    pub fn len(&self) -> usize {
+        self.exchanges.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 再生でまだ使っていない記録の数
+    pub fn remaining(&self) -> usize {
+        self.len().saturating_sub(*self.cursor.lock().unwrap())
+    }
+
+    fn push(&self, exchange: HttpExchange) {
+        let mut exchanges = self.exchanges.lock().unwrap();
+        exchanges.push(exchange);
+        match serde_json::to_string_pretty(&*exchanges) {
+            Ok(json) => {
+                if let Err(e) = fs::write(&self.path, json) {
+                    eprintln!("Failed to write HTTP fixture: {} ({})", self.path.display(), e);
+                }
+            }
+            Err(e) => eprintln!("Failed to serialize HTTP fixture: {}", e),
+        }
+    }
+
+    /// 記録を順番どおりに返す。パスやクエリが違えばエラー
+    fn next(&self, request: &HttpRequest) -> Result<HttpResponse, HttpError> {
+        let exchanges = self.exchanges.lock().unwrap();
+        let mut cursor = self.cursor.lock().unwrap();
+        let Some(exchange) = exchanges.get(*cursor) else {
+            return Err(HttpError::Fixture(format!("no more recorded exchanges for {}", request.path())));
+        };
+        if !exchange.matches(request) {
+            return Err(HttpError::Fixture(format!(
+                "expected {} {} {:?}, got {} {} {:?}",
+                exchange.method,
+                exchange.path,
+                exchange.query,
+                request.method,
+                request.path(),
+                request.query
+            )));
+        }
+        *cursor += 1;
+        exchange.response()
+    }
+}
+
+/// HTTP クライアント。カセットがあれば記録・再生を挟む
+#[derive(Clone)]
+pub struct HttpClient {
+    client: Client,
+    cassette: Option<Arc<Cassette>>,
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self::live()
+    }
+}
+
+impl HttpClient {
+    pub fn live() -> Self {
+        Self { client: Client::new(), cassette: None }
+    }
+
+    pub fn with_cassette(cassette: Arc<Cassette>) -> Self {
+        Self { client: Client::new(), cassette: Some(cassette) }
+    }
+
+    /// HTTP_RECORD / HTTP_REPLAY で指定したカセットを使う（LLM と TTS で1本を共有する）
+    pub fn from_env() -> Self {
+        match shared_cassette() {
+            Some(cassette) => Self::with_cassette(cassette),
+            None => Self::live(),
+        }
+    }
+
+    pub fn cassette(&self) -> Option<&Arc<Cassette>> {
+        self.cassette.as_ref()
+    }
+
+    pub async fn send(&self, request: HttpRequest) -> Result<HttpResponse, HttpError> {
+        if let Some(cassette) = &self.cassette
+            && cassette.mode() == CassetteMode::Replay
+        {
+            return cassette.next(&request);
+        }
+
+        let response = self.send_live(&request).await?;
+        if let Some(cassette) = &self.cassette {
+            cassette.push(HttpExchange::new(&request, &response));
+        }
+        Ok(response)
+    }
+
+    async fn send_live(&self, request: &HttpRequest) -> Result<HttpResponse, HttpError> {
+        let mut builder = self.client.request(request.method.clone(), &request.url).query(&request.query).body(request.body.clone());
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        let response = builder.send().await?;
+        let status = response.status();
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(k, v)| Some((k.to_string(), v.to_str().ok()?.to_string())))
+            .collect();
+        let body = response.bytes().await?.to_vec();
+        Ok(HttpResponse { status, headers, body })
+    }
+}
+
+fn shared_cassette() -> Option<Arc<Cassette>> {
+    static CASSETTE: OnceLock<Option<Arc<Cassette>>> = OnceLock::new();
+    CASSETTE
+        .get_or_init(|| {
+            if let Ok(path) = env::var("HTTP_REPLAY") {
+                match Cassette::replay(Path::new(&path)) {
+                    Ok(cassette) => return Some(Arc::new(cassette)),
+                    Err(e) => eprintln!("Failed to load HTTP fixture: {} ({})", path, e),
+                }
+            }
+            env::var("HTTP_RECORD").ok().map(|path| Arc::new(Cassette::record(Path::new(&path))))
+        })
+        .clone()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_bodies_round_trip() {
+        let request = HttpRequest::post("http://127.0.0.1:50021/synthesis").query("speaker", "8");
+        let response = HttpResponse {
+            status: StatusCode::OK,
+            headers: vec![("Content-Type".into(), "audio/wav".into()), ("Set-Cookie".into(), "secret".into())],
+            body: vec![b'R', b'I', b'F', b'F', 0xff, 0x00],
+        };
+
+        let exchange = HttpExchange::new(&request, &response);
+        assert_eq!(exchange.path, "/synthesis");
+        assert!(exchange.body.is_none());
+        assert_eq!(exchange.headers.len(), 1);
+
+        let replayed = exchange.response().unwrap();
+        assert_eq!(replayed.body, response.body);
+        assert_eq!(replayed.header("content-type"), Some("audio/wav"));
+    }
+
+    #[test]
+    fn test_replay_checks_order() {
+        let path = std::env::temp_dir().join(format!("cassette_test_{}.json", uuid::Uuid::new_v4()));
+        let recorder = Cassette::record(&path);
+        let ok = HttpResponse { status: StatusCode::OK, headers: vec![], body: b"{}".to_vec() };
+        recorder.push(HttpExchange::new(&HttpRequest::post("http://a/audio_query").query("text", "こんにちは"), &ok));
+
+        let cassette = Cassette::replay(&path).unwrap();
+        assert!(cassette.next(&HttpRequest::post("http://b/synthesis")).is_err());
+        assert!(cassette.next(&HttpRequest::post("http://b/audio_query").query("text", "こんにちは")).is_ok());
+        assert_eq!(cassette.remaining(), 0);
+        assert!(cassette.next(&HttpRequest::post("http://b/audio_query")).is_err());
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -1,97 +1,519 @@
 // src/tts.rs
 
 
-#[cfg(feature = "tts")]
-use rodio::{Decoder, OutputStream, Sink};
-
-#[cfg(feature = "tts")]
-use std::io::Cursor;
-
-#[cfg(feature = "tts")]
-use reqwest::Client;
-
-#[cfg(feature = "tts")]
-const KASUKABE_TSUMUGI_ID: &str = "8"; // 春日部つむぎのID
+use crate::audio_cache::CachedEngine;
+use crate::fallback::FallbackEngine;
+use crate::persona;
+use crate::playback::{Clip, PlaybackEnd, Player, WavFileSink, DEFAULT_AUDIO_OUTPUT_DIR};
+use crate::speech::SpeechKind;
+use crate::transport::{HttpClient, HttpError};
+use crate::voicevox::VoiceVoxEngine;
 
+pub use crate::voicevox::{DEFAULT_VOICEVOX_URL, synthesize, voicevox_url};
 
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::env;
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+
+pub const DEFAULT_VOICE_FILE: &str = "voice.json";
+
+/// 発話を鳴らす再生スレッド
+/// - AUDIO_OUTPUT=wav（TTS_ENGINE=file も同じ）: 鳴らさずに AUDIO_OUTPUT_DIR（既定: speech_audio）へ WAV と索引を書く（TTS 機能が無くても使える）
+/// - それ以外はスピーカー。TTS 機能の無いビルドでは鳴らさない（それでも記録・再生中は合成までは通す）
+pub fn player() -> &'static Player {
+    static PLAYER: OnceLock<Player> = OnceLock::new();
+    PLAYER.get_or_init(|| {
+        if env::var("AUDIO_OUTPUT").as_deref() == Ok("wav") || env::var("TTS_ENGINE").as_deref() == Ok("file") {
+            let dir = env::var("AUDIO_OUTPUT_DIR").unwrap_or_else(|_| DEFAULT_AUDIO_OUTPUT_DIR.to_string());
+            return Player::with_sink(WavFileSink::new(Path::new(&dir)));
+        }
+        #[cfg(feature = "tts")]
+        let player = Player::spawn(|| Ok(Box::new(crate::playback::RodioSink::open()?) as Box<dyn crate::playback::AudioSink>));
+        #[cfg(not(feature = "tts"))]
+        let player = Player::silent();
+        player
+    })
+}
 
 static MOCK_MODE: Lazy<AtomicBool> = Lazy::new(|| {
     AtomicBool::new(env::var("MOCK_TTS").is_ok())
 });
 
 
-// これを main.rs から呼ぶ
+// これを main.rs から呼ぶ（以後 engine_choice は mock になる）
 pub fn enable_mock_mode() {
     MOCK_MODE.store(true, Ordering::Relaxed);
 }
 
-use std::sync::{Mutex, OnceLock};
+/// 音声合成の失敗
+#[derive(Debug)]
+pub enum TtsError {
+    Http(HttpError),
+    Engine(String),     // エンジンがエラーを返した・応答が壊れている
+    Rejected(String),   // エンジンは動いているが、この入力を受け付けなかった（4xx・読むものが無い）
+    Io(std::io::Error),
+}
+
+impl TtsError {
+    /// エンジンが使えない状態か（接続できない・5xx・壊れた応答）。入力のせいで断られただけなら false
+    pub fn is_outage(&self) -> bool {
+        match self {
+            TtsError::Http(HttpError::Timeout | HttpError::Network(_)) | TtsError::Engine(_) | TtsError::Io(_) => true,
+            TtsError::Http(HttpError::Fixture(_)) | TtsError::Rejected(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for TtsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TtsError::Http(e) => write!(f, "{}", e),
+            TtsError::Engine(e) => write!(f, "TTS engine error: {}", e),
+            TtsError::Rejected(e) => write!(f, "TTS input rejected: {}", e),
+            TtsError::Io(e) => write!(f, "TTS I/O error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TtsError {}
+
+impl From<HttpError> for TtsError {
+    fn from(e: HttpError) -> Self {
+        TtsError::Http(e)
+    }
+}
+
+impl From<std::io::Error> for TtsError {
+    fn from(e: std::io::Error) -> Self {
+        TtsError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for TtsError {
+    fn from(e: serde_json::Error) -> Self {
+        TtsError::Engine(e.to_string())
+    }
+}
+
+pub type TtsFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, TtsError>> + Send + 'a>>;
+
+/// 選べる声1つ（VoiceVox ならスタイルごと）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Voice {
+    pub id: u32,
+    pub name: String,
+    #[serde(default)]
+    pub style: String,
+}
+
+impl Voice {
+    /// 表示用（例: 四国めたん（ノーマル））
+    pub fn label(&self) -> String {
+        if self.style.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{}（{}）", self.name, self.style)
+        }
+    }
+}
+
+/// 声の一覧から、ID・「名前」・「名前/スタイル」・「名前（スタイル）」で探す
+/// スタイルを省いたら、その話者の最初のスタイルにする
+pub fn find_voice(voices: &[Voice], spec: &str) -> Option<u32> {
+    let spec = spec.trim();
+    if let Ok(id) = spec.parse() {
+        return Some(id);
+    }
+    let (name, style) = match spec.split_once(['/', '／', '（']) {
+        Some((name, style)) => (name.trim(), Some(style.trim_end_matches('）').trim())),
+        None => (spec, None),
+    };
+    voices
+        .iter()
+        .find(|v| v.name == name && style.is_none_or(|s| v.style == s))
+        .map(|v| v.id)
+}
+
+/// 声の調整（VoiceVox の audio_query の speedScale などをこの値で書き換える。None は既定のまま）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceParams {
+    pub speed_scale: Option<f64>,
+    pub pitch_scale: Option<f64>,
+    pub intonation_scale: Option<f64>,
+    pub volume_scale: Option<f64>,
+}
+
+impl VoiceParams {
+    /// other で指定されている値を優先して重ねる
+    pub fn overlay(&self, other: &VoiceParams) -> VoiceParams {
+        VoiceParams {
+            speed_scale: other.speed_scale.or(self.speed_scale),
+            pitch_scale: other.pitch_scale.or(self.pitch_scale),
+            intonation_scale: other.intonation_scale.or(self.intonation_scale),
+            volume_scale: other.volume_scale.or(self.volume_scale),
+        }
+    }
+}
+
+/// 1回の合成に使う声（話者IDと調整）
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VoiceSpec {
+    pub speaker: u32,
+    pub params: VoiceParams,
+}
+
+impl VoiceSpec {
+    pub fn speaker(speaker: u32) -> Self {
+        Self { speaker, params: VoiceParams::default() }
+    }
+}
+
+/// 声の設定1つ分。speaker は ID か名前（find_voice の形）
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct VoiceSettings {
+    pub speaker: Option<String>,
+    #[serde(flatten)]
+    pub params: VoiceParams,
+}
+
+/// 声の設定（VOICE_FILE、既定: voice.json）。全体の設定に、発話の種類ごとの設定を重ねる
+/// 例: {"speaker": "四国めたん/ノーマル", "speed_scale": 1.1, "alert": {"speed_scale": 1.3, "volume_scale": 1.4}}
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct VoiceConfig {
+    #[serde(flatten)]
+    pub base: VoiceSettings,
+    pub user: VoiceSettings,
+    pub alert: VoiceSettings,
+    pub monologue: VoiceSettings,
+}
+
+impl VoiceConfig {
+    /// ファイルが無い・読めないなら既定（キャラの声のまま）
+    pub fn load(path: &Path) -> Self {
+        let Ok(raw) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&raw).unwrap_or_else(|e| {
+            eprintln!("Failed to parse voice file: {} ({})", path.display(), e);
+            Self::default()
+        })
+    }
+
+    /// VOICE_FILE を読み、VOICEVOX_SPEAKER があれば全体の話者をそれにする
+    pub fn from_env() -> Self {
+        let path = env::var("VOICE_FILE").unwrap_or_else(|_| DEFAULT_VOICE_FILE.to_string());
+        let mut config = Self::load(Path::new(&path));
+        if let Ok(speaker) = env::var("VOICEVOX_SPEAKER") {
+            config.base.speaker = Some(speaker);
+        }
+        config
+    }
+
+    /// 種類ごとの設定を重ねたもの
+    pub fn for_kind(&self, kind: SpeechKind) -> VoiceSettings {
+        let specific = match kind {
+            SpeechKind::User => &self.user,
+            SpeechKind::Alert => &self.alert,
+            SpeechKind::Monologue => &self.monologue,
+        };
+        VoiceSettings {
+            speaker: specific.speaker.clone().or_else(|| self.base.speaker.clone()),
+            params: self.base.params.overlay(&specific.params),
+        }
+    }
+
+    /// 名前で話者を指定しているか（声の一覧が要るか）
+    pub fn needs_voice_list(&self) -> bool {
+        [&self.base, &self.user, &self.alert, &self.monologue]
+            .iter()
+            .filter_map(|s| s.speaker.as_deref())
+            .any(|spec| spec.trim().parse::<u32>().is_err())
+    }
+}
+
+/// 発話の種類ごとに、設定とキャラの声から実際に使う声を決める
+/// 名前で指定されていれば、最初に一度だけエンジンに声の一覧を聞く
+pub struct VoiceResolver {
+    config: VoiceConfig,
+    voices: Option<Vec<Voice>>,
+}
+
+impl VoiceResolver {
+    pub fn new(config: VoiceConfig) -> Self {
+        Self { config, voices: None }
+    }
+
+    /// speaker の指定が無い・見つからないときは default_speaker（キャラの声）にする
+    pub async fn voice_for(&mut self, engine: &dyn TtsEngine, kind: SpeechKind, default_speaker: u32) -> VoiceSpec {
+        let settings = self.config.for_kind(kind);
+        let Some(spec) = settings.speaker.as_deref() else {
+            return VoiceSpec { speaker: default_speaker, params: settings.params };
+        };
+
+        if self.voices.is_none() && self.config.needs_voice_list() {
+            match engine.voices().await {
+                Ok(voices) => self.voices = Some(voices),
+                Err(e) => eprintln!("Failed to list voices: {}", e),
+            }
+        }
+        let speaker = find_voice(self.voices.as_deref().unwrap_or_default(), spec).unwrap_or_else(|| {
+            eprintln!("Voice not found: {} (using speaker {})", spec, default_speaker);
+            default_speaker
+        });
+        VoiceSpec { speaker, params: settings.params }
+    }
+}
 
-static SPOKEN: OnceLock<Mutex<Vec<String>>> = OnceLock::new();
+/// 音声合成の実装。再生は呼び出し側（speak_with）が受け持つ
+pub trait TtsEngine: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// text を voice の声で WAV にする
+    fn synthesize<'a>(&'a self, text: &'a str, voice: &'a VoiceSpec) -> TtsFuture<'a, Vec<u8>>;
+
+    fn voices(&self) -> TtsFuture<'_, Vec<Voice>>;
+
+    /// いま使えるか（既定: 声の一覧が取れるか）。FallbackEngine が復帰を確かめるのに使う
+    fn health_check(&self) -> TtsFuture<'_, ()> {
+        Box::pin(async move { self.voices().await.map(|_| ()) })
+    }
+
+    /// 合成した音を鳴らすか（記録やファイル書き出しだけのエンジンは false）
+    fn plays_audio(&self) -> bool {
+        true
+    }
+}
+
+impl<E: TtsEngine + ?Sized> TtsEngine for Arc<E> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn synthesize<'a>(&'a self, text: &'a str, voice: &'a VoiceSpec) -> TtsFuture<'a, Vec<u8>> {
+        (**self).synthesize(text, voice)
+    }
+
+    fn voices(&self) -> TtsFuture<'_, Vec<Voice>> {
+        (**self).voices()
+    }
+
+    fn health_check(&self) -> TtsFuture<'_, ()> {
+        (**self).health_check()
+    }
+
+    fn plays_audio(&self) -> bool {
+        (**self).plays_audio()
+    }
+}
+
+/// 喋らずに、喋ろうとした内容を覚えておく（MOCK_TTS・テスト用）
+#[derive(Default)]
+pub struct MockEngine {
+    spoken: Mutex<Vec<String>>,
+}
+
+impl MockEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// これまでに喋ろうとした内容を取り出す（取り出した分は消える）
+    pub fn take_spoken(&self) -> Vec<String> {
+        std::mem::take(&mut *self.spoken.lock().unwrap())
+    }
+}
+
+impl TtsEngine for MockEngine {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn synthesize<'a>(&'a self, text: &'a str, _voice: &'a VoiceSpec) -> TtsFuture<'a, Vec<u8>> {
+        Box::pin(async move {
+            // モックモードなら、VOICEVOXには繋がずプリントする
+            println!("[MOCK VOICE]: {}", text);
+            self.spoken.lock().unwrap().push(text.to_string());
+            Ok(vec![])
+        })
+    }
+
+    fn voices(&self) -> TtsFuture<'_, Vec<Voice>> {
+        Box::pin(async { Ok(vec![Voice { id: 0, name: "mock".to_string(), style: String::new() }]) })
+    }
+
+    fn plays_audio(&self) -> bool {
+        false
+    }
+}
+
+// HTTP_RECORD / HTTP_REPLAY のカセットは LLM と共有する
+pub(crate) fn shared_http() -> &'static HttpClient {
+    static HTTP: OnceLock<HttpClient> = OnceLock::new();
+    HTTP.get_or_init(HttpClient::from_env)
+}
+
+/// VoiceVox の WAV キャッシュ（DISABLE_TTS_CACHE なら None）。中身と統計は全体で1つ
+pub fn audio_cache() -> Option<Arc<CachedEngine>> {
+    static CACHE: OnceLock<Option<Arc<CachedEngine>>> = OnceLock::new();
+    CACHE.get_or_init(|| CachedEngine::from_env(VoiceVoxEngine::from_env()).map(Arc::new)).clone()
+}
+
+// キャッシュがあればキャッシュ越しの VoiceVox
+fn voicevox_engine() -> Arc<dyn TtsEngine> {
+    match audio_cache() {
+        Some(cache) => cache,
+        None => Arc::new(VoiceVoxEngine::from_env()),
+    }
+}
+
+// MOCK_TTS のときに使う記録係（テストから take_spoken で覗く）
+fn mock_engine() -> Arc<MockEngine> {
+    static MOCK: OnceLock<Arc<MockEngine>> = OnceLock::new();
+    MOCK.get_or_init(|| Arc::new(MockEngine::new())).clone()
+}
 
 pub fn take_spoken() -> Vec<String> {
-    let m = SPOKEN.get_or_init(|| Mutex::new(Vec::new()));
-    let mut v = m.lock().unwrap();
-    std::mem::take(&mut *v)
+    mock_engine().take_spoken()
 }
 
+/// 使うエンジンの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineChoice {
+    VoiceVox,
+    Mock,
+}
 
-#[cfg(not(feature = "tts"))]
-pub async fn speak(text: &str) -> Result<(), Box<dyn std::error::Error>> {
-    // tts feature が無い場合でも、MOCK はテストで使いたい
+/// 設定からエンジンの種類を選ぶ（モックかどうかはここだけで決める）
+/// - MOCK_TTS（または enable_mock_mode）: TTS_ENGINE より優先して mock
+/// - TTS_ENGINE=voicevox（既定）/ mock。file は VoiceVox のまま、書き出しは player() が受け持つ
+pub fn engine_choice() -> EngineChoice {
     if MOCK_MODE.load(Ordering::Relaxed) {
-        println!("[MOCK VOICE(no-tts)]: {}", text);
+        return EngineChoice::Mock;
+    }
+    match env::var("TTS_ENGINE").as_deref() {
+        Ok("mock") => EngineChoice::Mock,
+        Ok("voicevox" | "file") | Err(_) => EngineChoice::VoiceVox,
+        Ok(other) => {
+            eprintln!("Unknown TTS_ENGINE: {} (using voicevox)", other);
+            EngineChoice::VoiceVox
+        }
+    }
+}
 
-        let m = SPOKEN.get_or_init(|| Mutex::new(Vec::new()));
-        m.lock().unwrap().push(text.to_string());
+/// 設定からエンジンを作る。作るのは種類ごとに一度だけで、以後は同じもの（落ちている・戻ったの状態を呼び出しをまたいで持つ）
+/// - mock: 記録だけ
+/// - voicevox: 落ちていれば Open JTalk / espeak-ng、それも無ければ文字だけに切り替える
+pub fn engine_from_env() -> Arc<dyn TtsEngine> {
+    static VOICEVOX: OnceLock<Arc<dyn TtsEngine>> = OnceLock::new();
+    match engine_choice() {
+        EngineChoice::Mock => mock_engine(),
+        EngineChoice::VoiceVox => VOICEVOX.get_or_init(|| Arc::new(FallbackEngine::with_offline(voicevox_engine()))).clone(),
     }
-    Ok(())
 }
 
+/// engine で合成して鳴らす（鳴り終わるまで待つ）
+pub async fn speak_with(engine: &dyn TtsEngine, text: &str, voice: &VoiceSpec) -> Result<(), TtsError> {
+    speak_on(engine, player(), None, text, voice).await.map(|_| ())
+}
 
-#[cfg(feature = "tts")]
-pub async fn speak(text: &str) -> Result<(), Box<dyn std::error::Error>> {
-    if MOCK_MODE.load(Ordering::Relaxed) {
-    // モックモードなら、VOICEVOXには繋がずプリントする
-    println!("[MOCK VOICE]: {}", text);
+/// engine で合成して player で鳴らす（kind は WAV の索引に残す）。止められたら PlaybackEnd::Stopped
+pub async fn speak_on(
+    engine: &dyn TtsEngine,
+    player: &Player,
+    kind: Option<SpeechKind>,
+    text: &str,
+    voice: &VoiceSpec,
+) -> Result<PlaybackEnd, TtsError> {
+    // 鳴らせないなら、記録・再生中でなければ VoiceVox を呼ぶ意味がない
+    if engine.plays_audio() && !player.is_audible() && shared_http().cassette().is_none() {
+        return Ok(PlaybackEnd::Finished);
+    }
 
-    // ★テスト時だけ「喋った内容」を記録する
-        let m = SPOKEN.get_or_init(|| Mutex::new(Vec::new()));
-        m.lock().unwrap().push(text.to_string());
+    let audio = engine.synthesize(text, voice).await?;
+    if engine.plays_audio() && !audio.is_empty() {
+        let clip = Clip { audio, text: text.to_string(), kind, engine: engine.name().to_string() };
+        return player.play(clip).await;
+    }
+    Ok(PlaybackEnd::Finished)
+}
 
-    return Ok(());
+/// 読みの辞書を VoiceVox のユーザー辞書に登録する（起動時に裏で呼ぶ）
+pub async fn sync_readings() {
+    let dictionary = crate::reading::dictionary();
+    if dictionary.is_empty() || engine_choice() == EngineChoice::Mock || (!player().is_audible() && shared_http().cassette().is_none()) {
+        return;
+    }
+    match crate::voicevox::sync_user_dict(shared_http(), &voicevox_url(), dictionary).await {
+        Ok(0) => {}
+        Ok(changed) => eprintln!("Registered readings with VoiceVox: {}", changed),
+        Err(e) => eprintln!("Failed to sync VoiceVox user dictionary: {}", e),
+    }
 }
 
+/// 決まり文句を、応答に使う声で VoiceVox のキャッシュに入れておく（起動時に裏で呼ぶ）
+pub async fn prewarm(phrases: &[String]) {
+    // 鳴らせないビルドでは使われないので作らない
+    let Some(cache) = audio_cache().filter(|_| player().is_audible() && engine_choice() != EngineChoice::Mock) else {
+        return;
+    };
+    let voice = shared_voices().lock().await.voice_for(cache.as_ref(), SpeechKind::User, persona::active().speaker).await;
+    let synthesized = cache.prewarm(phrases, &voice).await;
+    if synthesized > 0 {
+        eprintln!("Prewarmed TTS cache: {} phrases", synthesized);
+    }
+}
+
+// キューを通さない発話（挨拶・時報・タスク一覧）の声。VOICE_FILE / VOICEVOX_SPEAKER を読み、声の一覧は一度だけ取る
+fn shared_voices() -> &'static tokio::sync::Mutex<VoiceResolver> {
+    static VOICES: OnceLock<tokio::sync::Mutex<VoiceResolver>> = OnceLock::new();
+    VOICES.get_or_init(|| tokio::sync::Mutex::new(VoiceResolver::new(VoiceConfig::from_env())))
+}
 
-    // 本物のVoiceVoxを呼ぶ処理
-    let client = Client::new();
+/// 設定どおりのエンジンで喋る。声は応答と同じ（声の設定が無ければいまのキャラの声）
+pub async fn speak(text: &str) -> Result<(), TtsError> {
+    let engine = engine_from_env();
+    let voice = shared_voices().lock().await.voice_for(engine.as_ref(), SpeechKind::User, persona::active().speaker).await;
+    speak_with(engine.as_ref(), text, &voice).await
+}
 
-    let query = client
-        .post("http://127.0.0.1:50021/audio_query")
-        .query(&[("text", text), ("speaker", KASUKABE_TSUMUGI_ID)])
-        .send()
-        .await?
-        .text()
-        .await?;
 
-    let audio = client
-        .post("http://127.0.0.1:50021/synthesis")
-        .query(&[("speaker", KASUKABE_TSUMUGI_ID)])
-        .header("Content-Type", "application/json")
-        .body(query)
-        .send()
-        .await?
-        .bytes()
-        .await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let (_stream, handle) = OutputStream::try_default()?;
-    let sink = Sink::try_new(&handle)?;
-    let source = Decoder::new(Cursor::new(audio))?;
-    sink.append(source);
-    sink.sleep_until_end();
+    #[test]
+    fn test_voice_config_per_kind() {
+        let config: VoiceConfig = serde_json::from_str(
+            r#"{"speaker": "四国めたん", "speed_scale": 1.1, "alert": {"speed_scale": 1.3, "volume_scale": 1.4}, "monologue": {"speaker": "8"}}"#,
+        )
+        .unwrap();
 
-    Ok(())
+        let alert = config.for_kind(SpeechKind::Alert);
+        assert_eq!(alert.speaker.as_deref(), Some("四国めたん"));
+        assert_eq!((alert.params.speed_scale, alert.params.volume_scale), (Some(1.3), Some(1.4)));
+        assert_eq!(config.for_kind(SpeechKind::User).params.speed_scale, Some(1.1));
+        assert_eq!(config.for_kind(SpeechKind::Monologue).speaker.as_deref(), Some("8"));
+        assert!(config.needs_voice_list());
+    }
+
+    #[test]
+    fn test_find_voice() {
+        let voice = |id, name: &str, style: &str| Voice { id, name: name.into(), style: style.into() };
+        let voices = vec![voice(2, "四国めたん", "ノーマル"), voice(0, "四国めたん", "あまあま"), voice(8, "春日部つむぎ", "ノーマル")];
+        assert_eq!(find_voice(&voices, "四国めたん"), Some(2));
+        assert_eq!(find_voice(&voices, "四国めたん/あまあま"), Some(0));
+        assert_eq!(find_voice(&voices, "四国めたん（あまあま）"), Some(0));
+        assert_eq!(find_voice(&voices, "13"), Some(13));
+        assert_eq!(find_voice(&voices, "ずんだもん"), None);
+    }
 }
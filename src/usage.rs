@@ -0,0 +1,209 @@
+use crate::models::Usage;
+
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_USAGE_FILE: &str = "usage.jsonl";
+
+/// 予算超過時に切り替える安いモデル
+pub const CHEAP_MODEL: &str = "gpt-4o-mini";
+
+/// どこからの呼び出しか（集計の単位）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum CallSite {
+    Classify,   // 入力の分類
+    Action,     // タスク操作の分類
+    Extract,    // タスク名の抽出
+    Chat,       // 雑談
+    Memory,     // 事実の抽出
+    Summary,    // 会話の要約
+    Mood,       // 気分の判定
+}
+
+impl CallSite {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CallSite::Classify => "分類",
+            CallSite::Action => "操作判定",
+            CallSite::Extract => "タスク抽出",
+            CallSite::Chat => "雑談",
+            CallSite::Memory => "記憶",
+            CallSite::Summary => "要約",
+            CallSite::Mood => "気分",
+        }
+    }
+}
+
+/// 1Mトークンあたりの料金（USD）: (入力, 出力)
+pub fn price_per_million(model: &str) -> (f64, f64) {
+    match model {
+        m if m.starts_with("gpt-4o-mini") => (0.15, 0.60),
+        m if m.starts_with("gpt-4o") => (2.50, 10.00),
+        m if m.starts_with("gpt-4") => (10.00, 30.00),
+        m if m.starts_with("claude-3-haiku") => (0.25, 1.25),
+        m if m.starts_with("claude-3-5-haiku") => (0.80, 4.00),
+        m if m.contains("sonnet") => (3.00, 15.00),
+        m if m.contains("opus") => (15.00, 75.00),
+        m if m.starts_with("gemini-1.5-flash-8b") => (0.0375, 0.15),
+        m if m.starts_with("gemini-1.5-flash") => (0.075, 0.30),
+        m if m.starts_with("gemini-1.5-pro") => (1.25, 5.00),
+        _ => (0.50, 1.50), // gpt-3.5-turbo 相当
+    }
+}
+
+pub fn estimate_cost(model: &str, usage: &Usage) -> f64 {
+    let (input, output) = price_per_million(model);
+    (usage.prompt_tokens as f64 * input + usage.completion_tokens as f64 * output) / 1_000_000.0
+}
+
+/// 台帳の1行（JSONL）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub timestamp: DateTime<Local>,
+    pub call_site: CallSite,
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+    /// 使ったプロンプトテンプレート（例: classify_input@1/ja）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+}
+
+/// 集計（呼び出し回数・トークン数・費用）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tally {
+    pub calls: u64,
+    pub tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl Tally {
+    fn add(&mut self, record: &UsageRecord) {
+        self.calls += 1;
+        self.tokens += record.prompt_tokens + record.completion_tokens;
+        self.cost_usd += record.cost_usd;
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MonthSummary {
+    pub total: Tally,
+    pub per_site: BTreeMap<CallSite, Tally>,
+    pub per_day: BTreeMap<NaiveDate, Tally>,
+}
+
+impl MonthSummary {
+    /// 「今月のAPI利用状況」への答え
+    pub fn describe(&self, budget_usd: Option<f64>) -> String {
+        let mut lines = vec![format!(
+            "今月のAPI利用は {} 回、{} トークン、約 ${:.4} です。",
+            self.total.calls, self.total.tokens, self.total.cost_usd
+        )];
+        if let Some(budget) = budget_usd {
+            lines.push(format!("予算 ${:.2} のうち {:.0}% を使っています。", budget, self.total.cost_usd / budget * 100.0));
+        }
+        for (site, tally) in &self.per_site {
+            lines.push(format!("・{}: {} 回 / {} トークン / ${:.4}", site.label(), tally.calls, tally.tokens, tally.cost_usd));
+        }
+        lines.join("\n")
+    }
+}
+
+/// 利用台帳（USAGE_FILE、既定: usage.jsonl）
+#[derive(Debug, Clone)]
+pub struct UsageLedger {
+    path: PathBuf,
+}
+
+impl UsageLedger {
+    pub fn new(path: &Path) -> Self {
+        Self { path: path.to_path_buf() }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(Path::new(&env::var("USAGE_FILE").unwrap_or_else(|_| DEFAULT_USAGE_FILE.to_string())))
+    }
+
+    pub fn append(&self, record: &UsageRecord) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(record)?)
+    }
+
+    pub fn load(&self) -> Vec<UsageRecord> {
+        let Ok(file) = File::open(&self.path) else {
+            return vec![];
+        };
+        BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect()
+    }
+
+    /// now と同じ月の利用を集計する
+    pub fn month_summary(&self, now: DateTime<Local>) -> MonthSummary {
+        let mut summary = MonthSummary::default();
+        for record in self.load() {
+            let at = record.timestamp;
+            if at.year() != now.year() || at.month() != now.month() {
+                continue;
+            }
+            summary.total.add(&record);
+            summary.per_site.entry(record.call_site).or_default().add(&record);
+            summary.per_day.entry(at.date_naive()).or_default().add(&record);
+        }
+        summary
+    }
+}
+
+/// 月の予算（MONTHLY_BUDGET_USD）。未設定なら無制限
+pub fn monthly_budget_from_env() -> Option<f64> {
+    env::var("MONTHLY_BUDGET_USD").ok().and_then(|v| v.parse().ok())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn record(at: DateTime<Local>, call_site: CallSite, tokens: u64) -> UsageRecord {
+        let usage = Usage { prompt_tokens: tokens, completion_tokens: tokens, total_tokens: tokens * 2 };
+        UsageRecord {
+            timestamp: at,
+            call_site,
+            model: "gpt-3.5-turbo".into(),
+            prompt_tokens: tokens,
+            completion_tokens: tokens,
+            cost_usd: estimate_cost("gpt-3.5-turbo", &usage),
+            prompt: None,
+        }
+    }
+
+    #[test]
+    fn test_month_summary_groups_by_site_and_day() {
+        let path = std::env::temp_dir().join(format!("usage_test_{}.jsonl", uuid::Uuid::new_v4()));
+        let ledger = UsageLedger::new(&path);
+        let june = |day| Local.with_ymd_and_hms(2025, 6, day, 12, 0, 0).unwrap();
+
+        ledger.append(&record(june(1), CallSite::Classify, 100)).unwrap();
+        ledger.append(&record(june(1), CallSite::Chat, 1_000)).unwrap();
+        ledger.append(&record(june(2), CallSite::Chat, 1_000)).unwrap();
+        ledger.append(&record(Local.with_ymd_and_hms(2025, 5, 31, 12, 0, 0).unwrap(), CallSite::Chat, 1_000)).unwrap();
+
+        let summary = ledger.month_summary(june(15));
+        assert_eq!(summary.total.calls, 3);
+        assert_eq!(summary.per_site[&CallSite::Chat].tokens, 4_000);
+        assert_eq!(summary.per_day.len(), 2);
+        assert!((summary.total.cost_usd - 0.0042).abs() < 1e-9);
+        assert!(summary.describe(Some(1.0)).contains("雑談"));
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
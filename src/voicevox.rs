@@ -0,0 +1,252 @@
+use crate::reading::{self, ReadingDictionary, Segment};
+use crate::tts::{self, TtsEngine, TtsError, TtsFuture, Voice, VoiceParams, VoiceSpec};
+use crate::transport::{HttpClient, HttpRequest};
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+
+pub const DEFAULT_VOICEVOX_URL: &str = "http://127.0.0.1:50021";
+
+/// VoiceVox の場所（VOICEVOX_URL、既定: ローカル）
+pub fn voicevox_url() -> String {
+    env::var("VOICEVOX_URL").unwrap_or_else(|_| DEFAULT_VOICEVOX_URL.to_string())
+}
+
+/// audio_query の結果に声の調整を書き込む
+pub fn apply_params(query: &mut serde_json::Value, params: &VoiceParams) {
+    let fields = [
+        ("speedScale", params.speed_scale),
+        ("pitchScale", params.pitch_scale),
+        ("intonationScale", params.intonation_scale),
+        ("volumeScale", params.volume_scale),
+    ];
+    for (field, value) in fields {
+        if let Some(value) = value {
+            query[field] = serde_json::json!(value);
+        }
+    }
+}
+
+/// VoiceVox で WAV を作る（audio_query → synthesis）。再生はしない
+pub async fn synthesize(http: &HttpClient, base_url: &str, text: &str, speaker: u32) -> Result<Vec<u8>, TtsError> {
+    synthesize_voice(http, base_url, text, &VoiceSpec::speaker(speaker)).await
+}
+
+async fn audio_query(http: &HttpClient, base_url: &str, text: &str, speaker: &str) -> Result<serde_json::Value, TtsError> {
+    let query = http
+        .send(HttpRequest::post(format!("{}/audio_query", base_url)).query("text", text).query("speaker", speaker))
+        .await?;
+    if query.status.is_client_error() {
+        return Err(TtsError::Rejected(format!("VoiceVox audio_query failed ({}): {}", query.status, query.text())));
+    }
+    if !query.status.is_success() {
+        return Err(TtsError::Engine(format!("VoiceVox audio_query failed ({}): {}", query.status, query.text())));
+    }
+    // 壊れたクエリをそのまま合成に回さない
+    Ok(serde_json::from_slice(&query.body)?)
+}
+
+/// 読み上げる単位ごとに audio_query を取り、アクセント句をつなげて1つのクエリにする
+/// 強調はその単位の拍の高さ・長さを、間は直前のアクセント句の pause_mora を書き換える
+async fn query_segments(http: &HttpClient, base_url: &str, segments: &[Segment], speaker: &str) -> Result<serde_json::Value, TtsError> {
+    if let [Segment::Text { text, emphasis: false }] = segments {
+        return audio_query(http, base_url, text, speaker).await;
+    }
+
+    let mut merged: Option<serde_json::Value> = None;
+    let mut phrases: Vec<serde_json::Value> = Vec::new();
+    let mut leading_pause = Duration::ZERO;
+    for segment in segments {
+        match segment {
+            Segment::Text { text, emphasis } => {
+                if text.trim().is_empty() {
+                    continue;
+                }
+                let query = audio_query(http, base_url, text, speaker).await?;
+                let mut accent_phrases = query["accent_phrases"].as_array().cloned().unwrap_or_default();
+                if *emphasis {
+                    reading::emphasize(&mut accent_phrases);
+                }
+                phrases.extend(accent_phrases);
+                merged.get_or_insert(query);
+            }
+            Segment::Pause(pause) => match phrases.last_mut() {
+                Some(last) => last["pause_mora"] = reading::pause_mora(*pause),
+                None => leading_pause += *pause,
+            },
+        }
+    }
+
+    let mut query = merged.ok_or_else(|| TtsError::Rejected("nothing to synthesize".to_string()))?;
+    query["accent_phrases"] = serde_json::json!(phrases);
+    if !leading_pause.is_zero() {
+        let pre = query["prePhonemeLength"].as_f64().unwrap_or(0.0);
+        query["prePhonemeLength"] = serde_json::json!(pre + leading_pause.as_secs_f64());
+    }
+    Ok(query)
+}
+
+/// synthesize と同じだが、audio_query の速さ・高さ・抑揚・音量を voice の値にしてから合成する
+/// text のマークアップ（間・強調・読み）を解き、日付・時刻・URL・識別子を読みやすくしてから問い合わせる
+pub async fn synthesize_voice(http: &HttpClient, base_url: &str, text: &str, voice: &VoiceSpec) -> Result<Vec<u8>, TtsError> {
+    let base_url = base_url.trim_end_matches('/');
+    let speaker = voice.speaker.to_string();
+
+    let segments = reading::prepare(text, reading::dictionary());
+    let mut query = query_segments(http, base_url, &segments, &speaker).await?;
+    apply_params(&mut query, &voice.params);
+
+    let audio = http
+        .send(HttpRequest::post(format!("{}/synthesis", base_url)).query("speaker", &speaker).json(&query)?)
+        .await?;
+    if audio.status.is_client_error() {
+        return Err(TtsError::Rejected(format!("VoiceVox synthesis failed ({}): {}", audio.status, audio.text())));
+    }
+    if !audio.status.is_success() {
+        return Err(TtsError::Engine(format!("VoiceVox synthesis failed ({}): {}", audio.status, audio.text())));
+    }
+    if !audio.body.starts_with(b"RIFF") {
+        return Err(TtsError::Engine("VoiceVox synthesis did not return WAV audio".to_string()));
+    }
+    Ok(audio.body)
+}
+
+#[derive(Deserialize)]
+struct UserDictWord {
+    surface: String,
+    pronunciation: String,
+}
+
+/// 読みの辞書を VoiceVox のユーザー辞書に登録する（同じ表記があれば読みを更新する）
+/// 登録・更新した語の数を返す。受け付けられなかった語はログに出して飛ばす
+pub async fn sync_user_dict(http: &HttpClient, base_url: &str, dictionary: &ReadingDictionary) -> Result<usize, TtsError> {
+    let base_url = base_url.trim_end_matches('/');
+    let response = http.send(HttpRequest::get(format!("{}/user_dict", base_url))).await?;
+    if !response.status.is_success() {
+        return Err(TtsError::Engine(format!("VoiceVox user_dict failed ({}): {}", response.status, response.text())));
+    }
+    let registered: HashMap<String, UserDictWord> = serde_json::from_slice(&response.body)?;
+
+    let mut changed = 0;
+    for (surface, reading) in dictionary.entries() {
+        // VoiceVox は表記を全角、読みを片仮名で持つ
+        let pronunciation = reading::to_katakana(reading);
+        let existing = registered.iter().find(|(_, w)| w.surface == reading::to_fullwidth(surface));
+        let request = match existing {
+            Some((_, word)) if word.pronunciation == pronunciation => continue,
+            Some((uuid, _)) => HttpRequest::put(format!("{}/user_dict_word/{}", base_url, uuid)),
+            None => HttpRequest::post(format!("{}/user_dict_word", base_url)),
+        };
+        let request = request.query("surface", surface).query("pronunciation", &pronunciation).query("accent_type", "0");
+
+        let response = http.send(request).await?;
+        if response.status.is_success() {
+            changed += 1;
+        } else {
+            eprintln!("Failed to register reading: {} → {} ({}): {}", surface, reading, response.status, response.text());
+        }
+    }
+    Ok(changed)
+}
+
+#[derive(Deserialize)]
+struct Speaker {
+    name: String,
+    styles: Vec<Style>,
+}
+
+#[derive(Deserialize)]
+struct Style {
+    name: String,
+    id: u32,
+}
+
+/// /speakers の応答を、スタイルごとの声の一覧にする
+pub fn parse_speakers(body: &[u8]) -> Result<Vec<Voice>, TtsError> {
+    let speakers: Vec<Speaker> = serde_json::from_slice(body)?;
+    Ok(speakers
+        .into_iter()
+        .flat_map(|s| {
+            let name = s.name;
+            s.styles.into_iter().map(move |style| Voice { id: style.id, name: name.clone(), style: style.name })
+        })
+        .collect())
+}
+
+/// VoiceVox エンジン
+pub struct VoiceVoxEngine {
+    http: HttpClient,
+    base_url: String,
+}
+
+impl VoiceVoxEngine {
+    pub fn new(http: HttpClient, base_url: &str) -> Self {
+        Self { http, base_url: base_url.trim_end_matches('/').to_string() }
+    }
+
+    /// VOICEVOX_URL と、LLM と共有する HTTP クライアント（記録・再生）で作る
+    pub fn from_env() -> Self {
+        Self::new(tts::shared_http().clone(), &voicevox_url())
+    }
+}
+
+impl TtsEngine for VoiceVoxEngine {
+    fn name(&self) -> &str {
+        "voicevox"
+    }
+
+    fn synthesize<'a>(&'a self, text: &'a str, voice: &'a VoiceSpec) -> TtsFuture<'a, Vec<u8>> {
+        Box::pin(synthesize_voice(&self.http, &self.base_url, text, voice))
+    }
+
+    fn voices(&self) -> TtsFuture<'_, Vec<Voice>> {
+        Box::pin(async move {
+            let response = self.http.send(HttpRequest::get(format!("{}/speakers", self.base_url))).await?;
+            if !response.status.is_success() {
+                return Err(TtsError::Engine(format!("VoiceVox speakers failed ({}): {}", response.status, response.text())));
+            }
+            parse_speakers(&response.body)
+        })
+    }
+
+    // 一覧より軽い /version で、エンジンが起きているかだけ見る
+    fn health_check(&self) -> TtsFuture<'_, ()> {
+        Box::pin(async move {
+            let response = self.http.send(HttpRequest::get(format!("{}/version", self.base_url))).await?;
+            if !response.status.is_success() {
+                return Err(TtsError::Engine(format!("VoiceVox version failed ({})", response.status)));
+            }
+            Ok(())
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_speakers_flattens_styles() {
+        let body = r#"[
+            {"name":"四国めたん","speaker_uuid":"x","styles":[{"name":"ノーマル","id":2},{"name":"あまあま","id":0}],"version":"0.14"},
+            {"name":"春日部つむぎ","speaker_uuid":"y","styles":[{"name":"ノーマル","id":8}],"version":"0.14"}
+        ]"#;
+        let voices = parse_speakers(body.as_bytes()).unwrap();
+        assert_eq!(voices.len(), 3);
+        assert_eq!(voices[1], Voice { id: 0, name: "四国めたん".into(), style: "あまあま".into() });
+        assert_eq!(voices[1].label(), "四国めたん（あまあま）");
+        assert_eq!(voices[2].id, 8);
+    }
+
+    #[test]
+    fn test_apply_params_keeps_unset_fields() {
+        let mut query = serde_json::json!({"accent_phrases": [], "speedScale": 1.0, "pitchScale": 0.0, "volumeScale": 1.0});
+        apply_params(&mut query, &VoiceParams { speed_scale: Some(1.3), volume_scale: Some(1.5), ..Default::default() });
+        assert_eq!(query["speedScale"], 1.3);
+        assert_eq!(query["volumeScale"], 1.5);
+        assert_eq!(query["pitchScale"], 0.0);
+    }
+}
@@ -15,6 +15,8 @@ fn test_cli_add_and_complete_task() {
         .env("TASK_FILE", &task_file)
         .env("MOCK_TTS", "1")
         .env("MOCK_OPENAI", "1")
+        .env("HISTORY_DIR", std::env::temp_dir().join(format!("kotonoha_cli_sessions_{}", Uuid::new_v4())))
+        .env("MOOD_FILE", std::env::temp_dir().join(format!("kotonoha_cli_mood_{}.json", Uuid::new_v4())))
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -0,0 +1,147 @@
+// 結合テスト用の小さな HTTP スタブサーバー
+// 決めた順番でレスポンスを返し、受け取ったリクエストを記録する
+
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+#[derive(Clone)]
+pub struct StubResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub delay: Duration,
+}
+
+impl StubResponse {
+    pub fn json(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".into(), "application/json".into())],
+            body: body.as_bytes().to_vec(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// VoiceVox の合成が返す小さな WAV
+    pub fn wav() -> Self {
+        let mut wav = b"RIFF\x24\x00\x00\x00WAVEfmt ".to_vec();
+        wav.extend_from_slice(&[0xff, 0xfe, 0x00, 0x01]);
+        Self {
+            status: 200,
+            headers: vec![("Content-Type".into(), "audio/wav".into())],
+            body: wav,
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn delayed(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// 受け取ったリクエスト（パス付きの1行目とボディ）
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub request_line: String,
+    pub headers: String,
+    pub body: String,
+}
+
+pub struct StubServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl StubServer {
+    /// responses を順に返す。使い切ったら最後のものを返し続ける
+    pub async fn start(responses: Vec<StubResponse>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+
+        tokio::spawn(async move {
+            let mut index = 0;
+            loop {
+                let Ok((stream, _)) = listener.accept().await else { break };
+                let response = responses[index.min(responses.len() - 1)].clone();
+                index += 1;
+                let recorded = recorded.clone();
+                tokio::spawn(async move { serve(stream, response, recorded).await });
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    pub fn hits(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn serve(mut stream: TcpStream, response: StubResponse, recorded: Arc<Mutex<Vec<RecordedRequest>>>) {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    // ヘッダの終わりまで読む
+    let header_end = loop {
+        let Ok(n) = stream.read(&mut chunk).await else { return };
+        if n == 0 {
+            return;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let content_length = head
+        .lines()
+        .find_map(|l| {
+            let (name, value) = l.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length").then(|| value.trim().parse::<usize>().ok())?
+        })
+        .unwrap_or(0);
+
+    while buf.len() < header_end + content_length {
+        let Ok(n) = stream.read(&mut chunk).await else { return };
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let (request_line, headers) = head.split_once("\r\n").unwrap_or((&head, ""));
+    recorded.lock().unwrap().push(RecordedRequest {
+        request_line: request_line.to_string(),
+        headers: headers.to_string(),
+        body: String::from_utf8_lossy(&buf[header_end..]).to_string(),
+    });
+
+    tokio::time::sleep(response.delay).await;
+
+    let mut out = format!("HTTP/1.1 {} Stub\r\nContent-Length: {}\r\nConnection: close\r\n", response.status, response.body.len());
+    for (name, value) in &response.headers {
+        out.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    out.push_str("\r\n");
+
+    let _ = stream.write_all(out.as_bytes()).await;
+    let _ = stream.write_all(&response.body).await;
+    let _ = stream.shutdown().await;
+}
@@ -0,0 +1,70 @@
+use kotonoha_core::backend::FixtureBackend;
+use kotonoha_core::eval::{self, EvalCase};
+use kotonoha_core::llm::{CircuitBreaker, LlmClient, RetryPolicy};
+use kotonoha_core::prompts;
+use std::path::Path;
+
+fn render(name: &str, input: &str) -> String {
+    prompts::template(name).render(&[("input", input)])
+}
+
+/// データセットの正解どおりに答える記録を作る（wrong に入れた発言だけ分類を間違える）
+fn fixtures_for(cases: &[EvalCase], wrong: &[&str]) -> FixtureBackend {
+    let mut pairs = Vec::new();
+    for case in cases {
+        let kind = if wrong.contains(&case.input.as_str()) {
+            if case.kind == "タスク" { "雑談" } else { "タスク" }
+        } else {
+            case.kind.as_str()
+        };
+        pairs.push((render(prompts::CLASSIFY_INPUT, &case.input), kind.to_string()));
+        if let Some(action) = &case.action {
+            pairs.push((render(prompts::CLASSIFY_TASK_ACTION, &case.input), action.clone()));
+        }
+        if let Some(title) = &case.title {
+            pairs.push((render(prompts::EXTRACT_TASK, &case.input), title.clone()));
+        }
+    }
+    FixtureBackend::from_pairs(pairs)
+}
+
+fn client(fixtures: FixtureBackend) -> LlmClient {
+    LlmClient::with_backend(Box::new(fixtures), RetryPolicy::default(), CircuitBreaker::default())
+}
+
+#[tokio::test]
+async fn dataset_meets_baseline_with_recorded_answers() {
+    let cases = eval::load_dataset(Path::new(eval::DEFAULT_DATASET)).unwrap();
+    let baseline = eval::load_baseline(Path::new(eval::DEFAULT_BASELINE)).unwrap();
+    assert!(cases.len() >= 20);
+
+    let report = eval::run(&client(fixtures_for(&cases, &[])), &cases).await;
+    assert!(report.errors.is_empty(), "{:?}", report.errors);
+    assert!(report.regressions(&baseline).is_empty(), "{}", report.describe());
+    assert_eq!(report.classify.accuracy(), 1.0);
+    assert!(report.scores().contains_key("extract_task"));
+}
+
+#[tokio::test]
+async fn misclassifications_show_up_in_matrix_and_fail_baseline() {
+    let cases = eval::load_dataset(Path::new(eval::DEFAULT_DATASET)).unwrap();
+    let baseline = eval::load_baseline(Path::new(eval::DEFAULT_BASELINE)).unwrap();
+    let wrong = ["こんにちは", "今日はちょっと疲れたな", "おすすめの映画ってある？", "牛乳を買うのは終わった"];
+
+    let report = eval::run(&client(fixtures_for(&cases, &wrong)), &cases).await;
+    assert_eq!(report.classify.count("雑談", "タスク"), 3);
+    assert_eq!(report.classify.count("タスク", "雑談"), 1);
+    assert!(report.classify.precision("タスク").unwrap() < 1.0);
+    assert!(report.classify.recall("雑談").unwrap() < 1.0);
+    assert_eq!(report.regressions(&baseline).len(), 1);
+    assert!(report.describe().contains("分類 雑談 → タスク"));
+}
+
+#[tokio::test]
+async fn missing_fixture_is_reported_as_error() {
+    let cases = vec![EvalCase { input: "記録にない発言".into(), kind: "雑談".into(), action: None, title: None }];
+    let report = eval::run(&client(FixtureBackend::default()), &cases).await;
+
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.classify.accuracy(), 0.0);
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::{StubResponse, StubServer};
+use kotonoha_core::backend::OpenAiBackend;
+use kotonoha_core::chat;
+use kotonoha_core::llm::{CircuitBreaker, LlmClient, LlmError, RetryPolicy};
+use kotonoha_core::models::{ChatMessage, ChatRequest};
+use kotonoha_core::transport::{Cassette, HttpClient};
+use kotonoha_core::tts;
+use kotonoha_core::usage::CallSite;
+use std::path::Path;
+use std::sync::Arc;
+
+// 再生中はどこにも繋がらないはずなので、到達できないアドレスを渡す
+const NOWHERE: &str = "http://127.0.0.1:9";
+
+const FIXTURE: &str = "tests/fixtures/openai_voicevox.json";
+
+fn client(http: HttpClient, base_url: &str) -> LlmClient {
+    let backend = OpenAiBackend::with_http(http, "test-key", base_url);
+    LlmClient::with_backend(Box::new(backend), RetryPolicy::default(), CircuitBreaker::default())
+}
+
+fn request(text: &str) -> ChatRequest {
+    ChatRequest {
+        model: "gpt-3.5-turbo".into(),
+        messages: vec![ChatMessage::new("user", text)],
+        tools: None,
+    }
+}
+
+#[tokio::test]
+async fn replays_recorded_openai_and_voicevox_exchanges() {
+    let cassette = Arc::new(Cassette::replay(Path::new(FIXTURE)).unwrap());
+    let http = HttpClient::with_cassette(cassette.clone());
+    let llm = client(http.clone(), &format!("{}/v1", NOWHERE));
+
+    // 本物と同じ JSON（refusal や logprobs などの余分な項目付き）を読めること
+    assert_eq!(chat::classify_input(&llm, "牛乳を買う").await.unwrap(), "タスク");
+
+    // content が null でツール呼び出しだけの応答
+    let response = llm.send_chat(CallSite::Chat, &request("牛乳のタスクある？")).await.unwrap();
+    let message = &response.choices[0].message;
+    assert_eq!(message.content, "");
+    assert_eq!(message.tool_calls.as_ref().unwrap()[0].function.name, "search_tasks");
+    assert_eq!(response.usage.unwrap().total_tokens, 328);
+
+    let audio = tts::synthesize(&http, NOWHERE, "こんにちは", 8).await.unwrap();
+    assert!(audio.starts_with(b"RIFF"));
+    assert_eq!(cassette.remaining(), 0);
+}
+
+#[tokio::test]
+async fn replay_rejects_requests_that_were_not_recorded() {
+    let http = HttpClient::with_cassette(Arc::new(Cassette::replay(Path::new(FIXTURE)).unwrap()));
+
+    // 最初の記録は chat なので、VoiceVox への呼び出しは食い違いになる
+    let err = tts::synthesize(&http, NOWHERE, "こんにちは", 8).await.unwrap_err();
+    assert!(err.to_string().contains("fixture"));
+
+    let llm = client(http, &format!("{}/v1", NOWHERE));
+    llm.send_chat(CallSite::Chat, &request("a")).await.unwrap();
+    llm.send_chat(CallSite::Chat, &request("b")).await.unwrap();
+    let err = llm.send_chat(CallSite::Chat, &request("c")).await.unwrap_err();
+    assert!(matches!(err, LlmError::Fixture(_)));
+}
+
+#[tokio::test]
+async fn records_then_replays_offline() {
+    let path = std::env::temp_dir().join(format!("http_record_{}.json", uuid::Uuid::new_v4()));
+    let openai = StubServer::start(vec![StubResponse::json(
+        200,
+        r#"{"choices":[{"message":{"role":"assistant","content":"雑談"}}],"usage":{"prompt_tokens":10,"completion_tokens":1,"total_tokens":11}}"#,
+    )])
+    .await;
+    let voicevox = StubServer::start(vec![StubResponse::json(200, r#"{"accent_phrases":[],"speedScale":1.0}"#), StubResponse::wav()]).await;
+
+    let recorder = HttpClient::with_cassette(Arc::new(Cassette::record(&path)));
+    let recorded_kind = chat::classify_input(&client(recorder.clone(), &openai.url), "こんにちは").await.unwrap();
+    let recorded_audio = tts::synthesize(&recorder, &voicevox.url, "こんにちは", 3).await.unwrap();
+
+    // 認証ヘッダーは記録に残さない
+    let saved = std::fs::read_to_string(&path).unwrap();
+    assert!(!saved.contains("test-key"));
+
+    let replayer = HttpClient::with_cassette(Arc::new(Cassette::replay(&path).unwrap()));
+    assert_eq!(chat::classify_input(&client(replayer.clone(), NOWHERE), "こんにちは").await.unwrap(), recorded_kind);
+    assert_eq!(tts::synthesize(&replayer, NOWHERE, "こんにちは", 3).await.unwrap(), recorded_audio);
+
+    // audio_query の結果がそのまま synthesis に渡されている
+    assert!(voicevox.requests()[1].body.contains("speedScale"));
+    assert_eq!(openai.hits(), 1);
+    let _ = std::fs::remove_file(&path);
+}
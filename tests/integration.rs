@@ -30,7 +30,7 @@ fn test_add_and_mark_task_flow() {
     // 2. 読み込んでチェック
     let loaded = tasks::load_tasks_with_file(std::path::Path::new(TEST_FILE));
     assert_eq!(loaded.len(), 1);
-    assert_eq!(loaded[0].done, false);
+    assert!(!loaded[0].done);
 
     // 3. 完了にして保存
     let mut updated = loaded;
@@ -0,0 +1,127 @@
+mod common;
+
+use common::{StubResponse, StubServer};
+use kotonoha_core::chat;
+use kotonoha_core::cache::ResponseCache;
+use kotonoha_core::llm::{CircuitBreaker, LlmClient, LlmError, RetryPolicy};
+use kotonoha_core::models::{ChatMessage, ChatRequest};
+use kotonoha_core::usage::{CallSite, UsageLedger};
+use std::time::{Duration, Instant};
+
+const OK_BODY: &str = r#"{"choices":[{"message":{"role":"assistant","content":"雑談"}}]}"#;
+
+fn fast_policy() -> RetryPolicy {
+    RetryPolicy {
+        timeout: Duration::from_secs(5),
+        max_retries: 3,
+        base_delay: Duration::from_millis(10),
+        max_delay: Duration::from_secs(2),
+    }
+}
+
+fn request() -> ChatRequest {
+    ChatRequest {
+        model: "gpt-3.5-turbo".into(),
+        messages: vec![ChatMessage::new("user", "こんにちは")],
+        tools: None,
+    }
+}
+
+#[tokio::test]
+async fn retries_server_errors_then_succeeds() {
+    let server = StubServer::start(vec![
+        StubResponse::json(503, r#"{"error":"overloaded"}"#),
+        StubResponse::json(500, r#"{"error":"oops"}"#),
+        StubResponse::json(200, OK_BODY),
+    ])
+    .await;
+    let llm = LlmClient::new("test", &server.url, fast_policy(), CircuitBreaker::default());
+
+    let response = llm.send_chat(CallSite::Chat, &request()).await.expect("3回目で成功するはず");
+    assert_eq!(response.choices[0].message.content, "雑談");
+    assert_eq!(server.hits(), 3);
+}
+
+#[tokio::test]
+async fn honors_retry_after_on_429() {
+    let server = StubResponse::json(429, r#"{"error":"rate limited"}"#).header("Retry-After", "1");
+    let server = StubServer::start(vec![server, StubResponse::json(200, OK_BODY)]).await;
+    let llm = LlmClient::new("test", &server.url, fast_policy(), CircuitBreaker::default());
+
+    let started = Instant::now();
+    llm.send_chat(CallSite::Chat, &request()).await.unwrap();
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_eq!(server.hits(), 2);
+}
+
+#[tokio::test]
+async fn times_out_slow_responses() {
+    let server = StubServer::start(vec![StubResponse::json(200, OK_BODY).delayed(Duration::from_millis(500))]).await;
+    let policy = RetryPolicy { timeout: Duration::from_millis(100), max_retries: 0, ..fast_policy() };
+    let llm = LlmClient::new("test", &server.url, policy, CircuitBreaker::default());
+
+    let err = llm.send_chat(CallSite::Chat, &request()).await.unwrap_err();
+    assert!(matches!(err, LlmError::Timeout), "{:?}", err);
+}
+
+#[tokio::test]
+async fn does_not_retry_auth_errors() {
+    let server = StubServer::start(vec![StubResponse::json(401, r#"{"error":"bad key"}"#)]).await;
+    let llm = LlmClient::new("test", &server.url, fast_policy(), CircuitBreaker::default());
+
+    let err = llm.send_chat(CallSite::Chat, &request()).await.unwrap_err();
+    assert!(matches!(err, LlmError::Http { .. }));
+    assert_eq!(server.hits(), 1);
+    assert!(llm.is_available());
+}
+
+#[tokio::test]
+async fn breaker_switches_to_offline_classifier() {
+    let server = StubServer::start(vec![StubResponse::json(500, r#"{"error":"down"}"#)]).await;
+    let policy = RetryPolicy { max_retries: 0, ..fast_policy() };
+    let llm = LlmClient::new("test", &server.url, policy, CircuitBreaker::new(2, Duration::from_secs(60)));
+
+    assert!(chat::classify_input(&llm, "タスク一覧を見せて").await.is_err());
+    assert!(chat::classify_input(&llm, "タスク一覧を見せて").await.is_err());
+    assert!(!llm.is_available());
+
+    // ブレーカーが開いたらサーバーには行かずにオフラインで分類する
+    let mode = chat::classify_input(&llm, "タスク一覧を見せて").await.unwrap();
+    assert_eq!(mode, "タスク");
+    assert_eq!(server.hits(), 2);
+}
+
+#[tokio::test]
+async fn records_usage_and_downgrades_over_budget() {
+    let body = r#"{"choices":[{"message":{"role":"assistant","content":"雑談"}}],"usage":{"prompt_tokens":1000000,"completion_tokens":0,"total_tokens":1000000}}"#;
+    let server = StubServer::start(vec![StubResponse::json(200, body)]).await;
+    let path = std::env::temp_dir().join(format!("usage_resilience_{}.jsonl", uuid::Uuid::new_v4()));
+    let llm = LlmClient::new("test", &server.url, fast_policy(), CircuitBreaker::default())
+        .with_usage(UsageLedger::new(&path), Some(0.25));
+
+    assert_eq!(llm.model_for("gpt-3.5-turbo"), "gpt-3.5-turbo");
+    assert_eq!(chat::classify_input(&llm, "こんにちは").await.unwrap(), "雑談");
+
+    // 1Mトークン × $0.5 で予算 $0.25 を超える
+    let summary = llm.usage_summary().unwrap();
+    assert_eq!(summary.per_site[&CallSite::Classify].calls, 1);
+    assert!(llm.over_budget());
+    assert_eq!(llm.model_for("gpt-3.5-turbo"), "gpt-4o-mini");
+
+    // 予算超過中の分類はオフラインで行う
+    chat::classify_input(&llm, "タスク一覧").await.unwrap();
+    assert_eq!(server.hits(), 1);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn cached_classification_skips_the_network() {
+    let server = StubServer::start(vec![StubResponse::json(200, OK_BODY)]).await;
+    let llm = LlmClient::new("test", &server.url, fast_policy(), CircuitBreaker::default())
+        .with_cache(ResponseCache::new(None, chrono::Duration::hours(1), 10));
+
+    assert_eq!(chat::classify_input(&llm, "今日は暑いね").await.unwrap(), "雑談");
+    assert_eq!(chat::classify_input(&llm, "今日は暑いね。").await.unwrap(), "雑談");
+    assert_eq!(server.hits(), 1);
+}
@@ -0,0 +1,263 @@
+mod common;
+
+use common::{StubResponse, StubServer};
+use kotonoha_core::llm::{CircuitBreaker, LlmClient, LlmError, RetryPolicy};
+use kotonoha_core::models::{ChatMessage, ChatRequest};
+use kotonoha_core::providers::{Provider, ProviderConfig};
+use kotonoha_core::tools;
+use kotonoha_core::transport::HttpClient;
+use kotonoha_core::usage::CallSite;
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::time::Duration;
+
+fn no_retry() -> RetryPolicy {
+    RetryPolicy {
+        timeout: Duration::from_secs(5),
+        max_retries: 0,
+        base_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(10),
+    }
+}
+
+fn client(config: &ProviderConfig) -> LlmClient {
+    LlmClient::with_backend(config.backend(HttpClient::live()), no_retry(), CircuitBreaker::default())
+        .with_models(config.model.clone(), config.provider.cheap_model())
+}
+
+fn request(llm: &LlmClient) -> ChatRequest {
+    ChatRequest {
+        model: llm.model_for("gpt-3.5-turbo"),
+        messages: vec![ChatMessage::new("system", "あなたは秘書のことのはです。"), ChatMessage::new("user", "こんにちは")],
+        tools: Some(tools::definitions()),
+    }
+}
+
+fn sse(body: &str) -> StubResponse {
+    let mut response = StubResponse::json(200, body);
+    response.headers = vec![("Content-Type".into(), "text/event-stream".into())];
+    response
+}
+
+fn sent_json(server: &StubServer) -> Value {
+    serde_json::from_str(&server.requests()[0].body).unwrap()
+}
+
+// ---- Azure OpenAI ----
+
+fn azure(server: &StubServer) -> ProviderConfig {
+    let mut config = ProviderConfig::new(Provider::Azure, "azure-key", &server.url);
+    config.azure_deployment = "kotonoha-gpt35".into();
+    config
+}
+
+#[tokio::test]
+async fn azure_uses_deployment_url_and_api_key_header() {
+    let server = StubServer::start(vec![StubResponse::json(
+        200,
+        r#"{"choices":[{"index":0,"message":{"role":"assistant","content":"こんにちは！"},"finish_reason":"stop","content_filter_results":{}}],"prompt_filter_results":[],"usage":{"prompt_tokens":20,"completion_tokens":5,"total_tokens":25}}"#,
+    )])
+    .await;
+    let llm = client(&azure(&server));
+
+    let response = llm.send_chat(CallSite::Chat, &request(&llm)).await.unwrap();
+    assert_eq!(response.choices[0].message.content, "こんにちは！");
+
+    let sent = &server.requests()[0];
+    assert!(sent.request_line.starts_with("POST /openai/deployments/kotonoha-gpt35/chat/completions?api-version="));
+    assert!(sent.headers.to_lowercase().contains("api-key: azure-key"));
+    assert!(!sent.headers.to_lowercase().contains("authorization"));
+}
+
+#[tokio::test]
+async fn azure_error_body_is_unwrapped() {
+    let server = StubServer::start(vec![StubResponse::json(
+        404,
+        r#"{"error":{"code":"DeploymentNotFound","message":"The API deployment for this resource does not exist."}}"#,
+    )])
+    .await;
+    let llm = client(&azure(&server));
+
+    match llm.send_chat(CallSite::Chat, &request(&llm)).await.unwrap_err() {
+        LlmError::Http { status, body } => {
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(body, "The API deployment for this resource does not exist.");
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn openai_stream_is_assembled() {
+    let server = StubServer::start(vec![sse(concat!(
+        "data: {\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"\"}}]}\n\n",
+        "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"こんに\"}}]}\n\n",
+        "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"ちは\"}}]}\n\n",
+        "data: {\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"type\":\"function\",\"function\":{\"name\":\"search_tasks\",\"arguments\":\"{\\\"query\\\"\"}}]}}]}\n\n",
+        "data: {\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\":\\\"牛乳\\\"}\"}}]}}]}\n\n",
+        "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":30,\"completion_tokens\":7,\"total_tokens\":37}}\n\n",
+        "data: [DONE]\n\n",
+    ))])
+    .await;
+    let mut config = ProviderConfig::new(Provider::OpenAi, "sk-test", &server.url);
+    config.stream = true;
+    let llm = client(&config);
+
+    let response = llm.send_chat(CallSite::Chat, &request(&llm)).await.unwrap();
+    let message = &response.choices[0].message;
+    assert_eq!(message.content, "こんにちは");
+    assert_eq!(message.tool_calls.as_ref().unwrap()[0].function.arguments, r#"{"query":"牛乳"}"#);
+    assert_eq!(response.usage.unwrap().total_tokens, 37);
+    assert_eq!(sent_json(&server)["stream_options"]["include_usage"], true);
+}
+
+// ---- Anthropic ----
+
+#[tokio::test]
+async fn anthropic_moves_system_prompt_and_reads_content_blocks() {
+    let server = StubServer::start(vec![StubResponse::json(
+        200,
+        r#"{"id":"msg_01","type":"message","role":"assistant","model":"claude-3-5-haiku-20241022","content":[{"type":"text","text":"探してみますね。"},{"type":"tool_use","id":"toolu_01","name":"search_tasks","input":{"query":"牛乳"}}],"stop_reason":"tool_use","stop_sequence":null,"usage":{"input_tokens":120,"output_tokens":40}}"#,
+    )])
+    .await;
+    let llm = client(&ProviderConfig::new(Provider::Anthropic, "ant-key", &server.url));
+
+    let response = llm.send_chat(CallSite::Chat, &request(&llm)).await.unwrap();
+    let message = &response.choices[0].message;
+    assert_eq!(message.content, "探してみますね。");
+    let call = &message.tool_calls.as_ref().unwrap()[0];
+    assert_eq!((call.id.as_str(), call.function.name.as_str()), ("toolu_01", "search_tasks"));
+    assert_eq!(serde_json::from_str::<Value>(&call.function.arguments).unwrap()["query"], "牛乳");
+    assert_eq!(response.usage.unwrap().total_tokens, 160);
+
+    let sent = &server.requests()[0];
+    assert!(sent.request_line.starts_with("POST /v1/messages "));
+    let headers = sent.headers.to_lowercase();
+    assert!(headers.contains("x-api-key: ant-key"));
+    assert!(headers.contains("anthropic-version: 2023-06-01"));
+
+    let body = sent_json(&server);
+    assert_eq!(body["model"], "claude-3-5-haiku-latest");
+    assert_eq!(body["system"], "あなたは秘書のことのはです。");
+    assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+    assert_eq!(body["messages"][0]["role"], "user");
+    assert!(body["max_tokens"].as_u64().is_some());
+    assert!(body["tools"][0]["input_schema"].is_object());
+}
+
+#[tokio::test]
+async fn anthropic_overloaded_error_is_retryable() {
+    let server = StubServer::start(vec![StubResponse::json(
+        529,
+        r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+    )])
+    .await;
+    let llm = client(&ProviderConfig::new(Provider::Anthropic, "ant-key", &server.url));
+
+    let err = llm.send_chat(CallSite::Chat, &request(&llm)).await.unwrap_err();
+    assert!(err.is_retryable());
+    assert!(matches!(err, LlmError::Http { ref body, .. } if body == "Overloaded"));
+}
+
+#[tokio::test]
+async fn anthropic_stream_is_assembled() {
+    let server = StubServer::start(vec![sse(concat!(
+        "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_01\",\"content\":[],\"usage\":{\"input_tokens\":25,\"output_tokens\":1}}}\n\n",
+        "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+        "event: ping\ndata: {\"type\":\"ping\"}\n\n",
+        "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"こんに\"}}\n\n",
+        "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"ちは\"}}\n\n",
+        "event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":0}\n\n",
+        "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":1,\"content_block\":{\"type\":\"tool_use\",\"id\":\"toolu_01\",\"name\":\"list_tasks\",\"input\":{}}}\n\n",
+        "event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":1}\n\n",
+        "event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"tool_use\"},\"usage\":{\"output_tokens\":15}}\n\n",
+        "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n",
+    ))])
+    .await;
+    let mut config = ProviderConfig::new(Provider::Anthropic, "ant-key", &server.url);
+    config.stream = true;
+    let llm = client(&config);
+
+    let response = llm.send_chat(CallSite::Chat, &request(&llm)).await.unwrap();
+    let message = &response.choices[0].message;
+    assert_eq!(message.content, "こんにちは");
+    assert_eq!(message.tool_calls.as_ref().unwrap()[0].function.arguments, "{}");
+    assert_eq!(response.usage.unwrap().total_tokens, 40);
+    assert_eq!(sent_json(&server)["stream"], true);
+}
+
+// ---- Gemini ----
+
+#[tokio::test]
+async fn gemini_uses_system_instruction_and_model_role() {
+    let server = StubServer::start(vec![StubResponse::json(
+        200,
+        r#"{"candidates":[{"content":{"parts":[{"text":"こんにちは！"}],"role":"model"},"finishReason":"STOP","index":0,"safetyRatings":[]}],"usageMetadata":{"promptTokenCount":18,"candidatesTokenCount":4,"totalTokenCount":22},"modelVersion":"gemini-1.5-flash"}"#,
+    )])
+    .await;
+    let llm = client(&ProviderConfig::new(Provider::Gemini, "g-key", &server.url));
+
+    let mut req = request(&llm);
+    req.messages.push(ChatMessage::new("assistant", "はい"));
+    req.messages.push(ChatMessage::new("user", "元気？"));
+    let response = llm.send_chat(CallSite::Chat, &req).await.unwrap();
+    assert_eq!(response.choices[0].message.content, "こんにちは！");
+    assert_eq!(response.usage.unwrap().total_tokens, 22);
+
+    let sent = &server.requests()[0];
+    assert!(sent.request_line.starts_with("POST /v1beta/models/gemini-1.5-flash:generateContent "));
+    assert!(sent.headers.to_lowercase().contains("x-goog-api-key: g-key"));
+
+    let body = sent_json(&server);
+    assert_eq!(body["systemInstruction"]["parts"][0]["text"], "あなたは秘書のことのはです。");
+    let roles: Vec<&str> = body["contents"].as_array().unwrap().iter().map(|c| c["role"].as_str().unwrap()).collect();
+    assert_eq!(roles, vec!["user", "model", "user"]);
+
+    // 引数の無い関数（list_tasks）には parameters を付けない
+    let declarations = body["tools"][0]["functionDeclarations"].as_array().unwrap();
+    let list = declarations.iter().find(|d| d["name"] == "list_tasks").unwrap();
+    assert!(list.get("parameters").is_none());
+}
+
+#[tokio::test]
+async fn gemini_function_calls_and_errors() {
+    let server = StubServer::start(vec![
+        StubResponse::json(
+            200,
+            r#"{"candidates":[{"content":{"parts":[{"functionCall":{"name":"complete_task","args":{"task_id":3}}}],"role":"model"},"finishReason":"STOP"}]}"#,
+        ),
+        StubResponse::json(429, r#"{"error":{"code":429,"message":"Resource has been exhausted (e.g. check quota).","status":"RESOURCE_EXHAUSTED"}}"#),
+        StubResponse::json(200, r#"{"promptFeedback":{"blockReason":"SAFETY"}}"#),
+    ])
+    .await;
+    let llm = client(&ProviderConfig::new(Provider::Gemini, "g-key", &server.url));
+
+    let response = llm.send_chat(CallSite::Chat, &request(&llm)).await.unwrap();
+    let call = &response.choices[0].message.tool_calls.as_ref().unwrap()[0];
+    assert_eq!(call.function.name, "complete_task");
+    assert_eq!(call.function.arguments, r#"{"task_id":3}"#);
+    assert!(!call.id.is_empty());
+
+    let err = llm.send_chat(CallSite::Chat, &request(&llm)).await.unwrap_err();
+    assert!(matches!(err, LlmError::Http { status: StatusCode::TOO_MANY_REQUESTS, ref body } if body.starts_with("Resource has been exhausted")));
+
+    let err = llm.send_chat(CallSite::Chat, &request(&llm)).await.unwrap_err();
+    assert!(matches!(err, LlmError::Parse(ref e) if e.contains("SAFETY")));
+}
+
+#[tokio::test]
+async fn gemini_stream_is_assembled() {
+    let server = StubServer::start(vec![sse(concat!(
+        "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"こんに\"}],\"role\":\"model\"}}]}\r\n\r\n",
+        "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"ちは\"}],\"role\":\"model\"},\"finishReason\":\"STOP\"}],\"usageMetadata\":{\"promptTokenCount\":10,\"candidatesTokenCount\":3,\"totalTokenCount\":13}}\r\n\r\n",
+    ))])
+    .await;
+    let mut config = ProviderConfig::new(Provider::Gemini, "g-key", &server.url);
+    config.stream = true;
+    let llm = client(&config);
+
+    let response = llm.send_chat(CallSite::Chat, &request(&llm)).await.unwrap();
+    assert_eq!(response.choices[0].message.content, "こんにちは");
+    assert_eq!(response.usage.unwrap().total_tokens, 13);
+    assert!(server.requests()[0].request_line.contains(":streamGenerateContent?alt=sse"));
+}
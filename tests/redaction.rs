@@ -0,0 +1,51 @@
+mod common;
+
+use common::{StubResponse, StubServer};
+use kotonoha_core::chat;
+use kotonoha_core::llm::{CircuitBreaker, LlmClient, RetryPolicy};
+use kotonoha_core::models::{ChatMessage, ChatRequest};
+use kotonoha_core::redact::Redactor;
+use kotonoha_core::usage::CallSite;
+
+fn client(url: &str) -> LlmClient {
+    LlmClient::new("test", url, RetryPolicy::default(), CircuitBreaker::default())
+        .with_redactor(Some(Redactor::new(vec!["佐藤".into()])))
+}
+
+#[tokio::test]
+async fn pii_never_leaves_and_comes_back_in_titles() {
+    let server = StubServer::start(vec![StubResponse::json(
+        200,
+        r#"{"choices":[{"message":{"role":"assistant","content":"[NAME_1]さん（[TEL_1]）に折り返す"}}]}"#,
+    )])
+    .await;
+    let llm = client(&server.url);
+
+    let title = chat::extract_task(&llm, "佐藤さん（090-1234-5678）に折り返し電話しないと").await.unwrap();
+    assert_eq!(title, "佐藤さん（090-1234-5678）に折り返す");
+
+    let sent = &server.requests()[0].body;
+    assert!(!sent.contains("090-1234-5678"));
+    assert!(!sent.contains("佐藤"));
+    assert!(sent.contains("[TEL_1]"));
+}
+
+#[tokio::test]
+async fn tool_arguments_are_restored() {
+    let server = StubServer::start(vec![StubResponse::json(
+        200,
+        r#"{"choices":[{"message":{"role":"assistant","content":null,"tool_calls":[{"id":"call_1","type":"function","function":{"name":"add_task","arguments":"{\"title\":\"[EMAIL_1] に返信\"}"}}]}}]}"#,
+    )])
+    .await;
+    let llm = client(&server.url);
+    let request = ChatRequest {
+        model: "gpt-3.5-turbo".into(),
+        messages: vec![ChatMessage::new("user", "hanako@example.com に返信するタスクを追加して")],
+        tools: None,
+    };
+
+    let response = llm.send_chat(CallSite::Chat, &request).await.unwrap();
+    let call = &response.choices[0].message.tool_calls.as_ref().unwrap()[0];
+    assert_eq!(call.function.arguments, r#"{"title":"hanako@example.com に返信"}"#);
+    assert!(!server.requests()[0].body.contains("hanako@"));
+}
@@ -1,19 +1,38 @@
 use std::time::Duration;
 
-use kotonoha_core::speech::{SpeechKind, SpeechQueue};
-use kotonoha_core::tts;
+use kotonoha_core::speech::{DropReason, KindCounts, ShutdownMode, SpeechEvent, SpeechKind, SpeechPolicy, SpeechQueue};
+use kotonoha_core::playback::{FakeSink, Player, SinkEvent};
+use kotonoha_core::quiet::QuietSchedule;
+use kotonoha_core::tts::{self, MockEngine, TtsEngine, TtsFuture, Voice, VoiceConfig, VoiceSpec};
 
-use std::sync::{Mutex, OnceLock};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{Mutex, MutexGuard};
 
-fn test_lock() -> std::sync::MutexGuard<'static, ()> {
+// await をまたいで保持するので tokio の Mutex を使う
+async fn test_lock() -> MutexGuard<'static, ()> {
     static LOCK: OnceLock<Mutex<()>> = OnceLock::new();
-    LOCK.get_or_init(|| Mutex::new(())).lock().unwrap()
+    LOCK.get_or_init(|| Mutex::new(())).lock().await
 }
 
+// 頼んだ発話がすべて片付くまで待つ
+async fn flushed(speech: &SpeechQueue) {
+    tokio::time::timeout(Duration::from_secs(2), speech.flush()).await.expect("flush did not resolve");
+}
+
+// 再生の記録が done を満たすまで待つ（割り込む前に、確かに鳴り始めているようにする）
+async fn until(events: &Arc<std::sync::Mutex<Vec<SinkEvent>>>, done: impl Fn(&[SinkEvent]) -> bool) {
+    tokio::time::timeout(Duration::from_secs(2), async {
+        while !done(&events.lock().unwrap()) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("playback did not reach the expected state");
+}
 
 #[tokio::test]
 async fn user_has_priority_over_monologue() {
-    let _g = test_lock();        // ←追加：このスコープ中は他テストが入れない
+    let _g = test_lock().await;        // ←追加：このスコープ中は他テストが入れない
     let _ = tts::take_spoken();  // ←追加：前のテストの残りをクリア
 
     // 実音を鳴らさない
@@ -26,8 +45,8 @@ async fn user_has_priority_over_monologue() {
     speech.say(SpeechKind::Monologue, "mono").await;
     speech.say(SpeechKind::User, "user").await;
 
-    // 非同期ワーカーが処理する時間を少し与える
-    tokio::time::sleep(Duration::from_millis(50)).await;
+    // 非同期ワーカーが喋り終えるまで待つ
+    flushed(&speech).await;
 
     let spoken = tts::take_spoken();
     assert!(!spoken.is_empty(), "nothing was spoken");
@@ -36,7 +55,7 @@ async fn user_has_priority_over_monologue() {
 
 #[tokio::test]
 async fn alert_has_priority_over_monologue() {
-    let _g = test_lock();        // ←追加：このスコープ中は他テストが入れない
+    let _g = test_lock().await;        // ←追加：このスコープ中は他テストが入れない
     let _ = tts::take_spoken();  // ←追加：前のテストの残りをクリア
 
     tts::enable_mock_mode();
@@ -46,9 +65,325 @@ async fn alert_has_priority_over_monologue() {
     speech.say(SpeechKind::Monologue, "mono").await;
     speech.say(SpeechKind::Alert, "alert").await;
 
-    tokio::time::sleep(Duration::from_millis(50)).await;
+    flushed(&speech).await;
 
     let spoken = tts::take_spoken();
     assert!(!spoken.is_empty(), "nothing was spoken");
     assert_eq!(spoken[0], "alert");
 }
+
+#[tokio::test]
+async fn gentle_mode_skips_monologue() {
+    let _g = test_lock().await;
+    let _ = tts::take_spoken();
+
+    tts::enable_mock_mode();
+
+    let speech = SpeechQueue::spawn(Duration::from_secs(0), Duration::from_secs(0));
+    speech.set_gentle(true).await;
+
+    speech.say(SpeechKind::Monologue, "mono").await;
+    speech.say(SpeechKind::Alert, "alert").await;
+
+    flushed(&speech).await;
+
+    assert_eq!(tts::take_spoken(), vec!["alert".to_string()]);
+    assert!(speech.is_gentle().await);
+}
+
+#[tokio::test]
+async fn injected_engine_receives_the_speech() {
+    let engine = Arc::new(MockEngine::new());
+    let speech = SpeechQueue::with_engine(engine.clone(), Duration::from_secs(0), Duration::from_secs(0));
+
+    speech.say(SpeechKind::User, "こんにちは").await;
+    flushed(&speech).await;
+
+    assert_eq!(engine.take_spoken(), vec!["こんにちは".to_string()]);
+}
+
+// 文をそのまま「音」として返す（FakeSink の記録から何を鳴らしたか分かる）
+struct EchoEngine;
+
+impl TtsEngine for EchoEngine {
+    fn name(&self) -> &str {
+        "echo"
+    }
+
+    fn synthesize<'a>(&'a self, text: &'a str, _voice: &'a VoiceSpec) -> TtsFuture<'a, Vec<u8>> {
+        Box::pin(async move { Ok(text.as_bytes().to_vec()) })
+    }
+
+    fn voices(&self) -> TtsFuture<'_, Vec<Voice>> {
+        Box::pin(async { Ok(vec![]) })
+    }
+}
+
+fn played(text: &str) -> SinkEvent {
+    SinkEvent::Play(text.as_bytes().to_vec())
+}
+
+#[tokio::test]
+async fn user_reply_interrupts_monologue_and_alert() {
+    let sink = FakeSink::new(Duration::from_millis(150));
+    let events = sink.events();
+    let speech = SpeechQueue::with_player(
+        Arc::new(EchoEngine),
+        VoiceConfig::default(),
+        Player::with_sink(sink),
+        Duration::from_secs(0),
+        Duration::from_secs(0),
+    );
+
+    speech.say_monologue("mono").await;
+    until(&events, |e| e.contains(&played("mono"))).await;
+    speech.say_user("reply").await;
+    flushed(&speech).await;
+
+    speech.say_alert("alert").await;
+    until(&events, |e| e.contains(&played("alert"))).await;
+    speech.say_user("reply2").await;
+    // 応答のあとで通知を言い直す
+    flushed(&speech).await;
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            played("mono"),
+            SinkEvent::Stop,
+            played("reply"),
+            played("alert"),
+            SinkEvent::Stop,
+            played("reply2"),
+            played("alert"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn user_replies_are_not_interrupted() {
+    let sink = FakeSink::new(Duration::from_millis(100));
+    let events = sink.events();
+    let speech = SpeechQueue::with_player(
+        Arc::new(EchoEngine),
+        VoiceConfig::default(),
+        Player::with_sink(sink),
+        Duration::from_secs(0),
+        Duration::from_secs(0),
+    );
+
+    speech.say_user("first").await;
+    until(&events, |e| e.contains(&played("first"))).await;
+    speech.say_user("second").await;
+    flushed(&speech).await;
+
+    assert_eq!(*events.lock().unwrap(), vec![played("first"), played("second")]);
+}
+
+fn fake_queue(clip: Duration) -> (SpeechQueue, Arc<std::sync::Mutex<Vec<SinkEvent>>>) {
+    let sink = FakeSink::new(clip);
+    let events = sink.events();
+    let speech = SpeechQueue::with_player(
+        Arc::new(EchoEngine),
+        VoiceConfig::default(),
+        Player::with_sink(sink),
+        Duration::from_secs(0),
+        Duration::from_secs(0),
+    );
+    (speech, events)
+}
+
+#[tokio::test]
+async fn pending_alerts_are_deduplicated_and_coalesced() {
+    let (speech, events) = fake_queue(Duration::from_millis(100));
+
+    speech.say_monologue("mono").await;
+    until(&events, |e| e.contains(&played("mono"))).await;
+    speech.say_alert("「A」の期限は今日です。").await;
+    speech.say_alert("「B」の期限は明日です").await;
+    speech.say_alert("「A」の期限は今日です。").await;
+    flushed(&speech).await;
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![played("mono"), played("お知らせが2件あります。「A」の期限は今日です。「B」の期限は明日です。")]
+    );
+    let stats = speech.stats().await;
+    assert_eq!(stats.dropped(SpeechKind::Alert, DropReason::Duplicate), 1);
+    assert_eq!(stats.coalesced, 2);
+}
+
+#[tokio::test]
+async fn stale_requests_expire_and_queues_are_bounded() {
+    let (speech, events) = fake_queue(Duration::from_millis(150));
+    speech.set_policy(SpeechPolicy { max_monologue: 1, coalesce_alerts: false, ..SpeechPolicy::default() }).await;
+
+    speech.say_user("long").await;
+    speech.say_with_ttl(SpeechKind::Alert, "old", Duration::from_millis(50)).await;
+    speech.say_alert("fresh").await;
+    for text in ["m1", "m2", "m3"] {
+        speech.say_monologue(text).await;
+    }
+    flushed(&speech).await;
+
+    assert_eq!(*events.lock().unwrap(), vec![played("long"), played("fresh"), played("m3")]);
+    let stats = speech.stats().await;
+    assert_eq!(stats.dropped(SpeechKind::Alert, DropReason::Expired), 1);
+    assert_eq!(stats.dropped(SpeechKind::Monologue, DropReason::Overflow), 2);
+    assert_eq!(stats.total_dropped(), 3);
+}
+
+#[tokio::test]
+async fn events_are_broadcast_and_flush_waits_for_the_queue() {
+    let (speech, events) = fake_queue(Duration::from_millis(50));
+    let mut rx = speech.subscribe();
+
+    speech.say_user("a").await;
+    until(&events, |e| e.contains(&played("a"))).await;
+    speech.say_alert("b").await;
+    speech.say_alert("b").await;
+    flushed(&speech).await;
+
+    let mut seen = Vec::new();
+    while let Ok(event) = rx.try_recv() {
+        seen.push(event);
+    }
+    let text = |t: &str| t.to_string();
+    assert_eq!(
+        seen,
+        vec![
+            SpeechEvent::Enqueued { kind: SpeechKind::User, text: text("a") },
+            SpeechEvent::Started { kind: SpeechKind::User, text: text("a") },
+            SpeechEvent::Enqueued { kind: SpeechKind::Alert, text: text("b") },
+            SpeechEvent::Dropped { kind: SpeechKind::Alert, text: text("b"), reason: DropReason::Duplicate },
+            SpeechEvent::Finished { kind: SpeechKind::User, text: text("a") },
+            SpeechEvent::Started { kind: SpeechKind::Alert, text: text("b") },
+            SpeechEvent::Finished { kind: SpeechKind::Alert, text: text("b") },
+        ]
+    );
+    let stats = speech.stats().await;
+    assert_eq!(stats.counts(SpeechKind::User), KindCounts { enqueued: 1, started: 1, finished: 1, dropped: 0 });
+    assert_eq!(stats.counts(SpeechKind::Alert), KindCounts { enqueued: 1, started: 1, finished: 1, dropped: 1 });
+}
+
+#[tokio::test]
+async fn shutdown_cancel_stops_speaking_and_drops_the_rest() {
+    let (speech, events) = fake_queue(Duration::from_secs(5));
+
+    speech.say_alert("long").await;
+    speech.say_monologue("later").await;
+    until(&events, |e| e.contains(&played("long"))).await;
+    tokio::time::timeout(Duration::from_secs(1), speech.shutdown(ShutdownMode::Cancel))
+        .await
+        .expect("cancel waited for the clip");
+    speech.say_user("after").await;
+    flushed(&speech).await;
+    // 停止は再生スレッドが次に見たときに届く
+    until(&events, |e| e.contains(&SinkEvent::Stop)).await;
+
+    assert_eq!(*events.lock().unwrap(), vec![played("long"), SinkEvent::Stop]);
+    let stats = speech.stats().await;
+    assert_eq!(stats.dropped(SpeechKind::Alert, DropReason::Cancelled), 1);
+    assert_eq!(stats.dropped(SpeechKind::Monologue, DropReason::Cancelled), 1);
+}
+
+#[tokio::test]
+async fn shutdown_finish_speaks_everything_first() {
+    let (speech, events) = fake_queue(Duration::from_millis(50));
+
+    speech.say_user("one").await;
+    speech.say_alert("two").await;
+    speech.shutdown(ShutdownMode::Finish).await;
+
+    assert_eq!(*events.lock().unwrap(), vec![played("one"), played("two")]);
+    assert_eq!(speech.stats().await.total_dropped(), 0);
+}
+
+#[tokio::test]
+async fn quiet_for_defers_alerts_and_drops_monologues_but_answers_the_user() {
+    let (speech, events) = fake_queue(Duration::from_millis(50));
+    let quiet = Duration::from_millis(300);
+    let started = std::time::Instant::now();
+    speech.quiet_for(quiet).await;
+    assert!(speech.is_quiet().await);
+
+    speech.say_monologue("mono").await;
+    speech.say_alert("alert").await;
+    speech.say_user("reply").await;
+    until(&events, |e| e.contains(&played("reply"))).await;
+
+    // 静かな時間が終われば、溜めていた通知を読む（それまでは溜めたまま）
+    flushed(&speech).await;
+    assert!(started.elapsed() >= quiet);
+    assert!(!speech.is_quiet().await);
+    assert_eq!(*events.lock().unwrap(), vec![played("reply"), played("alert")]);
+    assert_eq!(speech.stats().await.dropped(SpeechKind::Monologue, DropReason::Quiet), 1);
+}
+
+#[tokio::test]
+async fn quiet_schedule_can_show_alerts_as_text_only() {
+    let (speech, events) = fake_queue(Duration::from_millis(50));
+    let schedule: QuietSchedule =
+        serde_json::from_str(r#"{"weekday": ["00:00-00:00"], "weekend": ["00:00-00:00"], "alerts": "text"}"#).unwrap();
+    speech.set_quiet_schedule(schedule).await;
+
+    speech.say_alert("alert").await;
+    speech.say_user("reply").await;
+    flushed(&speech).await;
+
+    assert_eq!(*events.lock().unwrap(), vec![played("reply")]);
+    assert_eq!(speech.stats().await.counts(SpeechKind::Alert).finished, 1);
+}
+
+// 声の一覧を持ち、話者の番号を付けて「音」にする
+struct NamedVoiceEngine;
+
+impl TtsEngine for NamedVoiceEngine {
+    fn name(&self) -> &str {
+        "named"
+    }
+
+    fn synthesize<'a>(&'a self, text: &'a str, voice: &'a VoiceSpec) -> TtsFuture<'a, Vec<u8>> {
+        Box::pin(async move { Ok(format!("{}:{}", voice.speaker, text).into_bytes()) })
+    }
+
+    fn voices(&self) -> TtsFuture<'_, Vec<Voice>> {
+        Box::pin(async { Ok(vec![Voice { id: 2, name: "四国めたん".into(), style: "ノーマル".into() }]) })
+    }
+}
+
+#[tokio::test]
+async fn text_only_alerts_do_not_hide_named_voices() {
+    let sink = FakeSink::new(Duration::from_millis(20));
+    let events = sink.events();
+    let config: VoiceConfig = serde_json::from_str(r#"{"speaker": "四国めたん"}"#).unwrap();
+    let speech = SpeechQueue::with_player(
+        Arc::new(NamedVoiceEngine),
+        config,
+        Player::with_sink(sink),
+        Duration::from_secs(0),
+        Duration::from_secs(0),
+    );
+    let schedule: QuietSchedule =
+        serde_json::from_str(r#"{"weekday": ["00:00-00:00"], "weekend": ["00:00-00:00"], "alerts": "text"}"#).unwrap();
+    speech.set_quiet_schedule(schedule).await;
+
+    // 最初に声を決めるのが文字だけの通知でも、あとの応答は名前で選んだ声で喋る
+    speech.say_alert("alert").await;
+    flushed(&speech).await;
+    speech.say_user("reply").await;
+    flushed(&speech).await;
+
+    assert_eq!(*events.lock().unwrap(), vec![played("2:reply")]);
+}
+
+#[tokio::test]
+async fn quiet_for_caps_oversized_durations() {
+    let (speech, _) = fake_queue(Duration::from_millis(50));
+    speech.quiet_for(Duration::MAX).await;
+    assert!(speech.is_quiet().await);
+    assert!(speech.holds_alerts().await);
+
+    speech.quiet_for(Duration::ZERO).await;
+    assert!(!speech.is_quiet().await);
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use common::{StubResponse, StubServer};
+use kotonoha_core::playback::{Player, WavFileSink};
+use kotonoha_core::reading::ReadingDictionary;
+use kotonoha_core::speech::{SpeechKind, SpeechQueue};
+use kotonoha_core::transport::HttpClient;
+use kotonoha_core::tts::{TtsEngine, VoiceConfig, VoiceResolver};
+use kotonoha_core::voicevox::{self, VoiceVoxEngine};
+use std::sync::Arc;
+use std::time::Duration;
+
+const SPEAKERS: &str = r#"[
+    {"name":"四国めたん","speaker_uuid":"a","styles":[{"name":"ノーマル","id":2},{"name":"ツンツン","id":6}],"version":"0.14.0"},
+    {"name":"春日部つむぎ","speaker_uuid":"b","styles":[{"name":"ノーマル","id":8}],"version":"0.14.0"}
+]"#;
+
+#[tokio::test]
+async fn alerts_use_the_named_style_and_faster_louder_voice() {
+    let server = StubServer::start(vec![
+        StubResponse::json(200, SPEAKERS),
+        StubResponse::json(200, r#"{"accent_phrases":[],"speedScale":1.0,"pitchScale":0.0,"volumeScale":1.0}"#),
+        StubResponse::wav(),
+    ])
+    .await;
+    let engine = VoiceVoxEngine::new(HttpClient::live(), &server.url);
+    let config: VoiceConfig = serde_json::from_str(
+        r#"{"speaker": "四国めたん/ツンツン", "alert": {"speed_scale": 1.3, "volume_scale": 1.4}, "user": {"speaker": "8"}}"#,
+    )
+    .unwrap();
+    let mut resolver = VoiceResolver::new(config);
+
+    let alert = resolver.voice_for(&engine, SpeechKind::Alert, 3).await;
+    assert_eq!(alert.speaker, 6);
+    // 一覧は一度だけ取りに行く
+    assert_eq!(resolver.voice_for(&engine, SpeechKind::User, 3).await.speaker, 8);
+
+    let audio = engine.synthesize("期限が近いです", &alert).await.unwrap();
+    assert!(audio.starts_with(b"RIFF"));
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert!(requests[0].request_line.starts_with("GET /speakers"));
+    assert!(requests[1].request_line.contains("speaker=6"));
+    let query: serde_json::Value = serde_json::from_str(&requests[2].body).unwrap();
+    assert_eq!(query["speedScale"], 1.3);
+    assert_eq!(query["volumeScale"], 1.4);
+    assert_eq!(query["pitchScale"], 0.0);
+}
+
+// 24kHz・16bit・モノラルで ms ミリ秒分の無音
+fn silent_wav(ms: u32) -> Vec<u8> {
+    let data_len = 48 * ms;
+    let mut wav = b"RIFF".to_vec();
+    wav.extend((36 + data_len).to_le_bytes());
+    wav.extend(b"WAVEfmt ");
+    wav.extend(16u32.to_le_bytes());
+    wav.extend(1u16.to_le_bytes()); // PCM
+    wav.extend(1u16.to_le_bytes()); // モノラル
+    wav.extend(24000u32.to_le_bytes());
+    wav.extend(48000u32.to_le_bytes()); // byte rate
+    wav.extend(2u16.to_le_bytes());
+    wav.extend(16u16.to_le_bytes());
+    wav.extend(b"data");
+    wav.extend(data_len.to_le_bytes());
+    wav.extend(vec![0u8; data_len as usize]);
+    wav
+}
+
+#[tokio::test]
+async fn headless_queue_writes_each_utterance_with_an_index() {
+    let audio = silent_wav(1500);
+    let server = StubServer::start(vec![
+        StubResponse::json(200, r#"{"accent_phrases":[],"speedScale":1.0}"#),
+        StubResponse { body: audio.clone(), ..StubResponse::wav() },
+    ])
+    .await;
+    let dir = std::env::temp_dir().join(format!("speech_audio_{}", uuid::Uuid::new_v4()));
+    let speech = SpeechQueue::with_player(
+        Arc::new(VoiceVoxEngine::new(HttpClient::live(), &server.url)),
+        VoiceConfig::default(),
+        Player::with_sink(WavFileSink::new(&dir)),
+        Duration::from_secs(0),
+        Duration::from_secs(0),
+    );
+
+    speech.say_alert("期限が近いです").await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let records = WavFileSink::read_index(&dir);
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].text, "期限が近いです");
+    assert_eq!(records[0].kind, Some(SpeechKind::Alert));
+    assert_eq!(records[0].engine, "voicevox");
+    assert_eq!(records[0].duration_ms, Some(1500));
+    assert_eq!(std::fs::read(dir.join(&records[0].file)).unwrap(), audio);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+fn query_with_mora(text: &str, pitch: f64) -> StubResponse {
+    let body = serde_json::json!({
+        "accent_phrases": [{"moras": [{"text": text, "vowel": "a", "vowel_length": 0.1, "pitch": pitch}], "accent": 1, "pause_mora": null}],
+        "speedScale": 1.0,
+        "prePhonemeLength": 0.1
+    });
+    StubResponse::json(200, &body.to_string())
+}
+
+#[tokio::test]
+async fn markup_becomes_audio_query_edits() {
+    let server = StubServer::start(vec![query_with_mora("ア", 5.0), query_with_mora("イ", 5.5), StubResponse::wav()]).await;
+    let engine = VoiceVoxEngine::new(HttpClient::live(), &server.url);
+
+    let text = r#"<break time="200ms"/>9:00から<break time="1s"/><emphasis>main.rs</emphasis>"#;
+    engine.synthesize(text, &kotonoha_core::tts::VoiceSpec::speaker(8)).await.unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert!(requests[0].request_line.contains(&format!("text={}", urlencode("9時から"))));
+    assert!(requests[1].request_line.contains(&format!("text={}", urlencode("main ドット アールエス"))));
+
+    let query: serde_json::Value = serde_json::from_str(&requests[2].body).unwrap();
+    let phrases = query["accent_phrases"].as_array().unwrap();
+    assert_eq!(phrases.len(), 2);
+    assert_eq!(phrases[0]["pause_mora"]["vowel"], "pau");
+    assert_eq!(phrases[0]["pause_mora"]["vowel_length"], 1.0);
+    assert!(phrases[1]["moras"][0]["pitch"].as_f64().unwrap() > 5.5);
+    assert!((query["prePhonemeLength"].as_f64().unwrap() - 0.3).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn readings_are_registered_in_the_user_dictionary() {
+    let registered = r#"{
+        "u-1": {"surface": "ｍａｉｎ．ｒｓ", "pronunciation": "メインドットアールエス", "accent_type": 0},
+        "u-2": {"surface": "ｇｉｔ", "pronunciation": "ジーアイティー", "accent_type": 0}
+    }"#;
+    let server = StubServer::start(vec![
+        StubResponse::json(200, registered),
+        StubResponse::json(200, r#""u-3""#),
+        StubResponse::json(204, ""),
+    ])
+    .await;
+    let dictionary = ReadingDictionary::new([
+        ("main.rs".to_string(), "めいんどっとあーるえす".to_string()),
+        ("git".to_string(), "ギット".to_string()),
+        ("Kotonoha".to_string(), "コトノハ".to_string()),
+    ]);
+
+    let changed = voicevox::sync_user_dict(&HttpClient::live(), &server.url, &dictionary).await.unwrap();
+    assert_eq!(changed, 2);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert!(requests[0].request_line.starts_with("GET /user_dict "));
+    // 長い表記から順に処理する
+    assert!(requests[1].request_line.starts_with("POST /user_dict_word?surface=Kotonoha"));
+    assert!(requests[2].request_line.starts_with("PUT /user_dict_word/u-2?surface=git"));
+    assert!(requests[2].request_line.contains(&urlencode("ギット")));
+}
+
+// reqwest がクエリに載せるときと同じ符号化
+fn urlencode(text: &str) -> String {
+    let url = reqwest::Url::parse_with_params("http://localhost/", &[("q", text)]).unwrap();
+    url.query().unwrap().trim_start_matches("q=").to_string()
+}
+
+#[tokio::test]
+async fn rejected_input_is_not_an_outage() {
+    let server = StubServer::start(vec![
+        StubResponse::json(422, r#"{"detail":[{"msg":"invalid text"}]}"#),
+        StubResponse::json(503, r#"{"detail":"busy"}"#),
+    ])
+    .await;
+    let engine = VoiceVoxEngine::new(HttpClient::live(), &server.url);
+    let voice = kotonoha_core::tts::VoiceSpec::speaker(8);
+
+    let rejected = engine.synthesize("？？？", &voice).await.unwrap_err();
+    assert!(!rejected.is_outage(), "{}", rejected);
+    let busy = engine.synthesize("こんにちは", &voice).await.unwrap_err();
+    assert!(busy.is_outage(), "{}", busy);
+}